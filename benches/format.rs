@@ -0,0 +1,159 @@
+//! Benchmarks of the formatter on generated SQL, through the library API.
+//!
+//! To see what a change does to formatting speed, save a baseline on the
+//! commit before it and compare the change against that:
+//!
+//! ```text
+//! git switch main && cargo bench --bench format -- --save-baseline main
+//! git switch my-branch && cargo bench --bench format -- --baseline main
+//! ```
+//!
+//! Criterion prints the change in time for each benchmark and says whether
+//! it is outside the noise. A single benchmark can be picked by name, as in
+//! `cargo bench --bench format -- insert`.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use sql_fmt::counts::StatementCounts;
+use sql_fmt::{format_file_content, statements, FormatOptions, SourceKind};
+
+/// A multi-row INSERT of `rows` rows of `columns` values, mixing numbers,
+/// strings of uneven length, NULLs and booleans so the grid has work to do.
+fn insert(rows: usize, columns: usize) -> String {
+    let names: Vec<String> = (0..columns).map(|column| format!("column_{}", column)).collect();
+    let mut sql = format!("INSERT INTO measurements ({}) VALUES\n", names.join(", "));
+    for row in 0..rows {
+        let values: Vec<String> = (0..columns)
+            .map(|column| match column % 4 {
+                0 => (row * 7919 + column).to_string(),
+                1 => format!("'{}'", "x".repeat((row + column) % 13)),
+                2 if row % 5 == 0 => "NULL".to_string(),
+                2 => format!("{}.{}", row % 1000, column),
+                _ => (row % 2 == 0).to_string().to_uppercase(),
+            })
+            .collect();
+        let end = if row + 1 == rows { ";\n" } else { ",\n" };
+        let _ = write!(sql, "({}){}", values.join(","), end);
+    }
+    sql
+}
+
+/// `count` short statements of the kinds a hand-written script mixes.
+fn mixed(count: usize) -> String {
+    let mut sql = String::new();
+    for i in 0..count {
+        let _ = match i % 5 {
+            0 => writeln!(sql, "insert into users (id,name,email) values ({},'user{}','user{}@example.com');", i, i, i),
+            1 => writeln!(sql, "select id,name from users where id={} and active=true order by name;", i),
+            2 => writeln!(sql, "update users set name='renamed{}',updated_at=now() where id={};", i, i),
+            3 => writeln!(sql, "delete from sessions where user_id={} and expires_at<now();", i),
+            _ => writeln!(sql, "create table t{} (id integer primary key,label varchar(40) not null,created_at timestamp);", i),
+        };
+    }
+    sql
+}
+
+/// A CREATE TABLE with `columns` columns of assorted types and constraints.
+fn wide_table(columns: usize) -> String {
+    let types = ["INTEGER", "VARCHAR(255)", "TIMESTAMP WITH TIME ZONE", "NUMERIC(12,4)", "BOOLEAN", "TEXT[]"];
+    let definitions: Vec<String> = (0..columns)
+        .map(|column| {
+            let constraint = match column % 3 {
+                0 => " NOT NULL",
+                1 => " DEFAULT 0",
+                _ => "",
+            };
+            format!("c{} {}{}", column, types[column % types.len()], constraint)
+        })
+        .collect();
+    format!("CREATE TABLE wide (id BIGINT PRIMARY KEY, {});\n", definitions.join(", "))
+}
+
+/// A migration of the usual shape: tables, indexes, seed data and grants,
+/// with comments and blank lines between them.
+fn migration() -> String {
+    let mut sql = String::from("-- Schema for the billing service\n\n");
+    for table in 0..20 {
+        let _ = write!(
+            sql,
+            "create table invoices_{t} (\n  id bigserial primary key,\n  customer_id bigint not null references customers(id),\n  amount numeric(12,2) not null default 0,\n  currency char(3) not null,\n  issued_at timestamp with time zone default now(),\n  note text\n);\n\ncreate index invoices_{t}_customer on invoices_{t} (customer_id);\n\n",
+            t = table
+        );
+        sql.push_str(&insert(50, 6).replace("measurements", &format!("invoices_{}", table)));
+        let _ = write!(sql, "\nalter table invoices_{t} add column paid_at timestamp;\ngrant select, insert on invoices_{t} to billing;\n\n", t = table);
+    }
+    sql
+}
+
+/// Writes `count` generated files, a mix of the workloads above, to a fresh
+/// directory under the system's temporary directory and returns their paths.
+fn generated_files(count: usize) -> Vec<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("sql-fmt-bench-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("temporary directory is writable");
+    (0..count)
+        .map(|i| {
+            let path = dir.join(format!("{:04}.sql", i));
+            let sql = match i % 3 {
+                0 => insert(100, 8),
+                1 => mixed(40),
+                _ => migration(),
+            };
+            fs::write(&path, sql).expect("temporary directory is writable");
+            path
+        })
+        .collect()
+}
+
+/// Formats `sql` as a file's statements, the way a `.sql` file's are.
+fn format_statements(sql: &str, options: &FormatOptions) -> String {
+    let mut counts = StatementCounts::default();
+    statements::format_statements(sql, options, &mut counts, &mut Vec::new(), &mut Vec::new()).expect("generated SQL formats")
+}
+
+fn benchmarks(c: &mut Criterion) {
+    let options = FormatOptions::default();
+    let mut group = c.benchmark_group("format");
+
+    let workloads = [
+        ("insert_1000_rows_20_columns", insert(1000, 20)),
+        ("mixed_500_statements", mixed(500)),
+        ("mixed_10000_statements", mixed(10_000)),
+        ("create_table_300_columns", wide_table(300)),
+    ];
+    for (name, sql) in &workloads {
+        group.throughput(Throughput::Bytes(sql.len() as u64));
+        group.bench_function(*name, |b| b.iter(|| format_statements(black_box(sql), &options)));
+    }
+
+    let migration = migration();
+    group.throughput(Throughput::Bytes(migration.len() as u64));
+    group.bench_function("migration_file", |b| {
+        b.iter(|| format_file_content(black_box(&migration), &options, SourceKind::Sql, &mut Vec::new(), &mut Vec::new()).expect("generated SQL formats"))
+    });
+
+    // Reading included, as a run over a directory of files does it; the
+    // results aren't written back, so every iteration has the same work
+    let files = generated_files(200);
+    group.throughput(Throughput::Elements(files.len() as u64));
+    group.bench_function("directory_200_files", |b| {
+        b.iter(|| {
+            for path in &files {
+                let content = fs::read_to_string(path).expect("generated file is readable");
+                black_box(format_file_content(&content, &options, SourceKind::Sql, &mut Vec::new(), &mut Vec::new()).expect("generated SQL formats"));
+            }
+        })
+    });
+    if let Some(dir) = files.first().and_then(|path| path.parent()) {
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
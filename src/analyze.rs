@@ -0,0 +1,187 @@
+use std::fmt::Write;
+use std::fs;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::counts::StatementCounts;
+use crate::dml;
+use crate::error::{arity_message, FormatError};
+use crate::output::Output;
+use crate::report::string;
+use crate::statements::{self, split_statements};
+use crate::{Cli, InputFile, OutputFormat};
+
+/// What `--analyze` found out about one file, which it never modifies.
+pub struct Analysis<'f> {
+    file: &'f InputFile,
+    outcome: Result<FileHealth, FormatError>,
+}
+
+struct FileHealth {
+    /// Every statement, by kind, whether or not it would be reformatted
+    kinds: StatementCounts,
+    /// Statements of a kind sql-fmt doesn't count separately
+    other: usize,
+    /// Statements that end in a semicolon
+    terminated: usize,
+    /// Where the file stops balancing, as `statements::imbalance` sees it
+    imbalance: Option<(usize, &'static str)>,
+    /// Everything else worth a look, each starting with its line
+    findings: Vec<String>,
+}
+
+impl FileHealth {
+    fn statements(&self) -> usize {
+        self.kinds.by_kind().iter().map(|(_, count)| count).sum::<usize>() + self.other
+    }
+
+    fn problems(&self) -> usize {
+        self.findings.len() + usize::from(self.imbalance.is_some())
+    }
+}
+
+/// Scans each of `files` for `--analyze`.
+pub fn analyze_files(files: &[InputFile]) -> Vec<Analysis<'_>> {
+    files.iter().map(|file| Analysis { file, outcome: analyze_file(file) }).collect()
+}
+
+/// Whether any file has findings, or couldn't be read.
+pub fn has_findings(analyses: &[Analysis]) -> bool {
+    analyses.iter().any(|analysis| analysis.outcome.as_ref().map_or(true, |health| health.problems() > 0))
+}
+
+fn analyze_file(file: &InputFile) -> Result<FileHealth, FormatError> {
+    let content = String::from_utf8(fs::read(&file.path)?).map_err(|_| FormatError::InvalidUtf8)?;
+    let line_of = |offset: usize| content[..offset].matches('\n').count() + 1;
+
+    let mut health = FileHealth {
+        kinds: StatementCounts::default(),
+        other: 0,
+        terminated: 0,
+        imbalance: statements::imbalance(&content).map(|(offset, problem)| (line_of(offset), problem)),
+        findings: Vec::new(),
+    };
+    for range in split_statements(&content) {
+        let text = &content[range.clone()];
+        match statements::statement_kind(text) {
+            Some(kind) => health.kinds.record(kind),
+            None => health.other += 1,
+        }
+        if text.ends_with(';') {
+            health.terminated += 1;
+        }
+        if let Some(row) = dml::unclosed_row(text) {
+            health.findings.push(format!("line {}: VALUES row is never closed", line_of(range.start + row)));
+        }
+        for mismatch in dml::arity_mismatches(text) {
+            health.findings.push(format!("line {}: {}", line_of(range.start + mismatch.offset), arity_message(mismatch.row, mismatch.expected, mismatch.actual)));
+        }
+    }
+
+    Ok(health)
+}
+
+/// Prints a table with a row per file, then each finding on a line of its
+/// own:
+///
+/// ```text
+/// File             Statements  Semicolons  Balanced  Findings  Kinds
+/// db/001_init.sql          12          12  yes              0  2 CREATE, 10 INSERT
+/// db/002_seed.sql           3           2  no               2  3 INSERT
+///
+/// db/002_seed.sql: line 4: unclosed quote
+/// db/002_seed.sql: line 2: row 3 of the INSERT has 2 values, but its column list has 3
+/// ```
+fn print_human(analyses: &[Analysis], cli: &Cli) {
+    let out = Output::new(cli);
+    let paths: Vec<String> = analyses.iter().map(|analysis| analysis.file.path.display().to_string()).collect();
+    let width = paths.iter().map(|path| path.width()).chain([4]).max().unwrap_or(0);
+
+    out.info(format_args!("{:<width$}  Statements  Semicolons  Balanced  Findings  Kinds", "File", width = width));
+    for (analysis, path) in analyses.iter().zip(&paths) {
+        let padding = " ".repeat(width - path.width());
+        match &analysis.outcome {
+            Ok(health) => {
+                let kinds: Vec<String> = health.kinds.by_kind()
+                    .iter()
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(kind, count)| format!("{} {}", count, kind))
+                    .chain((health.other > 0).then(|| format!("{} other", health.other)))
+                    .collect();
+                out.info(format_args!(
+                    "{}{}  {:>10}  {:>10}  {:<8}  {:>8}  {}",
+                    path, padding, health.statements(), health.terminated,
+                    if health.imbalance.is_some() { "no" } else { "yes" },
+                    health.problems(), kinds.join(", "),
+                ));
+            },
+            Err(e) => out.info(format_args!("{}{}  {}", path, padding, e)),
+        }
+    }
+
+    let mut first = true;
+    for (analysis, path) in analyses.iter().zip(&paths) {
+        let Ok(health) = &analysis.outcome else { continue };
+        let imbalance = health.imbalance.map(|(line, problem)| format!("line {}: {}", line, problem));
+        for finding in imbalance.iter().chain(&health.findings) {
+            if first {
+                out.info("");
+                first = false;
+            }
+            out.info(format_args!("{}: {}", path, finding));
+        }
+    }
+}
+
+/// Prints the analysis as one JSON document:
+///
+/// ```json
+/// {
+///   "files": [
+///     {
+///       "path": "db/002_seed.sql",
+///       "statements": { "insert": 3, ..., "copy": 0, "other": 0 },
+///       "semicolons": 2,
+///       "balanced": false,
+///       "findings": ["line 4: unclosed quote"],
+///       "error": null
+///     }
+///   ]
+/// }
+/// ```
+fn print_json(analyses: &[Analysis], cli: &Cli) {
+    let mut json = String::from("{\n  \"files\": [");
+    for (i, analysis) in analyses.iter().enumerate() {
+        json.push_str(if i == 0 { "\n" } else { ",\n" });
+        let path = string(&analysis.file.path.display().to_string());
+        match &analysis.outcome {
+            Ok(health) => {
+                let kinds: Vec<String> = health.kinds.by_kind()
+                    .iter()
+                    .map(|(kind, count)| format!("\"{}\": {}", kind.to_ascii_lowercase(), count))
+                    .chain([format!("\"other\": {}", health.other)])
+                    .collect();
+                let imbalance = health.imbalance.map(|(line, problem)| format!("line {}: {}", line, problem));
+                let findings: Vec<String> = imbalance.iter().chain(&health.findings).map(|finding| string(finding)).collect();
+                let _ = write!(
+                    json,
+                    "    {{\n      \"path\": {},\n      \"statements\": {{ {} }},\n      \"semicolons\": {},\n      \"balanced\": {},\n      \"findings\": [{}],\n      \"error\": null\n    }}",
+                    path, kinds.join(", "), health.terminated, health.imbalance.is_none(), findings.join(", "),
+                );
+            },
+            Err(e) => {
+                let _ = write!(json, "    {{\n      \"path\": {},\n      \"error\": {}\n    }}", path, string(&e.to_string()));
+            },
+        }
+    }
+    json.push_str(if analyses.is_empty() { "]\n}" } else { "\n  ]\n}" });
+    Output::new(cli).document(json);
+}
+
+/// Prints `analyses` the way `--output` asks for.
+pub fn print(analyses: &[Analysis], cli: &Cli) {
+    match cli.output {
+        OutputFormat::Human => print_human(analyses, cli),
+        OutputFormat::Json => print_json(analyses, cli),
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::report::string;
+use crate::FormatOptions;
+
+/// What `--cache` remembers between runs: for each file the formatter last
+/// left formatted, a hash of its content. A file whose content still hashes
+/// the same needs no formatting.
+///
+/// The cache is only valid for the version and options that wrote it, so
+/// both go into a key stored alongside the entries; a cache with another key
+/// is thrown away.
+pub struct Cache {
+    path: PathBuf,
+    key: String,
+    files: HashMap<String, String>,
+    /// Entries recorded during this run, on top of `files`
+    updated: Mutex<HashMap<String, String>>,
+}
+
+impl Cache {
+    /// Loads the cache at `path` for a run with `options`. A cache that is
+    /// missing, unreadable, or was written by another version or with other
+    /// options starts out empty, so the run formats every file.
+    pub fn load(path: &Path, options: &FormatOptions) -> Self {
+        let key = hash(format!("{} {:?}", env!("CARGO_PKG_VERSION"), options).as_bytes());
+        let files = fs::read_to_string(path)
+            .ok()
+            .and_then(|content| parse(&content))
+            .filter(|(stored_key, _)| *stored_key == key)
+            .map(|(_, files)| files)
+            .unwrap_or_default();
+        Cache { path: path.to_path_buf(), key, files, updated: Mutex::new(HashMap::new()) }
+    }
+
+    /// Whether `content` is what the formatter left `path` as last time.
+    pub fn is_formatted(&self, path: &Path, content: &[u8]) -> bool {
+        self.files.get(&entry_name(path)).is_some_and(|stored| *stored == hash(content))
+    }
+
+    /// Remembers that `path` is formatted with `content`.
+    pub fn record(&self, path: &Path, content: &[u8]) {
+        self.updated.lock().unwrap().insert(entry_name(path), hash(content));
+    }
+
+    /// Writes the cache back to its file, creating the directory it goes in.
+    pub fn save(self) -> io::Result<()> {
+        let mut files = self.files;
+        files.extend(self.updated.into_inner().unwrap());
+        let mut names: Vec<&String> = files.keys().collect();
+        names.sort();
+
+        let mut json = format!("{{\n  \"key\": {},\n  \"files\": {{", string(&self.key));
+        for (i, name) in names.iter().enumerate() {
+            json.push_str(if i == 0 { "\n" } else { ",\n" });
+            let _ = write!(json, "    {}: {}", string(name), string(&files[*name]));
+        }
+        json.push_str(if names.is_empty() { "}\n}\n" } else { "\n  }\n}\n" });
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, json)
+    }
+}
+
+/// The name `path` is stored under: its absolute path where it can be
+/// resolved, so runs from different directories share entries.
+fn entry_name(path: &Path) -> String {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().into_owned()
+}
+
+/// A 64-bit FNV-1a hash of `bytes`, in hex. Unlike `std`'s hashers it is
+/// the same in every build, which a cache that outlives the process needs.
+fn hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Reads back a cache file as [`Cache::save`] writes it: the key, then one
+/// `"name": "hash"` line per file. Anything else makes the whole file invalid.
+fn parse(content: &str) -> Option<(String, HashMap<String, String>)> {
+    let mut lines = content.lines().map(str::trim);
+    if lines.next()? != "{" {
+        return None;
+    }
+    let (name, key, _) = pair(lines.next()?)?;
+    if name != "key" {
+        return None;
+    }
+    let files_line = lines.next()?;
+    if files_line == "\"files\": {}" {
+        return (lines.next()? == "}").then_some((key, HashMap::new()));
+    }
+    if files_line != "\"files\": {" {
+        return None;
+    }
+
+    let mut files = HashMap::new();
+    for line in lines.by_ref() {
+        if line == "}" {
+            break;
+        }
+        let (name, hash, _) = pair(line)?;
+        files.insert(name, hash);
+    }
+    (lines.next()? == "}").then_some((key, files))
+}
+
+/// Splits a `"name": "value",` line into its two strings and whatever
+/// follows them.
+fn pair(line: &str) -> Option<(String, String, &str)> {
+    let (name, rest) = unquote(line)?;
+    let (value, rest) = unquote(rest.strip_prefix(':')?.trim_start())?;
+    Some((name, value, rest))
+}
+
+/// Reads a JSON string, as written by [`string`], from the start of `text`
+/// and returns it along with the text after it.
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &text[i + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'u' => {
+                    let digits: String = (0..4).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?);
+                },
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory holding one SQL file and the cache for it.
+    fn scratch(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sql-fmt-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("schema \"v1\".sql");
+        fs::write(&file, "SELECT 1;\n").unwrap();
+        (dir.clone(), file, dir.join("cache").join("sql-fmt.json"))
+    }
+
+    #[test]
+    fn recorded_file_is_a_hit_until_its_content_changes() {
+        let (dir, file, path) = scratch("hit");
+        let options = FormatOptions::default();
+        let cache = Cache::load(&path, &options);
+        assert!(!cache.is_formatted(&file, b"SELECT 1;\n"));
+        cache.record(&file, b"SELECT 1;\n");
+        cache.save().unwrap();
+
+        let cache = Cache::load(&path, &options);
+        assert!(cache.is_formatted(&file, b"SELECT 1;\n"));
+        assert!(!cache.is_formatted(&file, b"SELECT 2;\n"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn other_options_or_a_damaged_file_invalidate_the_cache() {
+        let (dir, file, path) = scratch("invalidate");
+        let cache = Cache::load(&path, &FormatOptions::default());
+        cache.record(&file, b"SELECT 1;\n");
+        cache.save().unwrap();
+
+        let other = FormatOptions { max_width: 80, ..FormatOptions::default() };
+        assert!(!Cache::load(&path, &other).is_formatted(&file, b"SELECT 1;\n"));
+        assert!(Cache::load(&path, &FormatOptions::default()).is_formatted(&file, b"SELECT 1;\n"));
+
+        let saved = fs::read_to_string(&path).unwrap();
+        fs::write(&path, saved.replace("\"files\": {", "\"files\": [")).unwrap();
+        assert!(!Cache::load(&path, &FormatOptions::default()).is_formatted(&file, b"SELECT 1;\n"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
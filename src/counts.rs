@@ -0,0 +1,122 @@
+use clap::ValueEnum;
+
+/// The kinds of statement counted separately in reports, which are also
+/// the formatters `--only` and `--skip` pick from.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum StatementKind {
+    #[value(name = "inserts")]
+    Insert,
+    #[value(name = "creates")]
+    Create,
+    #[value(name = "selects")]
+    Select,
+    #[value(name = "updates")]
+    Update,
+    #[value(name = "deletes")]
+    Delete,
+    #[value(name = "alters")]
+    Alter,
+    #[value(name = "drops")]
+    Drop,
+    #[value(name = "truncates")]
+    Truncate,
+    #[value(name = "grants")]
+    Grant,
+    #[value(name = "revokes")]
+    Revoke,
+    #[value(name = "merges")]
+    Merge,
+    #[value(name = "copies")]
+    Copy,
+}
+
+impl StatementKind {
+    /// The keyword the kind is named after, as in the summary.
+    pub fn name(self) -> &'static str {
+        match self {
+            StatementKind::Insert => "INSERT",
+            StatementKind::Create => "CREATE",
+            StatementKind::Select => "SELECT",
+            StatementKind::Update => "UPDATE",
+            StatementKind::Delete => "DELETE",
+            StatementKind::Alter => "ALTER",
+            StatementKind::Drop => "DROP",
+            StatementKind::Truncate => "TRUNCATE",
+            StatementKind::Grant => "GRANT",
+            StatementKind::Revoke => "REVOKE",
+            StatementKind::Merge => "MERGE",
+            StatementKind::Copy => "COPY",
+        }
+    }
+}
+
+/// Number of statements of each kind that formatting changed.
+#[derive(Clone, Copy, Default)]
+pub struct StatementCounts {
+    pub insert: usize,
+    pub create: usize,
+    pub select: usize,
+    pub update: usize,
+    pub delete: usize,
+    pub alter: usize,
+    pub drop: usize,
+    pub truncate: usize,
+    pub grant: usize,
+    pub revoke: usize,
+    pub merge: usize,
+    pub copy: usize,
+    /// Statements left as written for being over `--max-statement-size`
+    pub oversized: usize,
+}
+
+impl StatementCounts {
+    pub fn record(&mut self, kind: StatementKind) {
+        match kind {
+            StatementKind::Insert => self.insert += 1,
+            StatementKind::Create => self.create += 1,
+            StatementKind::Select => self.select += 1,
+            StatementKind::Update => self.update += 1,
+            StatementKind::Delete => self.delete += 1,
+            StatementKind::Alter => self.alter += 1,
+            StatementKind::Drop => self.drop += 1,
+            StatementKind::Truncate => self.truncate += 1,
+            StatementKind::Grant => self.grant += 1,
+            StatementKind::Revoke => self.revoke += 1,
+            StatementKind::Merge => self.merge += 1,
+            StatementKind::Copy => self.copy += 1,
+        }
+    }
+
+    pub fn add(&mut self, other: StatementCounts) {
+        self.insert += other.insert;
+        self.create += other.create;
+        self.select += other.select;
+        self.update += other.update;
+        self.delete += other.delete;
+        self.alter += other.alter;
+        self.drop += other.drop;
+        self.truncate += other.truncate;
+        self.grant += other.grant;
+        self.revoke += other.revoke;
+        self.merge += other.merge;
+        self.copy += other.copy;
+        self.oversized += other.oversized;
+    }
+
+    pub fn by_kind(&self) -> [(&'static str, usize); 12] {
+        [
+            ("INSERT", self.insert),
+            ("CREATE", self.create),
+            ("SELECT", self.select),
+            ("UPDATE", self.update),
+            ("DELETE", self.delete),
+            ("ALTER", self.alter),
+            ("DROP", self.drop),
+            ("TRUNCATE", self.truncate),
+            ("GRANT", self.grant),
+            ("REVOKE", self.revoke),
+            ("MERGE", self.merge),
+            ("COPY", self.copy),
+        ]
+    }
+}
@@ -0,0 +1,852 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::lexer::{item_comments, render, split_top_level, strip_terminator, take_trailing_comments, tokenize, top_level, Token, TokenKind, Words};
+use crate::select;
+use crate::statements::INDENT;
+use crate::{FormatOptions, TypeCase};
+
+/// Keywords that can follow `ADD` when it adds something other than a column.
+const ADD_NON_COLUMN: [&str; 10] = ["CONSTRAINT", "PRIMARY", "FOREIGN", "UNIQUE", "CHECK", "INDEX", "KEY", "FULLTEXT", "SPATIAL", "PARTITION"];
+
+/// Formats `ALTER TABLE name action, action, ...;` with each action on its own
+/// indented line, and the names, types and constraints of added columns
+/// aligned as in `CREATE TABLE`:
+///
+/// ```sql
+/// ALTER TABLE users
+///     ADD COLUMN email VARCHAR(255) NOT NULL,
+///     ADD COLUMN age   INT;
+/// ```
+///
+/// A statement with a single action stays on one line. `widths` lines the
+/// added columns up with those of neighbouring statements, as worked out by
+/// [`aligned_column_widths`]. Returns `None` for anything it doesn't
+/// recognize, or that contains comments.
+pub fn format_alter_table(tokens: &[Token], options: &FormatOptions, widths: Option<ColumnWidths>) -> Option<String> {
+    let alter = AlterTable::parse(tokens)?;
+    let columns: Vec<Option<Column>> = alter.actions.iter().map(|action| added_column(action, options.type_case)).collect();
+    let widths = widths.unwrap_or_else(|| ColumnWidths::of(columns.iter().flatten(), options.align_defaults));
+    let lines: Vec<String> = alter.actions
+        .iter()
+        .zip(&columns)
+        .map(|(action, column)| column.as_ref().map_or_else(|| render(action), |column| column.aligned(&widths)))
+        .collect();
+
+    if lines.len() == 1 {
+        return Some(format!("{} {}{}", alter.header, lines[0], alter.terminator));
+    }
+    Some(format!("{}\n{}{}{}", alter.header, INDENT, lines.join(&format!(",\n{}", INDENT)), alter.terminator))
+}
+
+/// Works out, for each of the `statements` in `sql`, the widths the columns
+/// it adds are padded to when it lines up with its neighbours. Consecutive
+/// `ALTER TABLE` statements on the same table with nothing but whitespace
+/// and comments between them take part, so that a run of them reads like a
+/// table definition:
+///
+/// ```sql
+/// ALTER TABLE users ADD COLUMN email      VARCHAR(255) NOT NULL;
+/// ALTER TABLE users ADD COLUMN created_at TIMESTAMP    DEFAULT now();
+/// ```
+pub fn aligned_column_widths(sql: &str, statements: &[Range<usize>], options: &FormatOptions) -> Vec<Option<ColumnWidths>> {
+    let parsed: Vec<Option<(String, Vec<Column>)>> = statements.iter()
+        .map(|range| {
+            let tokens = tokenize(&sql[range.clone()]);
+            let alter = AlterTable::parse(&tokens)?;
+            let columns: Vec<Column> = alter.actions.iter().filter_map(|action| added_column(action, options.type_case)).collect();
+            (!columns.is_empty()).then_some((alter.header, columns))
+        })
+        .collect();
+
+    let mut widths = vec![None; statements.len()];
+    let mut run_start = 0;
+    for i in 0..=statements.len() {
+        let continues = i > run_start
+            && i < statements.len()
+            && matches!((&parsed[i - 1], &parsed[i]), (Some((previous, _)), Some((header, _))) if previous == header)
+            && tokenize(&sql[statements[i - 1].end..statements[i].start]).iter().all(Token::is_trivia);
+        if continues {
+            continue;
+        }
+
+        // A run of one has nothing to line up with
+        if i - run_start > 1 {
+            let run = ColumnWidths::of(parsed[run_start..i].iter().flatten().flat_map(|(_, columns)| columns), options.align_defaults);
+            for slot in &mut widths[run_start..i] {
+                *slot = Some(run);
+            }
+        }
+        run_start = i;
+    }
+
+    widths
+}
+
+/// An `ALTER TABLE` statement, split into its header and its actions.
+struct AlterTable<'t, 'a> {
+    /// `ALTER TABLE [IF EXISTS] [ONLY] name`
+    header: String,
+    actions: Vec<&'t [Token<'a>]>,
+    /// The index of the first action's first token
+    start: usize,
+    terminator: &'static str,
+}
+
+impl<'t, 'a> AlterTable<'t, 'a> {
+    fn parse(tokens: &'t [Token<'a>]) -> Option<Self> {
+        if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+            return None;
+        }
+        let (body, terminator) = strip_terminator(tokens);
+
+        let mut words = Words::new(body);
+        words.expect(&["ALTER", "TABLE"])?;
+        words.skip(&["IF", "EXISTS"]);
+        words.skip(&["ONLY"]);
+        let header_end = words.object_name()?;
+
+        let actions: Vec<&[Token]> = split_top_level(&body[header_end..], ",");
+        if actions.iter().any(|action| action.iter().all(Token::is_trivia)) {
+            return None;
+        }
+        Some(AlterTable { header: render(&body[..header_end]), actions, start: header_end, terminator })
+    }
+}
+
+/// Keywords that start a clause after the column list of `CREATE INDEX`.
+const INDEX_CLAUSES: [&str; 6] = ["INCLUDE", "NULLS", "WITH", "TABLESPACE", "WHERE", "USING"];
+
+/// Formats `CREATE [UNIQUE] INDEX [CONCURRENTLY] [IF NOT EXISTS] [name] ON
+/// table [USING method] (columns)` with the column list on the first line and
+/// each trailing clause (`INCLUDE`, `WITH`, `WHERE`, ...) on its own line:
+///
+/// ```sql
+/// CREATE UNIQUE INDEX idx_users_email_lower ON users (LOWER(email), created_at DESC)
+///     WHERE deleted_at IS NULL;
+/// ```
+///
+/// If the first line would be longer than `max_width`, the columns go one
+/// per line instead.
+pub fn format_create_index(tokens: &[Token], max_width: usize) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+
+    let mut words = Words::new(body);
+    words.expect(&["CREATE"])?;
+    words.skip(&["UNIQUE"]);
+    words.expect(&["INDEX"])?;
+    words.skip(&["CONCURRENTLY"]);
+    words.skip(&["IF", "NOT", "EXISTS"]);
+    if !words.skip(&["ON"]) {
+        words.object_name()?;
+        words.expect(&["ON"])?;
+    }
+    words.skip(&["ONLY"]);
+    words.object_name()?;
+    if words.skip(&["USING"]) {
+        words.next()?;
+    }
+
+    let open = words.peek()?.0;
+    let close = words.skip_group()?;
+    let columns: Vec<String> = split_top_level(&body[open + 1..close], ",").into_iter().map(render).collect();
+    if columns.iter().any(String::is_empty) {
+        return None;
+    }
+    let clauses = split_clauses(&body[close + 1..], &INDEX_CLAUSES)?;
+
+    let header = render(&body[..open]);
+    let one_line = format!("{} ({})", header, columns.join(", "));
+    let mut formatted = if one_line.width() <= max_width || columns.len() == 1 {
+        one_line
+    } else {
+        format!("{} (\n{}{}\n)", header, INDENT, columns.join(&format!(",\n{}", INDENT)))
+    };
+    for clause in clauses {
+        formatted.push('\n');
+        formatted.push_str(INDENT);
+        formatted.push_str(&clause);
+    }
+    formatted.push_str(terminator);
+
+    Some(formatted)
+}
+
+/// Formats `CREATE [OR REPLACE] [MATERIALIZED] VIEW name [(columns)] AS
+/// query` with the query formatted by [`select::format_query`] and indented
+/// one level under the header:
+///
+/// ```sql
+/// CREATE OR REPLACE VIEW active_users AS
+///     SELECT id, name
+///     FROM users
+///     WHERE deleted_at IS NULL;
+/// ```
+///
+/// A trailing `WITH [NO] DATA` or `WITH ... CHECK OPTION` goes on its own
+/// line. Returns `None` if the query can't be formatted.
+pub fn format_create_view(tokens: &[Token], options: &FormatOptions) -> Option<String> {
+    let (body, terminator) = strip_terminator(tokens);
+
+    // CREATE [OR REPLACE] [TEMP | TEMPORARY] [RECURSIVE] [MATERIALIZED] VIEW [IF NOT EXISTS] name
+    let mut words = Words::new(body);
+    words.expect(&["CREATE"])?;
+    words.skip(&["OR", "REPLACE"]);
+    let _ = words.skip(&["TEMP"]) || words.skip(&["TEMPORARY"]);
+    words.skip(&["RECURSIVE"]);
+    words.skip(&["MATERIALIZED"]);
+    words.expect(&["VIEW"])?;
+    words.skip(&["IF", "NOT", "EXISTS"]);
+    words.object_name()?;
+
+    // [(columns)] [WITH (options)] AS
+    words.skip_group();
+    if words.skip(&["WITH"]) {
+        words.skip_group()?;
+    }
+    let (as_index, _) = words.next().filter(|(_, token)| token.is_keyword("AS"))?;
+
+    format_created_query(body, as_index + 1, terminator, options)
+}
+
+/// Formats the part of a `CREATE VIEW` or `CREATE TABLE ... AS` statement
+/// from `query_start` on: the query indented under the header, which stays
+/// on one line, and a trailing `WITH [NO] DATA` or `WITH ... CHECK OPTION`
+/// on a line of its own.
+fn format_created_query(body: &[Token], query_start: usize, terminator: &str, options: &FormatOptions) -> Option<String> {
+    // WITH [NO] DATA, WITH [CASCADED | LOCAL] CHECK OPTION
+    let tail_start = body[query_start..]
+        .iter()
+        .rposition(|token| token.is_keyword("WITH"))
+        .map(|index| query_start + index)
+        .filter(|&index| {
+            let mut tail = Words::new(&body[index..]);
+            tail.expect(&["WITH"]).is_some()
+                && (tail.skip(&["DATA"]) || tail.skip(&["NO", "DATA"]) || {
+                    let _ = tail.skip(&["CASCADED"]) || tail.skip(&["LOCAL"]);
+                    tail.skip(&["CHECK", "OPTION"])
+                })
+                && tail.peek().is_none()
+        })
+        .unwrap_or(body.len());
+
+    let query = select::format_query(&body[query_start..tail_start], options, options.max_width.saturating_sub(INDENT.len()))?;
+    let mut formatted = render(&body[..query_start]);
+    for line in query.lines() {
+        formatted.push('\n');
+        formatted.push_str(INDENT);
+        formatted.push_str(line);
+    }
+    if tail_start < body.len() {
+        formatted.push('\n');
+        formatted.push_str(&render(&body[tail_start..]));
+    }
+    formatted.push_str(terminator);
+
+    Some(formatted)
+}
+
+/// Words that can come between `CREATE` and `TABLE`.
+const TABLE_MODIFIERS: [&str; 5] = ["TEMP", "TEMPORARY", "UNLOGGED", "GLOBAL", "LOCAL"];
+
+/// Keywords that start a table constraint rather than a column definition.
+const TABLE_CONSTRAINTS: [&str; 10] = ["CONSTRAINT", "PRIMARY", "FOREIGN", "UNIQUE", "CHECK", "EXCLUDE", "KEY", "INDEX", "FULLTEXT", "LIKE"];
+
+/// Keywords that start the constraints of a column definition, after its
+/// type.
+const COLUMN_CONSTRAINTS: [&str; 15] = [
+    "NOT", "NULL", "DEFAULT", "PRIMARY", "UNIQUE", "REFERENCES", "CHECK", "CONSTRAINT", "GENERATED", "COLLATE", "AUTO_INCREMENT",
+    "AUTOINCREMENT", "COMMENT", "ON", "IDENTITY",
+];
+
+/// Formats `CREATE TABLE name (definition, ...)` with one column or
+/// constraint per indented line and the column names, types and
+/// constraints aligned:
+///
+/// ```sql
+/// CREATE TABLE orders (
+///     id     BIGINT         PRIMARY KEY,
+///     price  DECIMAL(10, 2) NOT NULL,
+///     status VARCHAR(20)    CHECK (status IN ('new', 'paid')),
+///     UNIQUE (id, status)
+/// ) ENGINE = InnoDB;
+/// ```
+///
+/// With `options.align_defaults`, the `DEFAULT` of each column lines up
+/// too, when more than half of the columns have one. With
+/// `options.align_constraints`, the constraint names, `FOREIGN KEY` columns
+/// and `REFERENCES` targets of foreign keys line up, except on a line that
+/// would get too long:
+///
+/// ```sql
+///     CONSTRAINT fk_user  FOREIGN KEY (user_id)  REFERENCES users(id)  ON DELETE CASCADE,
+///     CONSTRAINT fk_order FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE SET NULL,
+///     CHECK (total >= 0)
+/// ```
+///
+/// Table options after the closing parenthesis stay on its line, or go one
+/// per line below it if that line would be longer than `options.max_width`.
+/// Definitions are split at top-level commas only, so the commas of
+/// `DECIMAL(10, 2)`, `ENUM('a', 'b')` or a `CHECK` expression stay where
+/// they are. Table constraints keep their place and take no part in the
+/// alignment.
+///
+/// `CREATE TABLE name AS query` gets its query indented under the header,
+/// as a view does. A `--` comment at the end of a definition stays there,
+/// lined up with the others under `options.align_trailing_comments`.
+/// Returns `None` for anything it doesn't recognize, or that contains any
+/// other comment.
+pub fn format_create_table(tokens: &[Token], options: &FormatOptions) -> Option<String> {
+    let (tokens, comments) = take_trailing_comments(tokens)?;
+    let (body, terminator) = strip_terminator(&tokens);
+    let mut words = create_table_header(body)?;
+
+    // CREATE TABLE name [(columns)] AS query
+    let mut query = words.clone();
+    query.skip_group();
+    if let Some((as_index, _)) = query.next().filter(|(_, token)| token.is_keyword("AS")) {
+        if !comments.is_empty() {
+            return None;
+        }
+        return format_created_query(body, as_index + 1, terminator, options);
+    }
+
+    let open = words.peek()?.0;
+    let close = words.skip_group()?;
+
+    let definitions: Vec<&[Token]> = split_top_level(&body[open + 1..close], ",");
+    if definitions.iter().any(|definition| definition.iter().all(Token::is_trivia)) {
+        return None;
+    }
+    let mut start = open + 1;
+    let spans: Vec<Range<usize>> = definitions
+        .iter()
+        .map(|definition| {
+            let span = start..start + definition.len();
+            start = span.end + 1;
+            span
+        })
+        .collect();
+    let definition_comments = item_comments(body, &spans, &comments)?;
+    let columns: Vec<Option<Column>> = definitions.iter().map(|definition| Column::parse(definition, options.type_case)).collect();
+    let widths = ColumnWidths::of(columns.iter().flatten(), options.align_defaults);
+
+    // Foreign keys line up on their parts, unless that makes one too long
+    let foreign_keys: Vec<Option<ForeignKey>> = definitions
+        .iter()
+        .map(|definition| ForeignKey::parse(definition).filter(|_| options.align_constraints && INDENT.len() + render(definition).width() <= options.max_width))
+        .collect();
+    let constraint_width = foreign_keys.iter().flatten().map(|key| key.constraint.width()).max().unwrap_or(0);
+    let key_width = foreign_keys.iter().flatten().map(|key| key.columns.width()).max().unwrap_or(0);
+    let references_width = foreign_keys.iter().flatten().map(|key| key.references.width()).max().unwrap_or(0);
+
+    let mut lines: Vec<String> = definitions
+        .iter()
+        .zip(&columns)
+        .zip(&foreign_keys)
+        .map(|((definition, column), foreign_key)| {
+            if let Some(key) = foreign_key {
+                let line = key.aligned(constraint_width, key_width, references_width);
+                return if INDENT.len() + line.width() <= options.max_width { line } else { render(definition) };
+            }
+            column.as_ref().map_or_else(|| render(definition), |column| column.aligned(&widths))
+        })
+        .collect();
+    let last = lines.len() - 1;
+    for line in &mut lines[..last] {
+        line.push(',');
+    }
+    select::end_with_comments(&mut lines, &definition_comments, INDENT.len(), options);
+
+    // Table options share the closing line, or go one per line if too long
+    let table_options = render(&body[close + 1..]);
+    let tail = if table_options.is_empty() {
+        String::new()
+    } else if format!(") {}{}", table_options, terminator).width() <= options.max_width {
+        format!(" {}", table_options)
+    } else {
+        format!("\n{}", split_table_options(&body[close + 1..]).join("\n"))
+    };
+    Some(format!("{} (\n{}{}\n){}{}", render(&body[..open]), INDENT, lines.join(&format!("\n{}", INDENT)), tail, terminator))
+}
+
+/// Consumes `CREATE [OR REPLACE] [TEMPORARY | UNLOGGED | ...] TABLE [IF NOT
+/// EXISTS] name` at the start of `body`, and returns the words after it.
+fn create_table_header<'t, 'a>(body: &'t [Token<'a>]) -> Option<Words<'t, 'a>> {
+    let mut words = Words::new(body);
+    words.expect(&["CREATE"])?;
+    words.skip(&["OR", "REPLACE"]);
+    while TABLE_MODIFIERS.iter().any(|modifier| words.skip(&[modifier])) {}
+    words.expect(&["TABLE"])?;
+    words.skip(&["IF", "NOT", "EXISTS"]);
+    words.object_name()?;
+    Some(words)
+}
+
+/// Keywords that start a table option after the definitions of `CREATE
+/// TABLE`, such as MySQL's `ENGINE=InnoDB`, Postgres's `PARTITION BY RANGE
+/// (created_at)` and SQLite's `WITHOUT ROWID`.
+const TABLE_OPTIONS: [&str; 18] = [
+    "ENGINE", "DEFAULT", "CHARSET", "CHARACTER", "COLLATE", "AUTO_INCREMENT", "COMMENT", "ROW_FORMAT", "PARTITION", "WITH",
+    "WITHOUT", "STRICT", "TABLESPACE", "INHERITS", "ON", "USING", "SERVER", "OPTIONS",
+];
+
+/// The options after the definitions of `CREATE TABLE`, each rendered on
+/// its own. `DEFAULT` belongs with the option it comes before, as in
+/// `DEFAULT CHARSET=utf8mb4`.
+fn split_table_options(tokens: &[Token]) -> Vec<String> {
+    let words: Vec<usize> = top_level(tokens, 0).into_iter().filter(|&i| tokens[i].kind == TokenKind::Word).collect();
+    let mut starts: Vec<usize> = words
+        .iter()
+        .enumerate()
+        .filter(|&(n, &i)| {
+            TABLE_OPTIONS.iter().any(|keyword| tokens[i].is_keyword(keyword)) && (n == 0 || !tokens[words[n - 1]].is_keyword("DEFAULT"))
+        })
+        .map(|(_, &i)| i)
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    starts.push(tokens.len());
+    starts.windows(2).map(|pair| render(&tokens[pair[0]..pair[1]])).filter(|option| !option.is_empty()).collect()
+}
+
+/// A `[CONSTRAINT name] FOREIGN KEY (columns) REFERENCES table (columns)
+/// [actions]` definition of `CREATE TABLE`, split into the parts that line
+/// up.
+struct ForeignKey {
+    /// `CONSTRAINT name`, or nothing
+    constraint: String,
+    /// `FOREIGN KEY (columns)`
+    columns: String,
+    /// `REFERENCES table (columns)`
+    references: String,
+    /// `ON DELETE ...`, `MATCH ...` and the like
+    actions: String,
+}
+
+impl ForeignKey {
+    fn parse(definition: &[Token]) -> Option<Self> {
+        let mut words = Words::new(definition);
+        let start = words.peek()?.0;
+        if words.skip(&["CONSTRAINT"]) {
+            words.object_name()?;
+        }
+        let key_start = words.peek()?.0;
+        words.expect(&["FOREIGN", "KEY"])?;
+        words.skip_group()?;
+        let references_start = words.peek()?.0;
+        words.expect(&["REFERENCES"])?;
+        words.object_name()?;
+        words.skip_group();
+        let actions_start = words.peek().map_or(definition.len(), |(index, _)| index);
+
+        Some(ForeignKey {
+            constraint: render(&definition[start..key_start]),
+            columns: render(&definition[key_start..references_start]),
+            references: render(&definition[references_start..actions_start]),
+            actions: render(&definition[actions_start..]),
+        })
+    }
+
+    /// The definition with each part padded to its width. Nothing is padded
+    /// at the end of the line.
+    fn aligned(&self, constraint_width: usize, key_width: usize, references_width: usize) -> String {
+        let mut parts = Vec::new();
+        if constraint_width > 0 {
+            parts.push(pad(&self.constraint, constraint_width));
+        }
+        parts.push(pad(&self.columns, key_width));
+        if self.actions.is_empty() {
+            parts.push(self.references.clone());
+        } else {
+            parts.push(pad(&self.references, references_width));
+            parts.push(self.actions.clone());
+        }
+        parts.join(" ")
+    }
+}
+
+/// A column definition of `CREATE TABLE`, split into the parts that line
+/// up.
+struct Column {
+    /// `ADD [COLUMN] [IF NOT EXISTS]` of an `ALTER TABLE` action, or nothing
+    prefix: String,
+    name: String,
+    data_type: String,
+    /// Everything after the type, up to `DEFAULT` if `default` is set
+    constraints: String,
+    /// `DEFAULT value` and whatever follows it, when the definition has one
+    default: Option<String>,
+}
+
+impl Column {
+    /// Returns `None` for a table constraint. The type is written in
+    /// `type_case`.
+    fn parse(definition: &[Token], type_case: TypeCase) -> Option<Self> {
+        let (name_index, data_type) = column_type(definition)?;
+        let type_end = data_type.end;
+        let data_type = render_type(&definition[data_type], type_case);
+        if data_type.is_empty() {
+            return None;
+        }
+
+        let default = top_level(definition, type_end).into_iter().find(|&i| definition[i].is_keyword("DEFAULT"));
+        let (constraints, default) = match default {
+            Some(default) => (render(&definition[type_end..default]), Some(render(&definition[default..]))),
+            None => (render(&definition[type_end..]), None),
+        };
+        Some(Column { prefix: String::new(), name: definition[name_index].text.to_string(), data_type, constraints, default })
+    }
+
+    /// The definition with each part padded to `widths`. Nothing is padded
+    /// at the end of the line.
+    fn aligned(&self, widths: &ColumnWidths) -> String {
+        let constraints = match &self.default {
+            Some(default) => {
+                let before = pad(&self.constraints, widths.before_default.unwrap_or(0));
+                if before.is_empty() { default.clone() } else { format!("{} {}", before, default) }
+            },
+            None => self.constraints.clone(),
+        };
+
+        let mut parts = Vec::new();
+        if widths.prefix > 0 {
+            parts.push(pad(&self.prefix, widths.prefix));
+        }
+        if constraints.is_empty() {
+            parts.push(pad(&self.name, widths.name));
+            parts.push(self.data_type.clone());
+        } else {
+            parts.push(pad(&self.name, widths.name));
+            parts.push(pad(&self.data_type, widths.data_type));
+            parts.push(constraints);
+        }
+        parts.join(" ")
+    }
+}
+
+/// The widths the parts of a set of column definitions are padded to so
+/// that they line up.
+#[derive(Clone, Copy)]
+pub struct ColumnWidths {
+    prefix: usize,
+    name: usize,
+    data_type: usize,
+    /// The constraints before `DEFAULT`, when `DEFAULT` lines up
+    before_default: Option<usize>,
+}
+
+impl ColumnWidths {
+    /// The widths that fit all of `columns`. With `align_defaults`, `DEFAULT`
+    /// lines up when more than half of the columns have one.
+    fn of<'c>(columns: impl Iterator<Item = &'c Column> + Clone, align_defaults: bool) -> Self {
+        let with_default = columns.clone().filter(|column| column.default.is_some()).count();
+        let before_default = if align_defaults && 2 * with_default > columns.clone().count() {
+            columns.clone().filter(|column| column.default.is_some()).map(|column| column.constraints.width()).max()
+        } else {
+            None
+        };
+        ColumnWidths {
+            prefix: columns.clone().map(|column| column.prefix.width()).max().unwrap_or(0),
+            name: columns.clone().map(|column| column.name.width()).max().unwrap_or(0),
+            data_type: columns.map(|column| column.data_type.width()).max().unwrap_or(0),
+            before_default,
+        }
+    }
+}
+
+/// Formats `CREATE TYPE name AS ENUM (...)` with one value per indented line,
+/// and `CREATE TYPE name AS (field type, ...)` with one field per line and
+/// the field names and types aligned:
+///
+/// ```sql
+/// CREATE TYPE order_status AS ENUM (
+///     'pending',
+///     'paid',
+///     'shipped'
+/// );
+///
+/// CREATE TYPE address AS (
+///     street TEXT,
+///     zip    VARCHAR(10)
+/// );
+/// ```
+///
+/// Enums with up to `inline_enum_values` values stay on one line. Returns
+/// `None` for anything it doesn't recognize, or that contains comments.
+pub fn format_create_type(tokens: &[Token], inline_enum_values: usize) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+
+    let mut words = Words::new(body);
+    words.expect(&["CREATE", "TYPE"])?;
+    words.object_name()?;
+    words.expect(&["AS"])?;
+    let is_enum = words.skip(&["ENUM"]);
+    let open = words.peek()?.0;
+    let close = words.skip_group()?;
+    if words.peek().is_some() {
+        return None;
+    }
+
+    let header = render(&body[..open]);
+    let items: Vec<&[Token]> = split_top_level(&body[open + 1..close], ",");
+    if items.iter().all(|item| item.iter().all(Token::is_trivia)) {
+        return Some(format!("{} (){}", header, terminator));
+    }
+    if items.iter().any(|item| item.iter().all(Token::is_trivia)) {
+        return None;
+    }
+
+    let lines: Vec<String> = if is_enum {
+        let values: Vec<String> = items.into_iter().map(render).collect();
+        if values.len() <= inline_enum_values {
+            return Some(format!("{} ({}){}", header, values.join(", "), terminator));
+        }
+        values
+    } else {
+        // Fields line up on their type
+        let fields: Vec<(String, String)> = items
+            .into_iter()
+            .map(|field| {
+                let mut words = Words::new(field);
+                let name_end = words.object_name()?;
+                let data_type = render(&field[name_end..]);
+                (!data_type.is_empty()).then(|| (render(&field[..name_end]), data_type))
+            })
+            .collect::<Option<_>>()?;
+        let name_width = fields.iter().map(|(name, _)| name.width()).max().unwrap_or(0);
+        fields.into_iter().map(|(name, data_type)| format!("{} {}", pad(&name, name_width), data_type)).collect()
+    };
+
+    Some(format!("{} (\n{}{}\n){}", header, INDENT, lines.join(&format!(",\n{}", INDENT)), terminator))
+}
+
+/// Objects `DROP` statements are formatted for.
+const DROP_OBJECTS: [&[&str]; 5] = [&["TABLE"], &["INDEX"], &["VIEW"], &["MATERIALIZED", "VIEW"], &["SEQUENCE"]];
+
+/// Keywords that can follow the names of `DROP` and `TRUNCATE`.
+const DROP_MODIFIERS: [&str; 5] = ["CASCADE", "RESTRICT", "RESTART", "CONTINUE", "IDENTITY"];
+
+/// Most names a `DROP` or `TRUNCATE` keeps on one line.
+const MAX_INLINE_NAMES: usize = 3;
+
+/// Formats `DROP {TABLE | INDEX | VIEW | SEQUENCE} [IF EXISTS] names
+/// [CASCADE | RESTRICT]` and `TRUNCATE [TABLE] names [...]` with single
+/// spaces between words. Up to three names stay on one line; more go one per
+/// line, with any modifiers on a line of their own after them:
+///
+/// ```sql
+/// DROP TABLE IF EXISTS
+///     users,
+///     orders,
+///     order_items,
+///     payments
+/// CASCADE;
+/// ```
+///
+/// Returns `None` for anything it doesn't recognize, or that contains comments.
+pub fn format_drop(tokens: &[Token]) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+
+    let mut words = Words::new(body);
+    if words.skip(&["TRUNCATE"]) {
+        words.skip(&["TABLE"]);
+    } else {
+        words.expect(&["DROP"])?;
+        if !DROP_OBJECTS.iter().any(|object| words.skip(object)) {
+            return None;
+        }
+        words.skip(&["CONCURRENTLY"]);
+        words.skip(&["IF", "EXISTS"]);
+    }
+    let header_end = words.peek()?.0;
+
+    // name, name, ... where TRUNCATE allows `ONLY name` and `name *`
+    let mut names = Vec::new();
+    loop {
+        let (start, _) = words.peek()?;
+        words.skip(&["ONLY"]);
+        let mut end = words.object_name()?;
+        if words.peek().is_some_and(|(_, token)| token.is_symbol("*")) {
+            end = words.next()?.0 + 1;
+        }
+        names.push(render(&body[start..end]));
+        if !words.peek().is_some_and(|(_, token)| token.is_symbol(",")) {
+            break;
+        }
+        words.next();
+    }
+
+    let modifiers = &body[words.peek().map_or(body.len(), |(index, _)| index)..];
+    if !modifiers.iter().filter(|token| !token.is_trivia()).all(|token| DROP_MODIFIERS.iter().any(|keyword| token.is_keyword(keyword))) {
+        return None;
+    }
+    let modifiers = render(modifiers);
+    let inline = names.len() <= MAX_INLINE_NAMES;
+    let tail = match (modifiers.is_empty(), inline) {
+        (true, _) => terminator.to_string(),
+        (false, true) => format!(" {}{}", modifiers, terminator),
+        (false, false) => format!("\n{}{}", modifiers, terminator),
+    };
+
+    let header = render(&body[..header_end]);
+    if inline {
+        return Some(format!("{} {}{}", header, names.join(", "), tail));
+    }
+    Some(format!("{}\n{}{}{}", header, INDENT, names.join(&format!(",\n{}", INDENT)), tail))
+}
+
+/// Splits the tail of a statement into clauses, each starting at one of the
+/// top-level `keywords`; a `WHERE` clause is always the last. Returns `None`
+/// if anything comes before the first clause.
+fn split_clauses(tokens: &[Token], keywords: &[&str]) -> Option<Vec<String>> {
+    let mut starts = Vec::new();
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && keywords.iter().any(|keyword| token.is_keyword(keyword)) {
+            starts.push(i);
+            // A WHERE predicate runs to the end, whatever words it contains
+            if token.is_keyword("WHERE") {
+                break;
+            }
+        }
+    }
+
+    let first = starts.first().copied().unwrap_or(tokens.len());
+    if !tokens[..first].iter().all(Token::is_trivia) {
+        return None;
+    }
+    starts.push(tokens.len());
+    Some(starts.windows(2).map(|pair| render(&tokens[pair[0]..pair[1]])).collect())
+}
+
+/// Parses the column definition of an `ADD [COLUMN] [IF NOT EXISTS] name
+/// definition` action, keeping its keywords as the prefix.
+fn added_column(action: &[Token], type_case: TypeCase) -> Option<Column> {
+    let name_index = added_column_start(action)?;
+    let column = Column::parse(&action[name_index..], type_case)?;
+    Some(Column { prefix: render(&action[..name_index]), ..column })
+}
+
+/// Where the column definition of an `ADD [COLUMN] [IF NOT EXISTS] name
+/// definition` action starts, if it adds a column.
+fn added_column_start(action: &[Token]) -> Option<usize> {
+    let mut words = Words::new(action);
+    words.expect(&["ADD"])?;
+    let explicit = words.skip(&["COLUMN"]);
+    words.skip(&["IF", "NOT", "EXISTS"]);
+
+    let (name_index, name) = words.peek()?;
+    if !explicit && ADD_NON_COLUMN.iter().any(|keyword| name.is_keyword(keyword)) {
+        return None;
+    }
+    Some(name_index)
+}
+
+/// Where the name and data type of a column definition are: the index of
+/// the name, and the tokens after it up to the first constraint. `None` for
+/// a table constraint.
+fn column_type(definition: &[Token]) -> Option<(usize, Range<usize>)> {
+    let (name_index, name) = Words::new(definition).peek()?;
+    if name.kind == TokenKind::Word && TABLE_CONSTRAINTS.iter().any(|keyword| name.is_keyword(keyword)) {
+        return None;
+    }
+    let type_start = name_index + 1;
+    let type_end = top_level(definition, type_start)
+        .into_iter()
+        .find(|&i| COLUMN_CONSTRAINTS.iter().any(|keyword| definition[i].is_keyword(keyword)))
+        .unwrap_or(definition.len());
+    Some((name_index, type_start..type_end))
+}
+
+/// The indices of the words of `tokens`, a whole statement, that
+/// `type_case` can recase: the words outside parentheses in the data types
+/// of the columns a `CREATE TABLE` defines or an `ALTER TABLE` adds, as
+/// [`render_type`] writes them.
+pub fn type_words(tokens: &[Token]) -> Vec<usize> {
+    let (body, _) = strip_terminator(tokens);
+    let mut definitions: Vec<(usize, &[Token])> = Vec::new();
+    if let Some(alter) = AlterTable::parse(tokens) {
+        let mut start = alter.start;
+        for action in alter.actions {
+            if let Some(name_index) = added_column_start(action) {
+                definitions.push((start + name_index, &action[name_index..]));
+            }
+            start += action.len() + 1;
+        }
+    } else if let Some(mut words) = create_table_header(body) {
+        let open = words.peek().map_or(0, |(index, _)| index);
+        if let Some(close) = words.skip_group() {
+            let mut start = open + 1;
+            for definition in split_top_level(&body[open + 1..close], ",") {
+                definitions.push((start, definition));
+                start += definition.len() + 1;
+            }
+        }
+    }
+
+    let mut indices = Vec::new();
+    for (start, definition) in definitions {
+        let Some((_, data_type)) = column_type(definition) else { continue };
+        let mut depth = 0usize;
+        for i in data_type {
+            let token = &definition[i];
+            if token.is_symbol("(") {
+                depth += 1;
+            } else if token.is_symbol(")") {
+                depth = depth.saturating_sub(1);
+            } else if depth == 0 && token.kind == TokenKind::Word {
+                indices.push(start + i);
+            }
+        }
+    }
+    indices
+}
+
+/// Renders a column's data type like [`render`], with the words outside
+/// parentheses in `case`, so `double precision` and `character varying(20)`
+/// change as a whole while quoted names and type parameters stay as written.
+fn render_type(tokens: &[Token], case: TypeCase) -> String {
+    let mut text = String::new();
+    let mut depth = 0usize;
+    for token in tokens {
+        if token.kind == TokenKind::Whitespace {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            continue;
+        }
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth = depth.saturating_sub(1);
+        }
+        match case {
+            TypeCase::Upper if depth == 0 && token.kind == TokenKind::Word => text.push_str(&token.text.to_ascii_uppercase()),
+            TypeCase::Lower if depth == 0 && token.kind == TokenKind::Word => text.push_str(&token.text.to_ascii_lowercase()),
+            _ => text.push_str(token.text),
+        }
+    }
+    text.truncate(text.trim_end().len());
+    text
+}
+
+/// `text` followed by spaces up to `width` terminal columns.
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
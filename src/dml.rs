@@ -0,0 +1,974 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::lexer::{item_comments, render, split_top_level, strip_terminator, take_trailing_comments, tokenize, top_level, Token, TokenKind, Words};
+use crate::select::{self, fits, indented};
+use crate::statements::{spaced_blank_lines, INDENT};
+use crate::{Alignment, FormatOptions};
+
+/// Clauses that can follow the upsert clause of an INSERT.
+const INSERT_TAIL_CLAUSES: [&str; 2] = ["WHERE", "RETURNING"];
+
+/// MySQL's modifiers between `INSERT` and `INTO`.
+const INSERT_MODIFIERS: [&str; 4] = ["LOW_PRIORITY", "DELAYED", "HIGH_PRIORITY", "IGNORE"];
+
+/// What SQLite's `INSERT OR ...` does on a conflict.
+const INSERT_CONFLICT_ACTIONS: [&str; 5] = ["REPLACE", "ROLLBACK", "ABORT", "FAIL", "IGNORE"];
+
+/// The parts of `INSERT INTO table [(columns)] VALUES (row), ...`.
+struct Insert<'a> {
+    /// Everything before `VALUES`
+    header: &'a [Token<'a>],
+    values_keyword: &'a str,
+    /// The values of each row
+    rows: Vec<Vec<String>>,
+    /// Where each row is in the body, from its `(` to its `)`
+    spans: Vec<Range<usize>>,
+    /// Whatever follows the last row, such as `ON CONFLICT` or `RETURNING`
+    tail: &'a [Token<'a>],
+}
+
+impl<'a> Insert<'a> {
+    /// Walks the row groups after `VALUES` up to the end of the row list.
+    /// Returns `None` for an INSERT without `VALUES` (`INSERT ... SELECT`),
+    /// and for one whose rows don't all close.
+    fn parse(body: &'a [Token<'a>]) -> Option<Self> {
+        let mut words = Words::new(body);
+        skip_insert_header(&mut words)?;
+        let (values, values_keyword) = words.next().filter(|(_, token)| token.is_keyword("VALUES"))?;
+
+        let mut rows = Vec::new();
+        let mut spans = Vec::new();
+        loop {
+            let open = words.peek().filter(|(_, token)| token.is_symbol("("))?.0;
+            let close = words.skip_group()?;
+            let mut row: Vec<String> = split_top_level(&body[open + 1..close], ",").into_iter().map(render_value).collect();
+
+            // `()` is a row of no values; an empty value anywhere else, as
+            // after a trailing comma, leaves the statement as written
+            if row.len() == 1 && row[0].is_empty() {
+                row.clear();
+            }
+            if row.iter().any(String::is_empty) {
+                return None;
+            }
+            rows.push(row);
+            spans.push(open..close + 1);
+
+            if !words.peek().is_some_and(|(_, token)| token.is_symbol(",")) {
+                break;
+            }
+            words.next();
+        }
+        let tail = words.peek().map_or(body.len(), |(index, _)| index);
+
+        Some(Insert { header: &body[..values], values_keyword: values_keyword.text, rows, spans, tail: &body[tail..] })
+    }
+}
+
+/// Renders one value of a VALUES row like [`render`], but with a `::` cast
+/// kept against its value, so `'{}' :: jsonb` becomes `'{}'::jsonb`.
+fn render_value(tokens: &[Token]) -> String {
+    let is_cast = |i: usize| tokens.get(i).is_some_and(|token: &Token| token.is_symbol("::"));
+    let kept: Vec<Token> = tokens.iter()
+        .enumerate()
+        .filter(|&(i, token)| token.kind != TokenKind::Whitespace || !(is_cast(i + 1) || i > 0 && is_cast(i - 1)))
+        .map(|(_, token)| *token)
+        .collect();
+    render(&kept)
+}
+
+/// Formats `INSERT INTO table (columns) VALUES (row), ...` with `VALUES` on a
+/// line of its own and each row on its own line below it, padded so that
+/// the values of each column line up:
+///
+/// ```sql
+/// INSERT INTO users (id, name, email)
+/// VALUES
+/// (1  , 'alice', 'alice@example.com'),
+/// (22 , 'bob'  , 'bob@example.com');
+/// ```
+///
+/// Rows are walked as parenthesized groups, so a `;` or `)` inside a string
+/// never ends one. When the rows come from a query instead, the query is
+/// formatted by [`select::format_query`] and indented under the header:
+///
+/// ```sql
+/// INSERT INTO archive_orders (id, total)
+///     SELECT id, total
+///     FROM orders
+///     WHERE created < now() - interval '1 year';
+/// ```
+///
+/// `layout` lines the rows up with those of neighbouring statements, as
+/// worked out by [`aligned_layouts`]. Whatever follows the rows, such as
+/// `RETURNING`, goes on the line after them; an upsert clause is laid out as
+/// by [`format_upsert`]. With `options.compact_single_row`, an INSERT of a
+/// single row that fits within `options.max_width` stays on one line
+/// instead. A `--` comment at the end of a row stays there, lined up with
+/// the others under `options.align_trailing_comments`. Returns `None` for
+/// anything it doesn't recognize, or that contains any other comment.
+pub fn format_insert(tokens: &[Token], options: &FormatOptions, layout: Option<&GridLayout>) -> Option<String> {
+    let (tokens, comments) = take_trailing_comments(tokens)?;
+    let (body, terminator) = strip_terminator(&tokens);
+
+    let insert = Insert::parse(body);
+    let row_comments = match &insert {
+        Some(insert) => item_comments(body, &insert.spans, &comments)?,
+        None if comments.is_empty() => Vec::new(),
+        None => return None,
+    };
+    if let Some(insert) = insert.as_ref().filter(|insert| options.compact_single_row && layout.is_none() && insert.rows.len() == 1 && comments.is_empty()) {
+        let one_line = one_line(insert, terminator);
+        if fits(&one_line, options.max_width) {
+            return Some(one_line);
+        }
+    }
+
+    let (mut formatted, tail) = match insert {
+        Some(insert) => {
+            let layout = layout.cloned().unwrap_or_else(|| GridLayout::of(&insert.rows, options));
+            let mut rows = grid(&insert.rows, &layout);
+            let last = rows.len() - 1;
+            for row in &mut rows[..last] {
+                row.push(',');
+            }
+            select::end_with_comments(&mut rows, &row_comments, 0, options);
+            (format!("{}\n{}\n{}", format_header(insert.header, options), insert.values_keyword, rows.join("\n")), insert.tail)
+        },
+        None => format_default_values(body).or_else(|| format_insert_query(body, options))?,
+    };
+    if !tail.is_empty() {
+        formatted.push('\n');
+        formatted.push_str(&format_upsert_clause(tail).unwrap_or_else(|| render(tail)));
+    }
+    formatted.push_str(terminator);
+
+    Some(formatted)
+}
+
+/// A single-row INSERT on one line, with single spaces after the commas of
+/// the row. A semicolon on its own line keeps it from fitting.
+fn one_line(insert: &Insert, terminator: &str) -> String {
+    let mut line = format!("{} {} ({})", render(insert.header), insert.values_keyword, insert.rows[0].join(", "));
+    if !insert.tail.is_empty() {
+        line.push(' ');
+        line.push_str(&format_upsert_clause(insert.tail).unwrap_or_else(|| render(insert.tail)));
+    }
+    line.push_str(terminator);
+    line
+}
+
+/// `INSERT INTO table DEFAULT VALUES` on one line, and whatever follows it.
+fn format_default_values<'a, 't>(body: &'t [Token<'a>]) -> Option<(String, &'t [Token<'a>])> {
+    let mut words = Words::new(body);
+    skip_insert_header(&mut words)?;
+    words.expect(&["DEFAULT", "VALUES"])?;
+    let end = words.peek().map_or(body.len(), |(index, _)| index);
+    Some((render(&body[..end]), &body[end..]))
+}
+
+/// The header and query of `INSERT INTO table [(columns)] query`, and
+/// whatever follows the query.
+fn format_insert_query<'a, 't>(body: &'t [Token<'a>], options: &FormatOptions) -> Option<(String, &'t [Token<'a>])> {
+    let mut words = Words::new(body);
+    skip_insert_header(&mut words)?;
+    let (source, first) = words.peek()?;
+    if !first.is_keyword("SELECT") && !first.is_keyword("WITH") && !first.is_symbol("(") {
+        return None;
+    }
+
+    let end = top_level(body, source)
+        .into_iter()
+        .find(|&i| is_upsert(&body[i..]) || body[i].is_keyword("RETURNING"))
+        .unwrap_or(body.len());
+    let query = select::format_query(&body[source..end], options, options.max_width.saturating_sub(INDENT.len()))?;
+
+    Some((format!("{}\n{}", format_header(&body[..source], options), indented(&query)), &body[end..]))
+}
+
+/// `INSERT INTO table (columns)` on one line, or with the columns on lines
+/// of their own if that line is longer than `options.max_width`:
+///
+/// ```sql
+/// INSERT INTO users (
+///     id, name, email, phone, address_line_1, address_line_2, city,
+///     postal_code, country, created_at
+/// )
+/// ```
+///
+/// The columns fill each line up to the width, or go one per line with
+/// `options.insert_column_per_line`.
+fn format_header(header: &[Token], options: &FormatOptions) -> String {
+    let one_line = render(header);
+    if one_line.width() <= options.max_width {
+        return one_line;
+    }
+    let Some(Some((open, close))) = skip_insert_header(&mut Words::new(header)) else {
+        return one_line;
+    };
+    let columns: Vec<String> = split_top_level(&header[open + 1..close], ",").into_iter().map(render).collect();
+    if columns.iter().any(String::is_empty) {
+        return one_line;
+    }
+
+    let width = options.max_width.saturating_sub(INDENT.len());
+    let mut lines: Vec<String> = Vec::new();
+    for (i, column) in columns.iter().enumerate() {
+        let item = if i + 1 < columns.len() { format!("{},", column) } else { column.clone() };
+        match lines.last_mut() {
+            Some(line) if !options.insert_column_per_line && line.width() + 1 + item.width() <= width => {
+                line.push(' ');
+                line.push_str(&item);
+            },
+            _ => lines.push(item),
+        }
+    }
+    format!("{}\n{}\n){}", render(&header[..=open]), indented(&lines.join("\n")), render(&header[close + 1..]))
+}
+
+/// Consumes `INSERT INTO table [(columns)]`, or one of its variants:
+/// MySQL's `REPLACE INTO` and `INSERT [LOW_PRIORITY | ...] [IGNORE] INTO`,
+/// and SQLite's `INSERT OR REPLACE INTO` and the like, and returns the
+/// indices of the parentheses around the column list, if there is one. A
+/// parenthesized query right after the table is the rows, not a column
+/// list, and is left alone.
+fn skip_insert_header(words: &mut Words) -> Option<Option<(usize, usize)>> {
+    if !words.skip(&["REPLACE"]) {
+        words.expect(&["INSERT"])?;
+        if words.skip(&["OR"]) {
+            words.next().filter(|(_, token)| INSERT_CONFLICT_ACTIONS.iter().any(|action| token.is_keyword(action)))?;
+        } else {
+            while INSERT_MODIFIERS.iter().any(|modifier| words.skip(&[modifier])) {}
+        }
+    }
+    words.skip(&["INTO"]);
+    words.object_name()?;
+    let mut group = words.clone();
+    let (open, token) = group.next()?;
+    if !token.is_symbol("(") || group.peek().is_some_and(|(_, token)| token.is_keyword("SELECT") || token.is_keyword("WITH")) {
+        return Some(None);
+    }
+    Some(Some((open, words.skip_group()?)))
+}
+
+/// Byte offset in `text` of the first VALUES row of an INSERT that never
+/// closes its parenthesis, or a quote inside it, before the statement ends.
+pub fn unclosed_row(text: &str) -> Option<usize> {
+    let tokens = tokenize(text);
+    let mut words = Words::new(&tokens);
+    skip_insert_header(&mut words)?;
+    words.expect(&["VALUES"])?;
+
+    loop {
+        let open = words.peek().filter(|(_, token)| token.is_symbol("("))?.0;
+        if words.skip_group().is_none() {
+            return Some(tokens[..open].iter().map(|token| token.text.len()).sum());
+        }
+        words.peek().filter(|(_, token)| token.is_symbol(","))?;
+        words.next();
+    }
+}
+
+/// A VALUES row with a different number of values than its column list.
+pub struct ArityMismatch {
+    /// Byte offset of the row in its statement
+    pub offset: usize,
+    /// Position of the row in the statement, from 1
+    pub row: usize,
+    pub expected: usize,
+    pub actual: usize,
+}
+
+/// The VALUES rows of an INSERT in `text` whose number of values doesn't
+/// match its column list. An INSERT without a column list has nothing to
+/// match.
+pub fn arity_mismatches(text: &str) -> Vec<ArityMismatch> {
+    let tokens = tokenize(text);
+    let mut words = Words::new(&tokens);
+    let Some(Some((open, close))) = skip_insert_header(&mut words) else {
+        return Vec::new();
+    };
+    let expected = split_top_level(&tokens[open + 1..close], ",").len();
+    if words.expect(&["VALUES"]).is_none() {
+        return Vec::new();
+    }
+
+    let mut mismatches = Vec::new();
+    let mut row = 0;
+    while let Some((open, _)) = words.peek().filter(|(_, token)| token.is_symbol("(")) {
+        let Some(close) = words.skip_group() else {
+            break;
+        };
+        row += 1;
+        let mut values = split_top_level(&tokens[open + 1..close], ",");
+        if values.last().is_some_and(|value| value.iter().all(Token::is_trivia)) {
+            values.pop();
+        }
+        if values.len() != expected {
+            let offset = tokens[..open].iter().map(|token| token.text.len()).sum();
+            mismatches.push(ArityMismatch { offset, row, expected, actual: values.len() });
+        }
+
+        if !words.peek().is_some_and(|(_, token)| token.is_symbol(",")) {
+            break;
+        }
+        words.next();
+    }
+    mismatches
+}
+
+/// The width of each column of a VALUES grid, in terminal columns rather
+/// than bytes so that accented, CJK and emoji values line up, and whether
+/// it is right-aligned.
+#[derive(Clone)]
+pub struct GridLayout {
+    widths: Vec<usize>,
+    right: Vec<bool>,
+}
+
+impl GridLayout {
+    /// The layout that fits all of `rows`. Each column is aligned as a
+    /// whole, as decided by [`right_aligned`].
+    fn of(rows: &[Vec<String>], options: &FormatOptions) -> Self {
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for row in rows {
+            for (i, value) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell_width(value));
+            }
+        }
+        let right = (0..columns)
+            .map(|i| right_aligned(rows.iter().filter_map(|row| row.get(i)), options))
+            .collect();
+        GridLayout { widths, right }
+    }
+}
+
+/// Works out, for each of the `statements` in `sql`, the layout its VALUES
+/// grid shares with its neighbours. Consecutive INSERTs with the same header,
+/// that is into the same table with the same column list, and nothing but
+/// whitespace and comments between them are laid out over all of their rows
+/// together:
+///
+/// ```sql
+/// INSERT INTO users (id, name)
+/// VALUES
+/// (1   , 'alice');
+/// INSERT INTO users (id, name)
+/// VALUES
+/// (1024, 'bob');
+/// ```
+pub fn aligned_layouts(sql: &str, statements: &[Range<usize>], options: &FormatOptions) -> Vec<Option<GridLayout>> {
+    let parsed: Vec<Option<(String, Vec<Vec<String>>)>> = statements.iter()
+        .map(|range| {
+            let tokens = tokenize(&sql[range.clone()]);
+            if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+                return None;
+            }
+            let insert = Insert::parse(strip_terminator(&tokens).0)?;
+            Some((render(insert.header), insert.rows))
+        })
+        .collect();
+
+    let mut layouts = vec![None; statements.len()];
+    let mut run_start = 0;
+    for i in 0..=statements.len() {
+        let continues = i > run_start
+            && i < statements.len()
+            && matches!((&parsed[i - 1], &parsed[i]), (Some((previous, _)), Some((header, _))) if previous == header)
+            && tokenize(&sql[statements[i - 1].end..statements[i].start]).iter().all(Token::is_trivia);
+        if continues {
+            continue;
+        }
+
+        // A run of one has nothing to line up with
+        if i - run_start > 1 {
+            let rows: Vec<Vec<String>> = parsed[run_start..i].iter().flatten().flat_map(|(_, rows)| rows.iter().cloned()).collect();
+            let layout = GridLayout::of(&rows, options);
+            for slot in &mut layouts[run_start..i] {
+                *slot = Some(layout.clone());
+            }
+        }
+        run_start = i;
+    }
+
+    layouts
+}
+
+/// Merges each run of consecutive single-statement INSERTs with the same
+/// header (same table and column list, whitespace aside) into one multi-row
+/// INSERT, keeping the rows in order:
+///
+/// ```sql
+/// INSERT INTO users (id, name) VALUES (1, 'alice');
+/// INSERT INTO users (id, name) VALUES (2, 'bob');
+/// ```
+///
+/// becomes `INSERT INTO users (id, name) VALUES (1, 'alice'), (2, 'bob');`,
+/// laid out as a grid once formatted. A run ends at a comment, any other
+/// statement, or more than `options.merge_blank_lines` blank lines, counted
+/// as `options.blank_lines` leaves them. INSERTs with comments or anything
+/// after their rows, such as `RETURNING`, are left as they are. Returns
+/// `sql` with the runs replaced.
+pub fn merge_inserts(sql: &str, statements: &[Range<usize>], options: &FormatOptions) -> String {
+    let tokens: Vec<Vec<Token>> = statements.iter().map(|range| tokenize(&sql[range.clone()])).collect();
+    let parsed: Vec<Option<(Insert, &str)>> = tokens.iter()
+        .map(|tokens| {
+            if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+                return None;
+            }
+            let (body, terminator) = strip_terminator(tokens);
+            Insert::parse(body).filter(|insert| insert.tail.is_empty()).map(|insert| (insert, terminator))
+        })
+        .collect();
+    let same_header = |a: &Insert, b: &Insert| a.header.iter().filter(|token| !token.is_trivia()).map(|token| token.text)
+        .eq(b.header.iter().filter(|token| !token.is_trivia()).map(|token| token.text));
+
+    let mut merged = String::with_capacity(sql.len());
+    let mut copied = 0;
+    let mut run_start = 0;
+    for i in 0..=statements.len() {
+        let continues = i > run_start
+            && i < statements.len()
+            && matches!((&parsed[i - 1], &parsed[i]), (Some((previous, _)), Some((insert, _))) if same_header(previous, insert))
+            && {
+                let gap = &sql[statements[i - 1].end..statements[i].start];
+                gap.chars().all(char::is_whitespace) && spaced_blank_lines(gap, options) <= options.merge_blank_lines
+            };
+        if continues {
+            continue;
+        }
+
+        if i - run_start > 1 {
+            let run = &parsed[run_start..i];
+            let (first, _) = run[0].as_ref().expect("a run is made of INSERTs");
+            let (_, terminator) = run[run.len() - 1].as_ref().expect("a run is made of INSERTs");
+            let rows: Vec<String> = run.iter()
+                .flatten()
+                .flat_map(|(insert, _)| insert.rows.iter().map(|row| format!("({})", row.join(", "))))
+                .collect();
+            merged.push_str(&sql[copied..statements[run_start].start]);
+            merged.push_str(&format!("{}\n{}\n{}{}", render(first.header), first.values_keyword, rows.join(",\n"), terminator));
+            copied = statements[i - 1].end;
+        }
+        run_start = i;
+    }
+    merged.push_str(&sql[copied..]);
+
+    merged
+}
+
+/// Splits each INSERT with more than `batch` rows into INSERTs of at most
+/// `batch` rows each, with the same header, keeping the rows in order. Each
+/// gets a copy of whatever follows the rows, such as an upsert clause. The
+/// batches are separated by the statement's terminator, or by its entry in
+/// `delimiters` (`;` or a custom MySQL delimiter) where it has none, and the
+/// last keeps the original terminator, so one without any still has none.
+/// INSERTs with comments are left as they are. Returns `sql` with the
+/// statements replaced.
+pub fn split_inserts(sql: &str, statements: &[Range<usize>], delimiters: &[&str], batch: usize) -> String {
+    let mut split = String::with_capacity(sql.len());
+    let mut copied = 0;
+    for (range, delimiter) in statements.iter().zip(delimiters) {
+        let tokens = tokenize(&sql[range.clone()]);
+        if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+            continue;
+        }
+        let (body, terminator) = strip_terminator(&tokens);
+        let Some(insert) = Insert::parse(body).filter(|insert| insert.rows.len() > batch) else {
+            continue;
+        };
+
+        let tail = if insert.tail.is_empty() { String::new() } else { format!("\n{}", render(insert.tail)) };
+        let batches: Vec<String> = insert.rows
+            .chunks(batch)
+            .map(|rows| {
+                let rows: Vec<String> = rows.iter().map(|row| format!("({})", row.join(", "))).collect();
+                format!("{}\n{}\n{}{}", render(insert.header), insert.values_keyword, rows.join(",\n"), tail)
+            })
+            .collect();
+        split.push_str(&sql[copied..range.start]);
+        let separator = if terminator.is_empty() { delimiter } else { terminator };
+        split.push_str(&batches.join(&format!("{}\n", separator)));
+        split.push_str(terminator);
+        copied = range.end;
+    }
+    split.push_str(&sql[copied..]);
+
+    split
+}
+
+/// Lays out `rows` as a grid: each value is padded to the width of its
+/// column and all but the last of a row are followed by ` , `.
+fn grid(rows: &[Vec<String>], layout: &GridLayout) -> Vec<String> {
+    let GridLayout { widths, right } = layout;
+
+    rows.iter()
+        .map(|row| {
+            let mut line = String::from("(");
+            for (i, value) in row.iter().enumerate() {
+                let padding = " ".repeat(widths[i].saturating_sub(cell_width(value)));
+                let last = i + 1 == row.len();
+                if right[i] {
+                    line.push_str(&padding);
+                    line.push_str(value);
+                } else {
+                    line.push_str(value);
+                    if !last {
+                        line.push_str(&padding);
+                    }
+                }
+                if !last {
+                    line.push_str(" , ");
+                }
+            }
+            line.push(')');
+            line
+        })
+        .collect()
+}
+
+/// The width a value takes up in its column: that of its longest line, for
+/// a string with newlines in it. Such a value is written out as it is, and
+/// any padding goes after its closing quote.
+fn cell_width(value: &str) -> usize {
+    value.lines().map(UnicodeWidthStr::width).max().unwrap_or(0)
+}
+
+/// What a value in a VALUES row is, as far as lining it up goes.
+#[derive(Clone, Copy, PartialEq)]
+enum ValueKind {
+    Null,
+    /// A bind placeholder: `?`, `$1`, `:name` or `@name`
+    Placeholder,
+    Number,
+    /// A call to one of `--right-align-functions`: `POINT(1, 2)`
+    Function,
+    Boolean,
+    /// A quoted ISO date, with or without a time: `'2024-01-01 10:00:00'`
+    Date,
+    Text,
+}
+
+impl ValueKind {
+    fn of(value: &str, options: &FormatOptions) -> Self {
+        let starts_like_number = value.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'));
+        if value.eq_ignore_ascii_case("NULL") {
+            ValueKind::Null
+        } else if is_placeholder(value) {
+            ValueKind::Placeholder
+        } else if value.eq_ignore_ascii_case("TRUE") || value.eq_ignore_ascii_case("FALSE") {
+            ValueKind::Boolean
+        } else if starts_like_number && value.parse::<f64>().is_ok() {
+            ValueKind::Number
+        } else if is_right_aligned_call(value, &options.right_align_functions) {
+            ValueKind::Function
+        } else if is_iso_date(value) {
+            ValueKind::Date
+        } else {
+            ValueKind::Text
+        }
+    }
+
+    /// Whether a value of this kind lines up on the right of its column.
+    /// NULL and placeholders have no side of their own and follow their
+    /// column.
+    fn right_aligned(self, options: &FormatOptions) -> bool {
+        match self {
+            ValueKind::Number | ValueKind::Function => true,
+            ValueKind::Boolean => options.boolean_alignment == Alignment::Right,
+            ValueKind::Date => options.right_align_dates,
+            ValueKind::Null | ValueKind::Placeholder | ValueKind::Text => false,
+        }
+    }
+}
+
+/// Whether a column with `values` is right-aligned: most of its values
+/// other than NULL and placeholders line up on the right. A stray `'n/a'` in a column of
+/// numbers is lined up with them rather than breaking the column.
+fn right_aligned<'v>(values: impl Iterator<Item = &'v String>, options: &FormatOptions) -> bool {
+    let (mut right, mut counted) = (0, 0);
+    for kind in values.map(|value| ValueKind::of(value, options)).filter(|&kind| kind != ValueKind::Null && kind != ValueKind::Placeholder) {
+        counted += 1;
+        if kind.right_aligned(options) {
+            right += 1;
+        }
+    }
+    2 * right > counted
+}
+
+/// Whether `value` starts with one of `functions`, in any case, followed by
+/// its arguments: `POINT(1, 2)`, or `INTERVAL '1 day'` for a function-like
+/// keyword.
+fn is_right_aligned_call(value: &str, functions: &[String]) -> bool {
+    let name_end = value.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(value.len());
+    let (name, rest) = value.split_at(name_end);
+    rest.starts_with(['(', ' ', '\''])
+        && functions.iter().any(|function| function.eq_ignore_ascii_case(name))
+}
+
+/// Whether `value` is a bind placeholder: `?`, `$1`, `:name` or `@name`.
+fn is_placeholder(value: &str) -> bool {
+    let is_name = |name: &str| name.starts_with(|c: char| c.is_alphabetic() || c == '_') && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+    value == "?"
+        || value.strip_prefix('$').is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+        || value.strip_prefix(':').is_some_and(is_name)
+        || value.strip_prefix('@').is_some_and(is_name)
+}
+
+/// Whether `value` is a quoted `YYYY-MM-DD` date, optionally followed by a
+/// time.
+fn is_iso_date(value: &str) -> bool {
+    let Some(inner) = value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')) else {
+        return false;
+    };
+    let bytes = inner.as_bytes();
+    let date = bytes.len() >= 10
+        && bytes[..10].iter().enumerate().all(|(i, &b)| if i == 4 || i == 7 { b == b'-' } else { b.is_ascii_digit() });
+    date && (bytes.len() == 10 || matches!(bytes[10], b' ' | b'T'))
+}
+
+/// Formats the upsert clause of an `INSERT` that [`format_insert`] leaves
+/// alone, such as one with comments before the clause, leaving everything
+/// before the clause exactly as it was. The conflict target stays on the
+/// `ON CONFLICT` line and `DO UPDATE SET` assignments go one per line with
+/// their `=` aligned:
+///
+/// ```sql
+/// INSERT INTO counters (id, hits)
+/// -- first visit
+/// VALUES (1, 1)
+/// ON CONFLICT (id) DO UPDATE SET
+///     hits       = counters.hits + 1,
+///     updated_at = now()
+/// WHERE counters.locked = false;
+/// ```
+///
+/// `DO NOTHING` stays inline, and MySQL's `ON DUPLICATE KEY UPDATE` gets the
+/// same layout as `DO UPDATE SET`. Returns `None` for an INSERT without an
+/// upsert clause, or one with comments in it.
+pub fn format_upsert(tokens: &[Token]) -> Option<String> {
+    let (body, terminator) = strip_terminator(tokens);
+    let on = top_level(body, 0).into_iter().find(|&i| is_upsert(&body[i..]))?;
+
+    // Everything up to the clause is left as it was
+    let insert: String = body[..on].iter().map(|token| token.text).collect();
+    Some(format!("{}\n{}{}", insert.trim_end(), format_upsert_clause(&body[on..])?, terminator))
+}
+
+/// Whether `tokens` start with `ON CONFLICT` or `ON DUPLICATE KEY UPDATE`.
+fn is_upsert(tokens: &[Token]) -> bool {
+    let mut words = Words::new(tokens);
+    words.skip(&["ON", "CONFLICT"]) || words.skip(&["ON", "DUPLICATE", "KEY", "UPDATE"])
+}
+
+/// Formats an upsert clause, from its `ON` to the end of the statement.
+fn format_upsert_clause(clause: &[Token]) -> Option<String> {
+    if !is_upsert(clause) || clause.iter().any(|token| token.kind == TokenKind::Comment) {
+        return None;
+    }
+
+    let mut words = Words::new(clause);
+    let set_start = if words.skip(&["ON", "DUPLICATE", "KEY", "UPDATE"]) {
+        words.peek()?.0
+    } else {
+        let action_start = top_level(clause, 0).into_iter().find(|&i| clause[i].is_keyword("DO"))?;
+        let mut action = Words::new(&clause[action_start..]);
+        action.expect(&["DO"])?;
+        if action.skip(&["NOTHING"]) {
+            return Some(render(clause));
+        }
+        action.expect(&["UPDATE", "SET"])?;
+        action_start + action.peek()?.0
+    };
+
+    let ends: Vec<usize> = top_level(clause, set_start)
+        .into_iter()
+        .filter(|&i| INSERT_TAIL_CLAUSES.iter().any(|keyword| clause[i].is_keyword(keyword)))
+        .collect();
+    let set_end = ends.first().copied().unwrap_or(clause.len());
+
+    let mut formatted = render(&clause[..set_start]);
+    for line in format_assignments(&clause[set_start..set_end])? {
+        formatted.push('\n');
+        formatted.push_str(INDENT);
+        formatted.push_str(&line);
+    }
+    let mut bounds = ends;
+    bounds.push(clause.len());
+    for pair in bounds.windows(2) {
+        formatted.push('\n');
+        formatted.push_str(&render(&clause[pair[0]..pair[1]]));
+    }
+
+    Some(formatted)
+}
+
+/// Keywords that start a clause after the SET list of an `UPDATE`.
+const UPDATE_CLAUSES: [&str; 5] = ["FROM", "WHERE", "RETURNING", "ORDER", "LIMIT"];
+
+/// Formats `UPDATE table SET column = value, ... [FROM ...] [WHERE ...]
+/// [RETURNING ...]` with each clause on its own line, laid out as in a
+/// SELECT, and the assignments one per line with their `=` aligned:
+///
+/// ```sql
+/// UPDATE orders o
+///     JOIN customers c ON c.id = o.customer_id
+/// SET
+///     o.status     = 'flagged',
+///     o.updated_at = now()
+/// WHERE c.blocked
+/// RETURNING *;
+/// ```
+///
+/// A single assignment stays on the `SET` line. Joins in the table position
+/// (MySQL's multi-table UPDATE) go one per line like those of a `FROM`, and
+/// so do those of Postgres's `UPDATE ... FROM`. Returns `None` for anything
+/// it doesn't recognize, or that contains comments.
+pub fn format_update(tokens: &[Token], options: &FormatOptions) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment || token.kind == TokenKind::Quoted && token.text.contains('\n')) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+    Words::new(body).expect(&["UPDATE"])?;
+
+    let starts = top_level(body, 0);
+    let set = starts.iter().copied().find(|&i| body[i].is_keyword("SET"))?;
+    let mut clauses: Vec<usize> = starts.into_iter().filter(|&i| i > set && UPDATE_CLAUSES.iter().any(|keyword| body[i].is_keyword(keyword))).collect();
+
+    let assignments = format_assignments(&select::operator_spacing(&body[set + 1..clauses.first().copied().unwrap_or(body.len())], options))?;
+    let mut lines = vec![select::format_from(&body[..set], options, options.max_width)];
+    if assignments.len() == 1 {
+        lines.push(format!("{} {}", body[set].text, assignments[0]));
+    } else {
+        lines.push(format!("{}\n{}", body[set].text, indented(&assignments.join("\n"))));
+    }
+    clauses.push(body.len());
+    for pair in clauses.windows(2) {
+        lines.push(select::format_clause(&body[pair[0]..pair[1]], options, options.max_width));
+    }
+
+    Some(lines.join("\n") + terminator)
+}
+
+/// Keywords that start a clause after the target of a `DELETE`.
+const DELETE_CLAUSES: [&str; 5] = ["USING", "WHERE", "ORDER", "LIMIT", "RETURNING"];
+
+/// Formats `DELETE FROM table [USING ...] [WHERE ...] [ORDER BY ...]
+/// [LIMIT ...] [RETURNING ...]` with each clause on its own line, laid out as
+/// in a SELECT:
+///
+/// ```sql
+/// DELETE FROM sessions
+/// USING users
+/// WHERE sessions.user_id = users.id
+///   AND users.banned
+///   AND sessions.expires_at < now()
+/// RETURNING sessions.id;
+/// ```
+///
+/// Joins in the target position (MySQL's `DELETE t1 FROM t1 JOIN t2 ...`) and
+/// in `USING` go one per line like those of a `FROM`. A `USING` after a join
+/// is that join's condition, not a clause. Returns `None` for anything it
+/// doesn't recognize, or that contains comments.
+pub fn format_delete(tokens: &[Token], options: &FormatOptions) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment || token.kind == TokenKind::Quoted && token.text.contains('\n')) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+    Words::new(body).expect(&["DELETE"])?;
+
+    let starts = top_level(body, 0);
+    let first_join = starts.iter().copied().find(|&i| body[i].is_keyword("JOIN")).unwrap_or(body.len());
+    let mut clauses: Vec<usize> = Vec::new();
+    for i in starts {
+        let using = body[i].is_keyword("USING");
+        // Only the first USING, and only before any join, is the DELETE's own
+        if using && (i > first_join || clauses.iter().any(|&clause| body[clause].is_keyword("USING"))) {
+            continue;
+        }
+        if i > 0 && DELETE_CLAUSES.iter().any(|keyword| body[i].is_keyword(keyword)) {
+            clauses.push(i);
+        }
+    }
+
+    let mut lines = vec![select::format_from(&body[..clauses.first().copied().unwrap_or(body.len())], options, options.max_width)];
+    clauses.push(body.len());
+    for pair in clauses.windows(2) {
+        let clause = &body[pair[0]..pair[1]];
+        if clause[0].is_keyword("USING") {
+            lines.push(select::format_from(clause, options, options.max_width));
+        } else {
+            lines.push(select::format_clause(clause, options, options.max_width));
+        }
+    }
+
+    Some(lines.join("\n") + terminator)
+}
+
+/// Formats `MERGE INTO target USING source ON condition WHEN ... THEN action`
+/// with `USING`, `ON` and each `WHEN` on its own line, and each action
+/// indented under its `WHEN`:
+///
+/// ```sql
+/// MERGE INTO inventory AS i
+/// USING deliveries AS d
+/// ON i.sku = d.sku
+/// WHEN MATCHED THEN
+///     UPDATE SET
+///         quantity   = i.quantity + d.quantity,
+///         updated_at = now()
+/// WHEN NOT MATCHED THEN
+///     INSERT (sku, quantity) VALUES (d.sku, d.quantity);
+/// ```
+///
+/// Returns `None` for anything it doesn't recognize, or that contains comments.
+pub fn format_merge(tokens: &[Token]) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+
+    let mut words = Words::new(body);
+    words.expect(&["MERGE"])?;
+    words.skip(&["INTO"]);
+
+    let starts = top_level(body, 0);
+    let using = starts.iter().copied().find(|&i| body[i].is_keyword("USING"))?;
+    let on = starts.iter().copied().find(|&i| i > using && body[i].is_keyword("ON"))?;
+    let mut whens: Vec<usize> = starts.iter().copied().filter(|&i| i > on && body[i].is_keyword("WHEN")).collect();
+    if whens.is_empty() {
+        return None;
+    }
+
+    let mut formatted = [&body[..using], &body[using..on], &body[on..whens[0]]]
+        .iter()
+        .map(|part| render(part))
+        .collect::<Vec<_>>()
+        .join("\n");
+    whens.push(body.len());
+    for pair in whens.windows(2) {
+        let when = &body[pair[0]..pair[1]];
+        let then = top_level(when, 0).into_iter().find(|&i| when[i].is_keyword("THEN"))?;
+        formatted.push('\n');
+        formatted.push_str(&render(&when[..=then]));
+        formatted.push('\n');
+        formatted.push_str(INDENT);
+        formatted.push_str(&format_merge_action(&when[then + 1..])?);
+    }
+    formatted.push_str(terminator);
+
+    Some(formatted)
+}
+
+/// The action of a `WHEN` clause; `UPDATE SET` gets its assignments one per
+/// line, one level further in.
+fn format_merge_action(tokens: &[Token]) -> Option<String> {
+    let mut words = Words::new(tokens);
+    if words.expect(&["UPDATE", "SET"]).is_none() {
+        let action = render(tokens);
+        return (!action.is_empty()).then_some(action);
+    }
+    let set_start = words.peek()?.0;
+
+    let mut formatted = render(&tokens[..set_start]);
+    for line in format_assignments(&tokens[set_start..])? {
+        formatted.push('\n');
+        formatted.push_str(INDENT);
+        formatted.push_str(INDENT);
+        formatted.push_str(&line);
+    }
+    Some(formatted)
+}
+
+/// Formats the header of `COPY table (columns) FROM ...` like the header of
+/// an INSERT: single spaces between words and one space after each comma of
+/// the column list. The data of `COPY ... FROM stdin` is not part of the
+/// statement and never gets here. Returns `None` for `COPY (query) TO ...`,
+/// or a statement with comments.
+pub fn format_copy(tokens: &[Token]) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+
+    let mut words = Words::new(body);
+    words.expect(&["COPY"])?;
+    words.object_name()?;
+    let open = match words.peek() {
+        Some((open, token)) if token.is_symbol("(") => open,
+        _ => return Some(render(body) + terminator),
+    };
+    let close = words.skip_group()?;
+
+    let columns: Vec<String> = split_top_level(&body[open + 1..close], ",").into_iter().map(render).collect();
+    if columns.iter().any(String::is_empty) {
+        return None;
+    }
+    let rest = render(&body[close + 1..]);
+    let separator = if rest.is_empty() { "" } else { " " };
+    Some(format!("{} ({}){}{}{}", render(&body[..open]), columns.join(", "), separator, rest, terminator))
+}
+
+/// Lays out the `column = value, ...` list of a SET clause one assignment
+/// per line, with the `=` signs lined up and a comma after all but the last.
+pub fn format_assignments(tokens: &[Token]) -> Option<Vec<String>> {
+    let mut assignments = Vec::new();
+    for assignment in split_top_level(tokens, ",") {
+        let equals = top_level(assignment, 0).into_iter().find(|&i| assignment[i].is_symbol("="))?;
+        let target = render(&assignment[..equals]);
+        let value = render(&assignment[equals + 1..]);
+        if target.is_empty() || value.is_empty() {
+            return None;
+        }
+        assignments.push((target, value));
+    }
+
+    let width = assignments.iter().map(|(target, _)| target.width()).max()?;
+    let count = assignments.len();
+    Some(assignments
+        .into_iter()
+        .enumerate()
+        .map(|(i, (target, value))| {
+            let padding = " ".repeat(width - target.width());
+            format!("{}{} = {}{}", target, padding, value, if i + 1 < count { "," } else { "" })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(sql: &str, delimiter: &str) -> String {
+        let end = sql.trim_end().len();
+        let statement = 0..sql[..end].strip_suffix(delimiter).filter(|_| delimiter != ";").map_or(end, str::len);
+        split_inserts(sql, std::slice::from_ref(&statement), &[delimiter], 2)
+    }
+
+    #[test]
+    fn batches_end_in_the_terminator() {
+        let sql = "INSERT INTO t (a) VALUES (1), (2), (3);\n";
+        assert_eq!(split(sql, ";"), "INSERT INTO t (a)\nVALUES\n(1),\n(2);\nINSERT INTO t (a)\nVALUES\n(3);\n");
+    }
+
+    #[test]
+    fn statement_without_terminator_gets_none() {
+        let sql = "INSERT INTO t (a) VALUES (1), (2), (3)";
+        assert_eq!(split(sql, ";"), "INSERT INTO t (a)\nVALUES\n(1),\n(2);\nINSERT INTO t (a)\nVALUES\n(3)");
+    }
+
+    #[test]
+    fn terminator_on_its_own_line_stays_there() {
+        let sql = "INSERT INTO t (a) VALUES (1), (2), (3)\n;\n";
+        assert_eq!(split(sql, ";"), "INSERT INTO t (a)\nVALUES\n(1),\n(2)\n;\nINSERT INTO t (a)\nVALUES\n(3)\n;\n");
+    }
+
+    #[test]
+    fn batches_are_separated_by_the_custom_delimiter() {
+        let sql = "INSERT INTO t (a) VALUES (1), (2), (3)//\n";
+        assert_eq!(split(sql, "//"), "INSERT INTO t (a)\nVALUES\n(1),\n(2)//\nINSERT INTO t (a)\nVALUES\n(3)//\n");
+    }
+}
@@ -0,0 +1,37 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::report::string;
+use crate::statements::Incident;
+
+/// Saves `incident`, found in `source`, for `--debug-dump`: the statement
+/// goes to the next free `NNNN.sql` in `dir`, and a `NNNN.json` next to it
+/// records where it came from and what went wrong. Returns the path of the
+/// statement file. Numbers are claimed with `create_new`, so files formatted
+/// in parallel never overwrite each other's dumps.
+pub fn write(dir: &Path, source: &str, incident: &Incident) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let mut number = 1;
+    let (path, mut file) = loop {
+        let path = dir.join(format!("{:04}.sql", number));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => break (path, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => number += 1,
+            Err(e) => return Err(e),
+        }
+    };
+    file.write_all(incident.text.as_bytes())?;
+
+    let sidecar = format!(
+        "{{\n  \"source\": {},\n  \"line\": {},\n  \"start\": {},\n  \"end\": {},\n  \"pass\": {},\n  \"error\": {}\n}}\n",
+        string(source),
+        incident.error.line,
+        incident.span.start,
+        incident.span.end,
+        string(incident.error.kind),
+        string(&incident.error.problem),
+    );
+    fs::write(path.with_extension("json"), sidecar)?;
+    Ok(path)
+}
@@ -0,0 +1,105 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Why a file could not be formatted.
+#[derive(Debug)]
+pub enum FormatError {
+    /// Reading, backing up or writing the file failed
+    Io(io::Error),
+    /// The file is not UTF-8 and `--encoding-error fail` was given
+    InvalidUtf8,
+    /// A VALUES row doesn't close its parenthesis (or a quote inside it)
+    /// before its statement ends; the file is left alone rather than mangled
+    UnbalancedSql { line: usize },
+    /// A VALUES row has a different number of values than its column list
+    /// and `--strict` was given
+    ArityMismatch { line: usize, row: usize, expected: usize, actual: usize },
+    /// Under `--verify`, formatting the output a second time changed it,
+    /// first on this line
+    NotIdempotent { line: usize },
+    /// The formatted file would differ from the original in more than
+    /// whitespace, first at `token` on this line; the file is left untouched
+    TokensChanged { line: usize, token: String },
+    /// A `--lines` range runs past the end of the file, which has `lines`
+    /// lines
+    LinesOutOfRange { start: usize, end: usize, lines: usize },
+    /// A statement's formatter went wrong
+    Statement(StatementError),
+    /// A bug in the formatter itself
+    Internal(String),
+}
+
+/// A statement whose formatter went wrong. The statement is left as written
+/// and the rest of its file is still formatted, so this ends up as a warning
+/// in the file's report rather than as its error.
+#[derive(Clone, Debug)]
+pub struct StatementError {
+    /// The formatter that ran, named after its kind of statement
+    pub kind: &'static str,
+    /// Where the statement starts in the text that was formatted, which has
+    /// LF line endings and reflects `--merge-inserts` and `--split-inserts`
+    pub offset: usize,
+    pub line: usize,
+    pub problem: String,
+}
+
+impl FormatError {
+    /// Stable name of the variant, as used in the JSON report.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FormatError::Io(_) => "io",
+            FormatError::InvalidUtf8 => "invalid_utf8",
+            FormatError::UnbalancedSql { .. } => "unbalanced_sql",
+            FormatError::ArityMismatch { .. } => "arity_mismatch",
+            FormatError::NotIdempotent { .. } => "not_idempotent",
+            FormatError::TokensChanged { .. } => "tokens_changed",
+            FormatError::LinesOutOfRange { .. } => "lines_out_of_range",
+            FormatError::Statement(_) => "statement",
+            FormatError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Io(e) => write!(f, "{}", e),
+            FormatError::InvalidUtf8 => write!(f, "not valid UTF-8"),
+            FormatError::UnbalancedSql { line } => write!(f, "unbalanced parentheses or quotes in the VALUES row on line {}", line),
+            FormatError::ArityMismatch { line, row, expected, actual } => write!(f, "line {}: {}", line, arity_message(*row, *expected, *actual)),
+            FormatError::NotIdempotent { line } => write!(f, "formatting is not idempotent: a second pass changes line {}", line),
+            FormatError::TokensChanged { line, token } => write!(f, "formatting would change more than whitespace, starting at `{}` on line {}", token, line),
+            FormatError::LinesOutOfRange { start, end, lines } => write!(f, "--lines {}:{} is past the end of the file, which has {} lines", start, end, lines),
+            FormatError::Statement(e) => write!(f, "{}", e),
+            FormatError::Internal(message) => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl fmt::Display for StatementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: the {} formatter failed on the statement at byte {}: {}; left as written", self.line, self.kind, self.offset, self.problem)
+    }
+}
+
+/// Describes row `row` of an INSERT having `actual` values where its
+/// column list has `expected`, for both the warning and the `--strict` error.
+pub fn arity_message(row: usize, expected: usize, actual: usize) -> String {
+    format!("row {} of the INSERT has {} values, but its column list has {}", row, actual, expected)
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FormatError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(e: io::Error) -> Self {
+        FormatError::Io(e)
+    }
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Which files `--changed` / `--staged` should pick up.
+pub enum ChangeSet<'a> {
+    /// Files changed on this branch since it forked from `base`, plus any
+    /// staged, unstaged and untracked files
+    Changed { base: &'a str },
+    /// Only files in the index
+    Staged,
+}
+
+/// Lists the existing files in `changes` that `is_sql_file` accepts, as paths
+/// usable from the current directory (git reports them relative to the
+/// repository root).
+pub fn changed_sql_files(changes: ChangeSet, is_sql_file: impl Fn(&Path) -> bool) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let toplevel = git(&["rev-parse", "--show-toplevel"])
+        .map_err(|e| format!("--changed and --staged need a git repository: {}", e))?;
+    let toplevel = PathBuf::from(toplevel.trim());
+
+    let mut names = Vec::new();
+    match changes {
+        ChangeSet::Changed { base } => {
+            let range = format!("{}...HEAD", base);
+            names.extend(git(&["diff", "--name-only", "--diff-filter=ACMR", &range])?.lines().map(String::from));
+            names.extend(git(&["diff", "--name-only", "--diff-filter=ACMR", "HEAD"])?.lines().map(String::from));
+            names.extend(git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?.lines().map(String::from));
+        },
+        ChangeSet::Staged => {
+            names.extend(git(&["diff", "--name-only", "--diff-filter=ACMR", "--cached"])?.lines().map(String::from));
+        },
+    }
+
+    let cwd = std::env::current_dir()?;
+    let mut files = Vec::new();
+    for name in names {
+        let path = toplevel.join(&name);
+        if !is_sql_file(&path) || !path.is_file() {
+            continue;
+        }
+        let path = relative_to(&path, &cwd);
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// The staged content of `path`, which a commit would take even when the
+/// working tree copy has changed since.
+pub fn staged_content(path: &Path) -> io::Result<Vec<u8>> {
+    // `:./path` names the index entry relative to the current directory
+    let output = Command::new("git").arg("cat-file").arg("blob").arg(format!(":./{}", path.display())).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(output.stdout)
+}
+
+/// `path` relative to `base` when it lies inside it, otherwise unchanged.
+fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let base = base.canonicalize().unwrap_or_else(|_| base.to_path_buf());
+    let path_canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    path_canonical.strip_prefix(&base).map(Path::to_path_buf).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Runs git with `args` and returns its stdout, or its stderr as the error.
+fn git(args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git").args(args).output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string().into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::lexer::{render, split_top_level, strip_terminator, tokenize, Token, TokenKind, Words};
+use crate::statements::{spaced_blank_lines, INDENT};
+use crate::FormatOptions;
+
+/// A `GRANT` or `REVOKE` on an object, split into the parts that line up
+/// across consecutive statements.
+struct Privileges {
+    /// `GRANT` or `REVOKE [GRANT OPTION FOR]`
+    keyword: String,
+    privileges: Vec<String>,
+    /// `ON [TABLE] object, ...`
+    object: String,
+    /// `TO role, ...` or `FROM role, ...`, with any trailing options
+    grantees: String,
+    terminator: &'static str,
+}
+
+impl Privileges {
+    fn parse(tokens: &[Token]) -> Option<Self> {
+        if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+            return None;
+        }
+        let (body, terminator) = strip_terminator(tokens);
+
+        let mut words = Words::new(body);
+        let revoke = words.skip(&["REVOKE"]);
+        if revoke {
+            words.skip(&["GRANT", "OPTION", "FOR"]);
+        } else {
+            words.expect(&["GRANT"])?;
+        }
+        let header_end = words.peek()?.0;
+
+        let on = top_level_keyword(body, header_end, "ON")?;
+        let to = top_level_keyword(body, on, if revoke { "FROM" } else { "TO" })?;
+
+        Some(Privileges {
+            keyword: render(&body[..header_end]),
+            privileges: items(&body[header_end..on])?,
+            object: items(&body[on..to])?.join(", "),
+            grantees: items(&body[to..])?.join(", "),
+            terminator,
+        })
+    }
+
+    fn one_line(&self) -> String {
+        format!("{} {} {} {}{}", self.keyword, self.privileges.join(", "), self.object, self.grantees, self.terminator)
+    }
+}
+
+/// Formats `GRANT privileges ON object TO roles` and `REVOKE privileges ON
+/// object FROM roles` with single spaces between words and after commas. A
+/// statement longer than `max_width` gets its privileges one per line:
+///
+/// ```sql
+/// GRANT
+///     SELECT,
+///     INSERT,
+///     UPDATE (name, email)
+/// ON TABLE users
+/// TO app_rw WITH GRANT OPTION;
+/// ```
+///
+/// `widths` pads the privileges and the object to line up with neighbouring
+/// statements, as worked out by [`aligned_widths`]. A role membership grant
+/// (`GRANT role TO user`) only has its spacing normalized. Returns `None` for
+/// anything it doesn't recognize, or that contains comments.
+pub fn format_grant(tokens: &[Token], max_width: usize, widths: Option<(usize, usize)>) -> Option<String> {
+    let Some(grant) = Privileges::parse(tokens) else {
+        return format_membership(tokens);
+    };
+
+    if let Some((privileges_width, object_width)) = widths {
+        let privileges = format!("{} {}", grant.keyword, grant.privileges.join(", "));
+        return Some(format!("{} {} {}{}", pad(&privileges, privileges_width), pad(&grant.object, object_width), grant.grantees, grant.terminator));
+    }
+
+    let one_line = grant.one_line();
+    if one_line.width() <= max_width || grant.privileges.len() == 1 {
+        return Some(one_line);
+    }
+    Some(format!(
+        "{}\n{}{}\n{}\n{}{}",
+        grant.keyword,
+        INDENT,
+        grant.privileges.join(&format!(",\n{}", INDENT)),
+        grant.object,
+        grant.grantees,
+        grant.terminator,
+    ))
+}
+
+/// Works out, for each of the `statements` in `sql`, the widths its parts
+/// are padded to when it lines up with its neighbours. Only `GRANT` and
+/// `REVOKE` statements on an object that fit on one line take part, and only
+/// when each starts a line no more than one blank line below the previous
+/// one, counting blank lines as `options.blank_lines` leaves them:
+///
+/// ```sql
+/// GRANT SELECT                 ON TABLE users  TO app_ro;
+/// GRANT SELECT, INSERT, UPDATE ON TABLE users  TO app_rw;
+/// REVOKE DELETE                ON TABLE orders FROM app_rw;
+/// ```
+pub fn aligned_widths(sql: &str, statements: &[Range<usize>], options: &FormatOptions) -> Vec<Option<(usize, usize)>> {
+    let parts: Vec<Option<(usize, usize)>> = statements.iter()
+        .map(|range| {
+            let grant = Privileges::parse(&tokenize(&sql[range.clone()]))?;
+            if grant.one_line().width() > options.max_width {
+                return None;
+            }
+            Some((grant.keyword.width() + 1 + grant.privileges.join(", ").width(), grant.object.width()))
+        })
+        .collect();
+
+    let mut widths = vec![None; statements.len()];
+    let mut run_start = 0;
+    for i in 0..=statements.len() {
+        let continues = i > run_start
+            && i < statements.len()
+            && parts[i].is_some()
+            && parts[i - 1].is_some()
+            && adjacent(&sql[statements[i - 1].end..statements[i].start], options);
+        if continues {
+            continue;
+        }
+
+        // A run of one has nothing to line up with
+        if i - run_start > 1 {
+            let run = &parts[run_start..i];
+            let privileges_width = run.iter().flatten().map(|(privileges, _)| *privileges).max().unwrap_or(0);
+            let object_width = run.iter().flatten().map(|(_, object)| *object).max().unwrap_or(0);
+            for width in &mut widths[run_start..i] {
+                *width = Some((privileges_width, object_width));
+            }
+        }
+        run_start = i;
+    }
+
+    widths
+}
+
+/// `GRANT role, ... TO user, ...` and its `REVOKE` counterpart.
+fn format_membership(tokens: &[Token]) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment) {
+        return None;
+    }
+    let (body, terminator) = strip_terminator(tokens);
+    Some(items(body)?.join(", ") + terminator)
+}
+
+/// Whether the text between two statements puts the second on a new line,
+/// with at most one blank line in between.
+fn adjacent(gap: &str, options: &FormatOptions) -> bool {
+    gap.chars().all(char::is_whitespace) && gap.contains('\n') && spaced_blank_lines(gap, options) <= 1
+}
+
+/// Index of the first top-level `keyword` at or after `from`.
+fn top_level_keyword(tokens: &[Token], from: usize, keyword: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(from) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && token.is_keyword(keyword) {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// The comma-separated items of `tokens`, each rendered on its own. Returns
+/// `None` if any item is empty.
+fn items(tokens: &[Token]) -> Option<Vec<String>> {
+    let items: Vec<String> = split_top_level(tokens, ",").into_iter().map(render).collect();
+    if items.iter().any(String::is_empty) {
+        return None;
+    }
+    Some(items)
+}
+
+/// `text` followed by spaces up to `width` terminal columns.
+fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(text.width())))
+}
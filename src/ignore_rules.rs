@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+
+/// Name of the project-level ignore file, found in the starting directory or
+/// the nearest ancestor that has one.
+const SQLFMTIGNORE: &str = ".sqlfmtignore";
+
+/// Ignore rules for files found during discovery. The project's
+/// `.sqlfmtignore` comes first; after it, git's rules: `.gitignore` files from
+/// the repository root down to the file, `.git/info/exclude`, and the global
+/// excludes file. Git rules don't apply to files outside a git repository.
+pub struct IgnoreRules {
+    sqlfmtignore: Option<Gitignore>,
+    repo_roots: HashMap<PathBuf, Option<PathBuf>>,
+    gitignores: HashMap<PathBuf, Option<Gitignore>>,
+    global: Option<Gitignore>,
+}
+
+impl IgnoreRules {
+    /// Rules for a run from the current directory.
+    pub fn new() -> Self {
+        Self::from_dir(&std::env::current_dir().unwrap_or_default())
+    }
+
+    /// Rules using the `.sqlfmtignore` nearest to `dir`.
+    pub fn from_dir(dir: &Path) -> Self {
+        let sqlfmtignore = absolute(dir)
+            .ancestors()
+            .map(|dir| dir.join(SQLFMTIGNORE))
+            .find(|file| file.is_file())
+            .map(|file| Gitignore::new(file).0);
+        let (global, _) = Gitignore::global();
+        IgnoreRules {
+            sqlfmtignore,
+            repo_roots: HashMap::new(),
+            gitignores: HashMap::new(),
+            global: if global.is_empty() { None } else { Some(global) },
+        }
+    }
+
+    /// Returns the `.sqlfmtignore` rule that ignores `path`, if any. This is
+    /// the only check applied to files named explicitly.
+    pub fn sqlfmtignore_rule(&self, path: &Path) -> Option<String> {
+        self.sqlfmtignore_decision(&absolute(path), false).flatten()
+    }
+
+    /// Returns a description of the rule that ignores `path` (the file that
+    /// declared it and the pattern), or `None` if the file should be formatted.
+    pub fn matching_rule(&mut self, path: &Path) -> Option<String> {
+        self.rule(path, false)
+    }
+
+    /// Returns the rule that ignores the directory `dir`, so discovery can
+    /// skip everything under it. Nothing is skipped when `.sqlfmtignore` has
+    /// `!pattern` rules, since one of them could bring back a file inside.
+    pub fn matching_dir_rule(&mut self, dir: &Path) -> Option<String> {
+        if self.sqlfmtignore.as_ref().is_some_and(|sqlfmtignore| sqlfmtignore.num_whitelists() > 0) {
+            return None;
+        }
+        self.rule(dir, true)
+    }
+
+    fn rule(&mut self, path: &Path, is_dir: bool) -> Option<String> {
+        let path = path.canonicalize().ok()?;
+
+        // `!pattern` in .sqlfmtignore can bring back a file git ignores
+        if let Some(decision) = self.sqlfmtignore_decision(&path, is_dir) {
+            return decision;
+        }
+
+        let repo_root = self.repo_root(path.parent()?)?;
+
+        // More specific rules win: the nearest .gitignore first, then the
+        // repository's exclude file, then the user's global excludes
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            let relative = path.strip_prefix(current).ok()?;
+            let gitignore = self.gitignore(current.join(".gitignore"));
+            if let Some(decision) = gitignore.and_then(|gitignore| describe(gitignore.matched_path_or_any_parents(relative, is_dir))) {
+                return decision;
+            }
+            if current == repo_root {
+                break;
+            }
+            dir = current.parent();
+        }
+
+        let relative = path.strip_prefix(&repo_root).ok()?;
+        let exclude = self.gitignore(repo_root.join(".git").join("info").join("exclude"));
+        if let Some(decision) = exclude.and_then(|exclude| describe(exclude.matched_path_or_any_parents(relative, is_dir))) {
+            return decision;
+        }
+
+        let global = self.global.as_ref()?;
+        describe(global.matched_path_or_any_parents(relative, is_dir)).flatten()
+    }
+
+    fn sqlfmtignore_decision(&self, path: &Path, is_dir: bool) -> Option<Option<String>> {
+        let sqlfmtignore = self.sqlfmtignore.as_ref()?;
+        let relative = path.strip_prefix(sqlfmtignore.path()).ok()?;
+        describe(sqlfmtignore.matched_path_or_any_parents(relative, is_dir))
+    }
+
+    fn repo_root(&mut self, dir: &Path) -> Option<PathBuf> {
+        if let Some(root) = self.repo_roots.get(dir) {
+            return root.clone();
+        }
+        let root = dir.ancestors().find(|ancestor| ancestor.join(".git").exists()).map(Path::to_path_buf);
+        self.repo_roots.insert(dir.to_path_buf(), root.clone());
+        root
+    }
+
+    fn gitignore(&mut self, file: PathBuf) -> Option<&Gitignore> {
+        self.gitignores
+            .entry(file)
+            .or_insert_with_key(|file| {
+                if !file.is_file() {
+                    return None;
+                }
+                let (gitignore, _) = Gitignore::new(file);
+                Some(gitignore)
+            })
+            .as_ref()
+    }
+}
+
+/// Canonical form of `path`. The file itself doesn't have to exist (as with
+/// `--stdin-filepath`) as long as its directory does.
+fn absolute(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+    let path = std::env::current_dir().map(|cwd| cwd.join(path)).unwrap_or_else(|_| path.to_path_buf());
+    match (path.parent().and_then(|parent| parent.canonicalize().ok()), path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => path,
+    }
+}
+
+/// `Some(Some(rule))` for an ignore match, `Some(None)` for a whitelist match
+/// (`!pattern`), and `None` when the rules say nothing about the path.
+fn describe(matched: Match<&ignore::gitignore::Glob>) -> Option<Option<String>> {
+    match matched {
+        Match::None => None,
+        Match::Whitelist(_) => Some(None),
+        Match::Ignore(glob) => {
+            let source = glob.from().map(|from| from.display().to_string()).unwrap_or_else(|| "global excludes".to_string());
+            Some(Some(format!("{}: {}", source, glob.original())))
+        },
+    }
+}
@@ -0,0 +1,466 @@
+use std::ops::Range;
+
+/// What a token is, as far as formatting is concerned.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenKind {
+    /// A keyword, identifier, number or `$1`-style placeholder
+    Word,
+    /// A string literal or quoted identifier, including its quotes
+    Quoted,
+    /// A `-- line comment` (without its newline) or a `/* block comment */`
+    Comment,
+    Whitespace,
+    /// Punctuation or an operator such as `(`, `,`, `<=` or `::`
+    Symbol,
+}
+
+/// A slice of SQL text. The tokens of a text, in order, add up to exactly
+/// that text.
+#[derive(Clone, Copy, Debug)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+}
+
+impl Token<'_> {
+    /// Whether this is the word `keyword`, in any case.
+    pub fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    pub fn is_symbol(&self, symbol: &str) -> bool {
+        self.kind == TokenKind::Symbol && self.text == symbol
+    }
+
+    /// Whitespace and comments: tokens that don't change what SQL means.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+/// A cursor over the tokens of a statement that skips whitespace and
+/// comments. Positions are indices into the token slice, so callers can
+/// slice and render the original tokens around what they matched.
+#[derive(Clone)]
+pub struct Words<'t, 'a> {
+    tokens: &'t [Token<'a>],
+    next: usize,
+}
+
+impl<'t, 'a> Words<'t, 'a> {
+    pub fn new(tokens: &'t [Token<'a>]) -> Self {
+        Words { tokens, next: 0 }
+    }
+
+    /// The next significant token and its index, without consuming it.
+    pub fn peek(&self) -> Option<(usize, &'t Token<'a>)> {
+        let tokens: &'t [Token<'a>] = self.tokens;
+        tokens.iter().enumerate().skip(self.next).find(|(_, token)| !token.is_trivia())
+    }
+
+    pub fn next(&mut self) -> Option<(usize, &'t Token<'a>)> {
+        let (index, token) = self.peek()?;
+        self.next = index + 1;
+        Some((index, token))
+    }
+
+    /// Consumes `keywords` in order, or returns `None` if they aren't next.
+    pub fn expect(&mut self, keywords: &[&str]) -> Option<()> {
+        for keyword in keywords {
+            let (_, token) = self.next()?;
+            if !token.is_keyword(keyword) {
+                return None;
+            }
+        }
+        Some(())
+    }
+
+    /// Consumes `keywords` if they come next, all of them, and says whether
+    /// they did.
+    pub fn skip(&mut self, keywords: &[&str]) -> bool {
+        let mut lookahead = self.clone();
+        if lookahead.expect(keywords).is_none() {
+            return false;
+        }
+        *self = lookahead;
+        true
+    }
+
+    /// Consumes a parenthesized group if one comes next, and returns the
+    /// index of its closing parenthesis.
+    pub fn skip_group(&mut self) -> Option<usize> {
+        let (open, token) = self.peek()?;
+        if !token.is_symbol("(") {
+            return None;
+        }
+        let close = matching_paren(self.tokens, open)?;
+        self.next = close + 1;
+        Some(close)
+    }
+
+    /// Consumes a possibly qualified object name (`schema.table`,
+    /// `"My Table"`) and returns the index just past it.
+    pub fn object_name(&mut self) -> Option<usize> {
+        let (mut index, first) = self.next()?;
+        if !matches!(first.kind, TokenKind::Word | TokenKind::Quoted) {
+            return None;
+        }
+        let tokens = self.tokens;
+        while index + 2 < tokens.len() && tokens[index + 1].is_symbol(".") && matches!(tokens[index + 2].kind, TokenKind::Word | TokenKind::Quoted) {
+            index += 2;
+        }
+        self.next = index + 1;
+        Some(index + 1)
+    }
+}
+
+/// Finds the first thing that leaves `sql` unbalanced: a `)` with nothing to
+/// close, a `(`, quote or block comment that is never closed. Parentheses
+/// and quotes inside comments and strings don't count, so `-- don't` is
+/// fine. Returns its byte offset and what it is.
+pub fn imbalance(sql: &str) -> Option<(usize, &'static str)> {
+    let mut open = Vec::new();
+    let mut offset = 0;
+    for token in tokenize(sql) {
+        let start = offset;
+        offset += token.text.len();
+        match token.kind {
+            TokenKind::Quoted if !quoted_region(sql, start).is_some_and(|(_, closed)| closed) => {
+                return Some((start, "unclosed quote"));
+            },
+            TokenKind::Comment if token.text.starts_with("/*") && (token.text.len() < 4 || !token.text.ends_with("*/")) => {
+                return Some((start, "unclosed comment"));
+            },
+            TokenKind::Symbol if token.text == "(" => open.push(start),
+            TokenKind::Symbol if token.text == ")" && open.pop().is_none() => {
+                return Some((start, "unmatched closing parenthesis"));
+            },
+            _ => {},
+        }
+    }
+    open.pop().map(|start| (start, "unclosed parenthesis"))
+}
+
+/// Operators longer than one character, longest first.
+const OPERATORS: [&str; 10] = ["->>", "<=", ">=", "<>", "!=", "::", "||", "->", "=>", ":="];
+
+/// Splits `sql` into tokens. Quoted regions and comments are single tokens,
+/// so nothing inside them is ever mistaken for a keyword or a semicolon.
+pub fn tokenize(sql: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    
+    while i < sql.len() {
+        let rest = &sql[i..];
+        let c = rest.chars().next().unwrap();
+        
+        let (kind, end) = if let Some(end) = quoted_region_end(sql, i) {
+            (TokenKind::Quoted, end)
+        } else if rest.starts_with("--") {
+            (TokenKind::Comment, i + rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            (TokenKind::Comment, i + body.find("*/").map_or(rest.len(), |close| close + 4))
+        } else if c.is_whitespace() {
+            (TokenKind::Whitespace, i + rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len()))
+        } else if is_word_char(c) || c == '$' || is_parameter(rest) {
+            // `$` only gets here when it doesn't open a dollar quote, as in `$1`
+            (TokenKind::Word, i + c.len_utf8() + rest[c.len_utf8()..].find(|c: char| !is_word_char(c)).unwrap_or(rest.len() - c.len_utf8()))
+        } else {
+            let len = OPERATORS.iter().find(|op| rest.starts_with(**op)).map_or(c.len_utf8(), |op| op.len());
+            (TokenKind::Symbol, i + len)
+        };
+        
+        tokens.push(Token { kind, text: &sql[i..end] });
+        i = end;
+    }
+    
+    tokens
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `rest` starts with a named parameter or variable (`:name`,
+/// `@name`), which is a single word so that `LIMIT :offset` has no OFFSET
+/// clause in it. `::` casts never get here: they are matched as an operator
+/// first.
+fn is_parameter(rest: &str) -> bool {
+    let mut chars = rest.chars();
+    matches!(chars.next(), Some(':' | '@')) && chars.next().is_some_and(is_word_char)
+}
+
+/// Writes `tokens` back out on one line: each run of whitespace becomes a
+/// single space, and leading and trailing whitespace is dropped.
+pub fn render(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    for token in tokens {
+        if token.kind == TokenKind::Whitespace {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+        } else {
+            text.push_str(token.text);
+        }
+    }
+    text.truncate(text.trim_end().len());
+    text
+}
+
+/// Symbols [`space_operators`] puts a space around.
+const SPACED_SYMBOLS: [&str; 8] = ["=", "<>", "!=", "<", ">", "<=", ">=", "||"];
+
+/// Keywords [`space_operators`] puts a space around where they touch a
+/// symbol, as in `x IN(1, 2)`.
+const SPACED_KEYWORDS: [&str; 6] = ["AND", "OR", "NOT", "IN", "LIKE", "BETWEEN"];
+
+/// Adds whitespace on both sides of each comparison operator, `||`, and
+/// `AND`, `OR`, `NOT`, `IN`, `LIKE` and `BETWEEN` outside parentheses in
+/// `tokens`, so that [`render`] leaves exactly one space there. An operator
+/// right next to another symbol, other than a parenthesis or a sign after
+/// it, is left alone: it may be part of one the lexer doesn't know, such as
+/// `<@` or `>>`.
+pub fn space_operators<'a>(tokens: &[Token<'a>]) -> Vec<Token<'a>> {
+    let is_neighbour_ok = |token: Option<&Token>| token.is_none_or(|token| token.kind != TokenKind::Symbol || token.is_symbol("(") || token.is_symbol(")"));
+    let is_sign = |token: Option<&Token>| token.is_some_and(|token| token.is_symbol("-") || token.is_symbol("+"));
+    let operators: Vec<usize> = top_level(tokens, 0)
+        .into_iter()
+        .filter(|&i| {
+            let token = &tokens[i];
+            SPACED_KEYWORDS.iter().any(|keyword| token.is_keyword(keyword))
+                || SPACED_SYMBOLS.iter().any(|symbol| token.is_symbol(symbol))
+                    && is_neighbour_ok(i.checked_sub(1).map(|previous| &tokens[previous]))
+                    && (is_neighbour_ok(tokens.get(i + 1)) || is_sign(tokens.get(i + 1)))
+        })
+        .collect();
+
+    let space = Token { kind: TokenKind::Whitespace, text: " " };
+    let mut spaced = Vec::with_capacity(tokens.len() + 2 * operators.len());
+    let mut operators = operators.into_iter().peekable();
+    for (i, token) in tokens.iter().enumerate() {
+        let is_operator = operators.next_if_eq(&i).is_some();
+        if is_operator && spaced.last().is_some_and(|previous: &Token| previous.kind != TokenKind::Whitespace) {
+            spaced.push(space);
+        }
+        spaced.push(*token);
+        if is_operator && tokens.get(i + 1).is_some_and(|next| next.kind != TokenKind::Whitespace) {
+            spaced.push(space);
+        }
+    }
+    spaced
+}
+
+/// A `--` comment at the end of a line with code on it.
+pub struct TrailingComment<'a> {
+    /// Index of the token before it, whitespace aside
+    pub after: usize,
+    pub text: &'a str,
+}
+
+/// Takes the `--` comments that end lines with code on them out of `tokens`,
+/// so that formatters which can't lay out comments can work on the rest.
+/// Returns `tokens` with each such comment turned into a space, and the
+/// comments. Returns `None` if `tokens` has any other comment, such as one
+/// on a line of its own.
+pub fn take_trailing_comments<'a>(tokens: &[Token<'a>]) -> Option<(Vec<Token<'a>>, Vec<TrailingComment<'a>>)> {
+    let mut kept = tokens.to_vec();
+    let mut comments = Vec::new();
+    for (i, token) in tokens.iter().enumerate().filter(|(_, token)| token.kind == TokenKind::Comment) {
+        let before = tokens[..i].iter().rposition(|token| token.kind != TokenKind::Whitespace)?;
+        if !token.text.starts_with("--") || tokens[before + 1..i].iter().any(|token| token.text.contains('\n')) {
+            return None;
+        }
+        kept[i] = Token { kind: TokenKind::Whitespace, text: " " };
+        comments.push(TrailingComment { after: before, text: token.text.trim_end() });
+    }
+    Some((kept, comments))
+}
+
+/// Matches `comments`, as found by [`take_trailing_comments`], to the items
+/// of a comma-separated list at `items` of `tokens`: a comment goes with the
+/// item whose last token, or the comma after it, it follows. Returns `None`
+/// if a comment follows anything else, such as a token in the middle of an
+/// item.
+pub fn item_comments<'a>(tokens: &[Token], items: &[Range<usize>], comments: &[TrailingComment<'a>]) -> Option<Vec<Option<&'a str>>> {
+    let mut found = vec![None; items.len()];
+    for comment in comments {
+        let item = items.iter().position(|item| {
+            let last = tokens[item.clone()].iter().rposition(|token| !token.is_trivia()).map(|i| item.start + i);
+            let next = tokens[item.end..].iter().position(|token| !token.is_trivia()).map(|i| item.end + i);
+            last == Some(comment.after) || next == Some(comment.after) && tokens[comment.after].is_symbol(",")
+        })?;
+        found[item] = Some(comment.text);
+    }
+    Some(found)
+}
+
+/// Separates a trailing semicolon from the rest of the statement. A
+/// semicolon written on a line of its own stays there; one after spaces on
+/// the statement's last line is pulled up against it.
+pub fn strip_terminator<'a, 't>(tokens: &'t [Token<'a>]) -> (&'t [Token<'a>], &'static str) {
+    match tokens.iter().rposition(|token| !token.is_trivia()) {
+        Some(last) if tokens[last].is_symbol(";") => {
+            let own_line = last > 0 && tokens[last - 1].kind == TokenKind::Whitespace && tokens[last - 1].text.contains('\n');
+            (&tokens[..last], if own_line { "\n;" } else { ";" })
+        },
+        _ => (tokens, ""),
+    }
+}
+
+/// Indices of the non-trivia tokens at or after `from` that are outside any
+/// parentheses and `CASE ... END`, so a keyword found there belongs to the
+/// statement itself.
+pub fn top_level(tokens: &[Token], from: usize) -> Vec<usize> {
+    let mut indices = Vec::new();
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(from) {
+        if token.is_symbol("(") || token.is_keyword("CASE") {
+            depth += 1;
+        } else if token.is_symbol(")") || token.is_keyword("END") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && !token.is_trivia() {
+            indices.push(i);
+        }
+    }
+    indices
+}
+
+/// Index of the parenthesis closing the one at `open`, if it is closed.
+pub fn matching_paren(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// Splits `tokens` at each top-level `separator`, ignoring separators inside
+/// parentheses or brackets. The separators themselves are dropped.
+pub fn split_top_level<'a, 't>(tokens: &'t [Token<'a>], separator: &str) -> Vec<&'t [Token<'a>]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_symbol("(") || token.is_symbol("[") {
+            depth += 1;
+        } else if token.is_symbol(")") || token.is_symbol("]") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && token.is_symbol(separator) {
+            parts.push(&tokens[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&tokens[start..]);
+    
+    parts
+}
+
+/// If a quoted region opens at byte offset `start` of `text`, returns the
+/// offset just past its closing quote (or the end of `text` if it is never
+/// closed). Recognizes single-quoted strings, double-quoted (ANSI) and
+/// backtick-quoted (MySQL) identifiers, and PostgreSQL dollar-quoted strings
+/// (`$$...$$`, `$tag$...$tag$`). A backslash only escapes in an `E'...'`
+/// string; in any other, as in standard SQL, `'C:\'` is closed.
+pub fn quoted_region_end(text: &str, start: usize) -> Option<usize> {
+    quoted_region(text, start).map(|(end, _)| end)
+}
+
+/// Like [`quoted_region_end`], but also says whether the region is closed.
+fn quoted_region(text: &str, start: usize) -> Option<(usize, bool)> {
+    let rest = &text[start..];
+    let first = rest.chars().next()?;
+    
+    match first {
+        '\'' | '"' | '`' => {
+            // A doubled quote is an escaped quote, never a close followed by
+            // a new string
+            let backslash_escapes = first == '\'' && is_escape_string(&text[..start]);
+            let mut escaped = false;
+            let mut chars = rest.char_indices().skip(1).peekable();
+            while let Some((i, c)) = chars.next() {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && backslash_escapes {
+                    escaped = true;
+                } else if c == first {
+                    if chars.peek().is_some_and(|&(_, next)| next == first) {
+                        chars.next();
+                    } else {
+                        return Some((start + i + 1, true));
+                    }
+                }
+            }
+            Some((text.len(), false))
+        },
+        '$' => {
+            let tag = dollar_quote_tag(rest)?;
+            match rest[tag.len()..].find(tag) {
+                Some(close) => Some((start + tag.len() + close + tag.len(), true)),
+                None => Some((text.len(), false)),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Whether a string opening right after `before` has the `E` prefix of a
+/// PostgreSQL escape string, as in `E'\n'`, rather than ending a word.
+fn is_escape_string(before: &str) -> bool {
+    let Some(prefix) = before.strip_suffix(['E', 'e']) else { return false };
+    !prefix.chars().next_back().is_some_and(is_word_char)
+}
+
+/// Returns the opening `$tag$` delimiter at the start of `text`, if any.
+/// The tag follows identifier rules, so `$1` placeholders never match.
+fn dollar_quote_tag(text: &str) -> Option<&str> {
+    let body = text.strip_prefix('$')?;
+    for (i, c) in body.char_indices() {
+        if c == '$' {
+            return Some(&text[..i + 2]);
+        }
+        let valid = if i == 0 { c.is_alphabetic() || c == '_' } else { c.is_alphanumeric() || c == '_' };
+        if !valid {
+            return None;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(sql: &str) -> Vec<&str> {
+        tokenize(sql).iter().filter(|token| !token.is_trivia()).map(|token| token.text).collect()
+    }
+
+    #[test]
+    fn backslash_does_not_escape_in_standard_strings() {
+        assert_eq!(texts(r"SELECT 'C:\', 'x'"), ["SELECT", r"'C:\'", ",", "'x'"]);
+        assert_eq!(imbalance(r"INSERT INTO paths VALUES ('C:\');"), None);
+    }
+
+    #[test]
+    fn backslash_escapes_in_escape_strings() {
+        assert_eq!(texts(r"SELECT E'it\'s', e'\\'"), ["SELECT", "E", r"'it\'s'", ",", "e", r"'\\'"]);
+        assert_eq!(imbalance(r"SELECT E'C:\';"), Some((8, "unclosed quote")));
+    }
+
+    #[test]
+    fn word_ending_in_e_is_not_a_prefix() {
+        assert_eq!(texts(r"SELECT name'C:\'"), ["SELECT", "name", r"'C:\'"]);
+    }
+
+    #[test]
+    fn doubled_quotes_escape() {
+        assert_eq!(texts("SELECT 'it''s', \"a\"\"b\""), ["SELECT", "'it''s'", ",", "\"a\"\"b\""]);
+    }
+}
@@ -0,0 +1,285 @@
+//! The formatter behind the `cargo-sql-fmt` command: [`format_file_content`]
+//! formats a file the way the command does, with the [`FormatOptions`] it
+//! builds from its flags.
+
+use std::any::Any;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+pub mod counts;
+mod ddl;
+pub mod dml;
+pub mod error;
+mod grant;
+mod lexer;
+mod markdown;
+pub mod rust;
+mod select;
+pub mod statements;
+mod template;
+
+use counts::{StatementCounts, StatementKind};
+use error::FormatError;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum NewlineStyle {
+    Lf,
+    Crlf,
+    Native,
+    Auto,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Alignment {
+    Left,
+    Right,
+}
+
+/// What `--blank-lines` does with the blank lines between statements.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlankLines {
+    Preserve,
+    /// At most this many in a row
+    Max(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum FinalNewline {
+    True,
+    False,
+    Preserve,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum TypeCase {
+    Upper,
+    Lower,
+    Preserve,
+}
+
+/// What a file holds, which decides what in it is formatted.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SourceKind {
+    Sql,
+    /// Rust, with SQL in the string literals of some calls
+    Rust,
+    /// Markdown, with SQL in fenced code blocks
+    Markdown,
+}
+
+impl SourceKind {
+    /// The kind of file `path` is, going by its extension.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rs") => SourceKind::Rust,
+            Some("md" | "markdown") => SourceKind::Markdown,
+            _ => SourceKind::Sql,
+        }
+    }
+}
+
+/// Settings that shape the formatted output, as opposed to which files get
+/// formatted and how the run is reported.
+#[derive(Clone, Debug)]
+pub struct FormatOptions {
+    pub newline: NewlineStyle,
+    pub max_width: usize,
+    pub align_grants: bool,
+    pub align_aliases: bool,
+    pub align_defaults: bool,
+    pub align_constraints: bool,
+    pub align_trailing_comments: bool,
+    pub inline_enum_values: usize,
+    pub inline_case_width: usize,
+    pub inline_in_values: usize,
+    pub in_values_per_line: usize,
+    pub boolean_alignment: Alignment,
+    pub right_align_dates: bool,
+    pub right_align_functions: Vec<String>,
+    pub align_across_statements: bool,
+    pub compact_single_row: bool,
+    pub insert_column_per_line: bool,
+    pub merge_inserts: bool,
+    pub merge_blank_lines: usize,
+    pub split_inserts: Option<usize>,
+    pub blank_lines: BlankLines,
+    pub trim_trailing_whitespace: bool,
+    pub final_newline: FinalNewline,
+    pub normalize_operator_spacing: bool,
+    pub indent_transactions: bool,
+    pub type_case: TypeCase,
+    pub strict: bool,
+    pub max_statement_size: Option<usize>,
+    pub line_ranges: Vec<RangeInclusive<usize>>,
+    pub only_kinds: Vec<StatementKind>,
+    pub skip_kinds: Vec<StatementKind>,
+    pub rust_calls: Vec<String>,
+    pub verify_tokens: bool,
+}
+
+impl Default for FormatOptions {
+    /// The options of a run with no flags.
+    fn default() -> Self {
+        FormatOptions {
+            newline: NewlineStyle::Auto,
+            max_width: 100,
+            align_grants: false,
+            align_aliases: false,
+            align_defaults: false,
+            align_constraints: false,
+            align_trailing_comments: false,
+            inline_enum_values: 2,
+            inline_case_width: 50,
+            inline_in_values: 10,
+            in_values_per_line: 10,
+            boolean_alignment: Alignment::Left,
+            right_align_dates: false,
+            right_align_functions: Vec::new(),
+            align_across_statements: false,
+            compact_single_row: true,
+            insert_column_per_line: false,
+            merge_inserts: false,
+            merge_blank_lines: 0,
+            split_inserts: None,
+            blank_lines: BlankLines::Preserve,
+            trim_trailing_whitespace: false,
+            final_newline: FinalNewline::Preserve,
+            normalize_operator_spacing: false,
+            indent_transactions: false,
+            type_case: TypeCase::Preserve,
+            strict: false,
+            max_statement_size: Some(256 * 1024),
+            line_ranges: Vec::new(),
+            only_kinds: Vec::new(),
+            skip_kinds: Vec::new(),
+            rust_calls: rust::DEFAULT_CALLS.iter().map(|call| call.to_string()).collect(),
+            verify_tokens: true,
+        }
+    }
+}
+
+const UTF8_BOM: &str = "\u{feff}";
+
+/// The message a panic was raised with, if it has one.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "formatter panicked".to_string())
+}
+
+/// Checks that formatting `formatted` again leaves it as it is, which any
+/// output of the formatter should.
+pub fn verify_idempotent(formatted: &str, options: &FormatOptions, kind: SourceKind) -> Result<(), FormatError> {
+    let (again, _) = format_file_content(formatted, options, kind, &mut Vec::new(), &mut Vec::new())?;
+    match formatted.lines().zip(again.lines()).position(|(first, second)| first != second) {
+        Some(line) => Err(FormatError::NotIdempotent { line: line + 1 }),
+        None if formatted != again => Err(FormatError::NotIdempotent { line: formatted.lines().count().min(again.lines().count()) + 1 }),
+        None => Ok(()),
+    }
+}
+
+/// Formats the full content of a file, keeping its byte order mark and
+/// writing the line endings `options` ask for. In Rust and Markdown files
+/// only the SQL they hold is formatted. Also returns how many statements of
+/// each kind were changed.
+pub fn format_file_content(content: &str, options: &FormatOptions, kind: SourceKind, warnings: &mut Vec<String>, incidents: &mut Vec<statements::Incident>) -> Result<(String, StatementCounts), FormatError> {
+    // A byte order mark is set aside so it can't stick to the first statement
+    let bom = if content.starts_with(UTF8_BOM) { UTF8_BOM } else { "" };
+    let body = &content[bom.len()..];
+
+    // Format with LF line endings, then restore the requested ending. The
+    // line endings inside SQL strings are part of their values, so they stay
+    // as they are both ways.
+    let normalized = match kind {
+        SourceKind::Sql => outside_quotes(body, |text| text.replace("\r\n", "\n")),
+        _ => body.replace("\r\n", "\n"),
+    };
+    let mut reformatted = StatementCounts::default();
+    let formatted = match kind {
+        SourceKind::Sql => statements::format_statements(&normalized, options, &mut reformatted, warnings, incidents)?,
+        SourceKind::Rust => rust::format_source(&normalized, options, &mut reformatted, warnings, incidents),
+        SourceKind::Markdown => markdown::format_source(&normalized, options, &mut reformatted, warnings, incidents),
+    };
+
+    // Content that needs no formatting keeps its line endings untouched unless
+    // the user explicitly asked for a particular style
+    if formatted == normalized && options.newline == NewlineStyle::Auto {
+        return Ok((content.to_string(), reformatted));
+    }
+
+    let use_crlf = match options.newline {
+        NewlineStyle::Lf => false,
+        NewlineStyle::Crlf => true,
+        NewlineStyle::Native => cfg!(windows),
+        NewlineStyle::Auto => uses_crlf(body),
+    };
+    let mut formatted_content = String::from(bom);
+    if use_crlf && kind == SourceKind::Sql {
+        formatted_content.push_str(&outside_quotes(&formatted, |text| text.replace('\n', "\r\n")));
+    } else if use_crlf {
+        formatted_content.push_str(&formatted.replace('\n', "\r\n"));
+    } else {
+        formatted_content.push_str(&formatted);
+    }
+    Ok((formatted_content, reformatted))
+}
+
+/// `sql` with `change` applied to the text between its quoted regions, which
+/// are copied as they are.
+fn outside_quotes(sql: &str, change: impl Fn(&str) -> String) -> String {
+    let mut changed = String::with_capacity(sql.len() + sql.len() / 16);
+    let mut copied = 0;
+    for quoted in statements::quoted_ranges(sql) {
+        changed.push_str(&change(&sql[copied..quoted.start]));
+        changed.push_str(&sql[quoted.clone()]);
+        copied = quoted.end;
+    }
+    changed.push_str(&change(&sql[copied..]));
+    changed
+}
+
+/// Whether CRLF is the dominant line ending in `content`.
+fn uses_crlf(content: &str) -> bool {
+    let crlf = content.matches("\r\n").count();
+    let lf = content.matches('\n').count() - crlf;
+    crlf > lf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(content: &str, options: &FormatOptions) -> String {
+        format_file_content(content, options, SourceKind::Sql, &mut Vec::new(), &mut Vec::new()).unwrap().0
+    }
+
+    #[test]
+    fn crlf_file_keeps_crlf_unless_another_ending_is_asked_for() {
+        let content = "insert into t (a,b) values (1,'x'),(22,'y');\r\n";
+        let formatted = "insert into t (a,b)\r\nvalues\r\n( 1 , 'x'),\r\n(22 , 'y');\r\n";
+        assert_eq!(format(content, &FormatOptions::default()), formatted);
+        assert_eq!(format(formatted, &FormatOptions::default()), formatted);
+        let lf = FormatOptions { newline: NewlineStyle::Lf, ..FormatOptions::default() };
+        assert_eq!(format(formatted, &lf), formatted.replace("\r\n", "\n"));
+    }
+
+    #[test]
+    fn byte_order_mark_is_kept_and_does_not_stick_to_the_first_statement() {
+        let content = "\u{feff}insert into t (a,b) values (1,'x'),(22,'y');\r\n";
+        let formatted = "\u{feff}insert into t (a,b)\r\nvalues\r\n( 1 , 'x'),\r\n(22 , 'y');\r\n";
+        assert_eq!(format(content, &FormatOptions::default()), formatted);
+        assert_eq!(format(formatted, &FormatOptions::default()), formatted);
+    }
+
+    #[test]
+    fn crlf_file_keeps_the_line_endings_inside_its_strings() {
+        let content = "INSERT INTO t (a,b) VALUES (1,'x\ny'),(22,'p\r\nq');\r\n";
+        let formatted = "INSERT INTO t (a,b)\r\nVALUES\r\n( 1 , 'x\ny'),\r\n(22 , 'p\r\nq');\r\n";
+        assert_eq!(format(content, &FormatOptions::default()), formatted);
+        assert_eq!(format(formatted, &FormatOptions::default()), formatted);
+        let lf = FormatOptions { newline: NewlineStyle::Lf, ..FormatOptions::default() };
+        assert_eq!(format(content, &lf), "INSERT INTO t (a,b)\nVALUES\n( 1 , 'x\ny'),\n(22 , 'p\r\nq');\n");
+    }
+}
@@ -1,348 +1,1099 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fs::{self, File};
-use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::io::{self, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
 use std::error::Error;
-use std::cmp::max;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use clap::Parser;
-use glob::glob;
+mod analyze;
+mod cache;
+mod dump;
+mod git;
+mod ignore_rules;
+mod output;
+mod report;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum, ValueHint};
+use clap_complete::Shell;
+use glob::{glob, Pattern};
+
+use sql_fmt::counts::{StatementCounts, StatementKind};
+use sql_fmt::error::{FormatError, StatementError};
+use sql_fmt::{counts, dml, error, rust, statements};
+use sql_fmt::{format_file_content, panic_message, verify_idempotent, Alignment, BlankLines, FinalNewline, FormatOptions, NewlineStyle, SourceKind, TypeCase};
+
+use cache::Cache;
+use git::ChangeSet;
+use ignore_rules::IgnoreRules;
+use output::Output;
+use report::{FileOutcome, FileReport};
 
 #[derive(Parser)]
-#[clap(name = "SQL Formatter", about = "Formats SQL files with aligned columns")]
+#[clap(
+    name = "cargo-sql-fmt",
+    version,
+    about = "Formats SQL files with aligned columns",
+    after_long_help = report::JSON_REPORT_HELP,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    /// Path to SQL file or glob pattern to match multiple files
-    #[clap(name = "PATH")]
-    path: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// SQL files, directories to search recursively, or glob patterns
+    #[clap(name = "PATH", value_hint = ValueHint::AnyPath, required_unless_present_any = ["changed", "staged", "stdin"])]
+    paths: Vec<String>,
+
+    /// Read SQL from standard input and write the formatted result to
+    /// standard output
+    #[clap(long, conflicts_with_all = ["PATH", "changed", "staged"])]
+    stdin: bool,
+
+    /// Path the standard input content belongs to; used to find
+    /// `.sqlfmtignore` and in messages (requires --stdin)
+    #[clap(long, value_name = "PATH", value_hint = ValueHint::FilePath, requires = "stdin")]
+    stdin_filepath: Option<PathBuf>,
+
+    /// Only format SQL files changed on this branch since BASE (default
+    /// HEAD), plus staged, unstaged and untracked ones
+    #[clap(long, value_name = "BASE", num_args = 0..=1, require_equals = true, default_missing_value = "HEAD", conflicts_with_all = ["PATH", "staged"])]
+    changed: Option<String>,
+
+    /// Only format staged SQL files, e.g. from a pre-commit hook; with
+    /// --check, their staged content is checked rather than the working tree
+    #[clap(long, conflicts_with = "PATH")]
+    staged: bool,
+
+    /// Don't write anything; exit with an error if any file would be
+    /// reformatted or a pattern matches no files
+    #[clap(long)]
+    check: bool,
+
+    /// Line ending to write; `auto` keeps the file's dominant line ending
+    #[clap(long, value_enum, default_value = "auto")]
+    newline: NewlineStyle,
+
+    /// Line length past which statements that can be wrapped are wrapped
+    #[clap(long, value_name = "COLUMNS", default_value_t = 100)]
+    max_width: usize,
+
+    /// Line up the objects and roles of consecutive GRANT and REVOKE statements
+    #[clap(long)]
+    align_grants: bool,
+
+    /// Line up the AS aliases of a select list that is split one column per line
+    #[clap(long)]
+    align_aliases: bool,
+
+    /// Line up the DEFAULT of each column in CREATE TABLE, when most columns have one
+    #[clap(long)]
+    align_defaults: bool,
+
+    /// Line up the names, columns and targets of FOREIGN KEY constraints in CREATE TABLE
+    #[clap(long)]
+    align_constraints: bool,
+
+    /// Line up the `--` comments at the ends of INSERT rows and CREATE TABLE
+    /// definitions
+    #[clap(long)]
+    align_trailing_comments: bool,
+
+    /// Enums with at most this many values are kept on one line
+    #[clap(long, value_name = "COUNT", default_value_t = 2)]
+    inline_enum_values: usize,
+
+    /// CASE expressions up to this long are kept on one line
+    #[clap(long, value_name = "COLUMNS", default_value_t = 50)]
+    inline_case_width: usize,
+
+    /// IN lists with at most this many values are kept on one line, unless it gets too long
+    #[clap(long, value_name = "COUNT", default_value_t = 10)]
+    inline_in_values: usize,
+
+    /// How many values of a wrapped IN list go on each line
+    #[clap(long, value_name = "COUNT", default_value_t = 10)]
+    in_values_per_line: usize,
+
+    /// Which side of their column TRUE and FALSE line up on in a VALUES grid
+    #[clap(long, value_enum, default_value = "left")]
+    boolean_alignment: Alignment,
+
+    /// Right-align quoted ISO dates (`'2024-01-01'`) in a VALUES grid, like numbers
+    #[clap(long)]
+    right_align_dates: bool,
+
+    /// Right-align calls to these functions (`POINT(1, 2)`) in a VALUES grid,
+    /// like numbers; comma-separated or repeated
+    #[clap(long, value_name = "NAME", value_delimiter = ',')]
+    right_align_functions: Vec<String>,
+
+    /// Line up the VALUES grids of consecutive INSERTs into the same table and columns
+    #[clap(long)]
+    align_across_statements: bool,
+
+    /// Lay out a single-row INSERT as a grid even when it fits on one line
+    #[clap(long)]
+    no_compact_single_row: bool,
+
+    /// Put each column of a wrapped INSERT column list on its own line, rather than filling lines
+    #[clap(long)]
+    insert_column_per_line: bool,
+
+    /// Merge consecutive INSERTs into the same table and columns into one multi-row INSERT
+    #[clap(long)]
+    merge_inserts: bool,
+
+    /// Most blank lines allowed between two INSERTs merged by --merge-inserts
+    #[clap(long, value_name = "COUNT", default_value_t = 0)]
+    merge_blank_lines: usize,
+
+    /// Split INSERTs with more than this many VALUES rows into several of at most this many (0 doesn't split)
+    #[clap(long, value_name = "ROWS")]
+    split_inserts: Option<usize>,
+
+    /// Blank lines between statements: `preserve` them as written, or cap
+    /// runs at a number (`collapse` is 2) and separate statements of
+    /// different kinds with one
+    #[clap(long, value_name = "POLICY", value_parser = parse_blank_lines, default_value = "preserve")]
+    blank_lines: BlankLines,
+
+    /// Put exactly one space around comparison operators, `||`, AND, OR,
+    /// NOT, IN, LIKE and BETWEEN in WHERE, HAVING, ON and SET clauses
+    #[clap(long)]
+    normalize_operator_spacing: bool,
+
+    /// Indent the statements between BEGIN and COMMIT (or ROLLBACK) one
+    /// level
+    #[clap(long)]
+    indent_transactions: bool,
+
+    /// Case for the data types of columns in CREATE TABLE and ALTER TABLE
+    /// ... ADD: `upper`, `lower`, or `preserve` as written. Quoted names and
+    /// what's in parentheses keep theirs
+    #[clap(long, value_enum, value_name = "CASE", default_value = "preserve")]
+    type_case: TypeCase,
+
+    /// Also strip trailing spaces and tabs from the lines the formatter
+    /// leaves alone (the lines it writes never have any)
+    #[clap(long)]
+    trim_trailing_whitespace: bool,
+
+    /// End each file with exactly one newline (`true`), with none (`false`),
+    /// or however it ends already (`preserve`)
+    #[clap(long, value_enum, value_name = "POLICY", default_value = "preserve")]
+    final_newline: FinalNewline,
+
+    /// Report on the files instead of formatting them: statements by kind,
+    /// semicolons, balance and anything suspicious (with --strict, findings
+    /// make the run fail)
+    #[clap(long, conflicts_with_all = ["stdin", "check"])]
+    analyze: bool,
+
+    /// Format files even if their parentheses, quotes or comments don't
+    /// balance, which normally gets them skipped with a warning
+    #[clap(long)]
+    force: bool,
+
+    /// Save each statement a formatter fails on, which is left as written,
+    /// and each file the formatter crashes on, to numbered files in DIR
+    #[clap(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    debug_dump: Option<PathBuf>,
+
+    /// Fail a file whose VALUES rows don't match its column list, instead of
+    /// just warning. Without it, such a row is still aligned as far as its
+    /// values go: a short row ends early, a long row's extra values trail
+    /// after the grid
+    #[clap(long)]
+    strict: bool,
+
+    /// Format each file's output a second time and fail the file if that changes anything
+    #[clap(long)]
+    verify: bool,
+
+    /// Skip all verification: both --verify and the check that formatting
+    /// changes nothing but whitespace
+    #[clap(long, overrides_with = "verify")]
+    no_verify: bool,
+
+    /// Format and verify every file, but write nothing; only a file that
+    /// fails verification fails the run
+    #[clap(long, conflicts_with_all = ["check", "no_verify", "stdin"])]
+    verify_only: bool,
+
+    /// Whether a file that is not valid UTF-8 fails the run or is just skipped
+    #[clap(long, value_enum, default_value = "skip")]
+    encoding_error: EncodingErrorMode,
+
+    /// Save a copy of each file before rewriting it
+    #[clap(long)]
+    backup: bool,
+
+    /// Suffix appended to the file name of backups
+    #[clap(long, default_value = ".bak")]
+    backup_suffix: String,
+
+    /// Skip files larger than this many bytes (accepts K, M and G suffixes;
+    /// 0 or `unlimited` disables the limit)
+    #[clap(long, value_parser = parse_file_size)]
+    max_file_size: Option<u64>,
+
+    /// Leave statements larger than this many bytes as they are, with a
+    /// warning (accepts K, M and G suffixes; 0 or `unlimited` disables the limit)
+    #[clap(long, value_parser = parse_file_size, default_value = "256K")]
+    max_statement_size: u64,
+
+    /// Only run the formatters for these kinds of statement, such as
+    /// `inserts,creates`, and leave other statements as they are
+    #[clap(long, value_enum, value_name = "KINDS", value_delimiter = ',')]
+    only: Vec<StatementKind>,
+
+    /// Leave these kinds of statement as they are, such as `selects`
+    #[clap(long, value_enum, value_name = "KINDS", value_delimiter = ',')]
+    skip: Vec<StatementKind>,
+
+    /// Only format statements that overlap lines START to END, counting from
+    /// 1, and leave the rest of the file as it is (can be repeated)
+    #[clap(long, value_name = "START:END", value_parser = parse_line_range, conflicts_with_all = ["merge_inserts", "split_inserts"])]
+    lines: Vec<RangeInclusive<usize>>,
+
+    /// Also format the SQL in string literals of `.rs` files found in
+    /// directories and patterns (a `.rs` file named outright always is)
+    #[clap(long, conflicts_with = "analyze")]
+    rust: bool,
+
+    /// The macros and functions whose string argument is SQL in Rust files,
+    /// such as `query!,sqlx::query` (a name ending in `!` is a macro; the
+    /// default covers sqlx and diesel)
+    #[clap(long, value_name = "NAMES", value_delimiter = ',')]
+    rust_calls: Vec<String>,
+
+    /// Also format the SQL code blocks of `.md` files found in directories
+    /// and patterns (a `.md` file named outright always is)
+    #[clap(long, conflicts_with = "analyze")]
+    markdown: bool,
+
+    /// Skip files matching this glob, relative to the current directory
+    /// (can be repeated)
+    #[clap(long, value_name = "GLOB", value_parser = parse_exclude_pattern)]
+    exclude: Vec<Pattern>,
+
+    /// Format files even if `.sqlfmtignore` or git ignore rules exclude them
+    #[clap(long)]
+    no_ignore: bool,
+
+    /// Number of files to format in parallel (defaults to the number of
+    /// logical CPUs)
+    #[clap(short, long)]
+    jobs: Option<usize>,
+
+    /// Skip files that haven't changed since a `--cache` run last left them
+    /// formatted
+    #[clap(long, conflicts_with = "stdin")]
+    cache: bool,
+
+    /// Where `--cache` keeps its record of formatted files
+    #[clap(long, value_name = "PATH", value_hint = ValueHint::FilePath, default_value = "target/sql-fmt-cache.json")]
+    cache_location: PathBuf,
+
+    /// Format every file, even with `--cache` (for overriding it in an alias
+    /// or hook)
+    #[clap(long, overrides_with = "cache")]
+    no_cache: bool,
+
+    /// How to report the run: a line per file, or one JSON document on
+    /// standard output (described at the end of `--help`)
+    #[clap(long, value_enum, default_value = "human", conflicts_with = "stdin")]
+    output: OutputFormat,
+
+    /// Print more detail about what was done to each file
+    #[clap(short, long)]
+    verbose: bool,
+
+    /// Print nothing but errors; the exit status tells the result
+    #[clap(short, long, conflicts_with = "verbose")]
+    quiet: bool,
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let cli = Cli::parse();
-    let paths = expand_glob(&cli.path)?;
-    
-    for path in paths {
-        println!("Processing file: {}", path.display());
-        match format_sql_file(&path) {
-            Ok(_) => println!("Successfully formatted {}", path.display()),
-            Err(e) => eprintln!("Error formatting {}: {}", path.display(), e),
+impl Cli {
+    /// The settings of the formatter this run asks for.
+    fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            newline: self.newline,
+            max_width: self.max_width,
+            align_grants: self.align_grants,
+            align_aliases: self.align_aliases,
+            align_defaults: self.align_defaults,
+            align_constraints: self.align_constraints,
+            align_trailing_comments: self.align_trailing_comments,
+            inline_enum_values: self.inline_enum_values,
+            inline_case_width: self.inline_case_width,
+            inline_in_values: self.inline_in_values,
+            in_values_per_line: self.in_values_per_line,
+            boolean_alignment: self.boolean_alignment,
+            right_align_dates: self.right_align_dates,
+            right_align_functions: self.right_align_functions.clone(),
+            align_across_statements: self.align_across_statements,
+            compact_single_row: !self.no_compact_single_row,
+            insert_column_per_line: self.insert_column_per_line,
+            merge_inserts: self.merge_inserts,
+            merge_blank_lines: self.merge_blank_lines,
+            split_inserts: self.split_inserts,
+            blank_lines: self.blank_lines,
+            trim_trailing_whitespace: self.trim_trailing_whitespace,
+            final_newline: self.final_newline,
+            normalize_operator_spacing: self.normalize_operator_spacing,
+            indent_transactions: self.indent_transactions,
+            type_case: self.type_case,
+            strict: self.strict,
+            max_statement_size: usize::try_from(self.max_statement_size).ok().filter(|&size| size > 0),
+            line_ranges: self.lines.clone(),
+            only_kinds: self.only.clone(),
+            skip_kinds: self.skip.clone(),
+            rust_calls: if self.rust_calls.is_empty() {
+                rust::DEFAULT_CALLS.iter().map(|call| call.to_string()).collect()
+            } else {
+                self.rust_calls.clone()
+            },
+            verify_tokens: !self.no_verify,
         }
     }
-    
-    Ok(())
+
+    /// Whether files are only formatted to see the result, never written.
+    fn is_dry_run(&self) -> bool {
+        self.check || self.verify_only
+    }
 }
 
-fn expand_glob(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
-    let mut paths = Vec::new();
+#[derive(Subcommand)]
+enum Command {
+    /// Print a completion script for SHELL, e.g.
+    /// `source <(cargo-sql-fmt completions bash)`
+    #[clap(hide = true)]
+    Completions {
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum EncodingErrorMode {
+    Fail,
+    Skip,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
+/// A file to format, and whether it was named on the command line rather than
+/// discovered through a directory or glob pattern.
+struct InputFile {
+    path: PathBuf,
+    explicit: bool,
+}
+
+/// The files a run will format, plus what discovery left out: directories
+/// or patterns that matched no SQL files, and files skipped by ignore rules,
+/// `--exclude` or the backup suffix.
+struct Discovery {
+    files: Vec<InputFile>,
+    unmatched: usize,
+    ignored: usize,
+}
+
+/// Files above this size are skipped during discovery unless `--max-file-size`
+/// says otherwise; generated dumps are rarely worth the time.
+const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Exit status when `--check` finds files that would be reformatted (or a
+/// pattern that matches nothing).
+const EXIT_CHECK_FAILED: u8 = 1;
+
+/// Exit status when any file, or the run itself, failed. Takes precedence
+/// over `EXIT_CHECK_FAILED`, and matches the status of a command-line usage
+/// error.
+const EXIT_ERROR: u8 = 2;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse_from(cli_args(std::env::args_os()));
+    if let Some(kind) = cli.only.iter().find(|kind| cli.skip.contains(kind)) {
+        let message = format!("--only and --skip both name {} statements", kind.name());
+        Cli::command().error(ErrorKind::ArgumentConflict, message).exit();
+    }
+    match run(&cli) {
+        Ok(code) => code,
+        Err(e) => {
+            Output::new(&cli).error(format_args!("Error: {}", e));
+            ExitCode::from(EXIT_ERROR)
+        },
+    }
+}
+
+/// `args`, the command line starting with the program, minus the subcommand
+/// name cargo passes first when the binary is run as `cargo sql-fmt`.
+fn cli_args(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = args.into_iter().collect();
+    let program = args.first().and_then(|program| Path::new(program).file_stem());
+    if program.is_some_and(|name| name == "cargo-sql-fmt") && args.get(1).is_some_and(|arg| arg == "sql-fmt") {
+        args.remove(1);
+    }
+    args
+}
+
+/// Adds a warning for each of the `incidents` from `source`, and under
+/// `--debug-dump` saves them, saying where they went.
+fn report_incidents(incidents: &[statements::Incident], source: &str, cli: &Cli, warnings: &mut Vec<String>) {
+    for incident in incidents {
+        let Some(dir) = &cli.debug_dump else {
+            warnings.push(incident.error.to_string());
+            continue;
+        };
+        let saved = match dump::write(dir, source, incident) {
+            Ok(path) => format!("saved to {}", path.display()),
+            Err(e) => format!("couldn't save it to {}: {}", dir.display(), e),
+        };
+        warnings.push(format!("{} ({})", incident.error, saved));
+    }
+}
+
+/// Name the binary was run as, which is what shell completions complete.
+fn program_name() -> String {
+    std::env::args_os()
+        .next()
+        .and_then(|program| Path::new(&program).file_stem().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "cargo-sql-fmt".to_string())
+}
+
+/// Does the whole run and returns the exit status it should end with. Errors
+/// in individual files end up in their reports; an `Err` means the run
+/// couldn't go ahead at all.
+fn run(cli: &Cli) -> Result<ExitCode, Box<dyn Error>> {
+    if let Some(Command::Completions { shell }) = cli.command {
+        clap_complete::generate(shell, &mut Cli::command(), program_name(), &mut io::stdout());
+        return Ok(ExitCode::SUCCESS);
+    }
+    if cli.stdin {
+        format_stdin(cli)?;
+        return Ok(ExitCode::SUCCESS);
+    }
     
-    for entry in glob(pattern)? {
-        match entry {
-            Ok(path) => {
-                if path.is_file() && path.extension().map_or(false, |ext| ext == "sql") {
-                    paths.push(path);
-                }
-            },
-            Err(e) => eprintln!("Error with glob pattern: {}", e),
+    let started = Instant::now();
+    let discovery = collect_input_files(cli)?;
+    if cli.analyze {
+        let analyses = analyze::analyze_files(&discovery.files);
+        analyze::print(&analyses, cli);
+        if cli.strict && analyze::has_findings(&analyses) {
+            return Ok(ExitCode::from(EXIT_CHECK_FAILED));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let cache = (cli.cache && !cli.no_cache).then(|| Cache::load(&cli.cache_location, &cli.format_options()));
+    let reports = format_files(&discovery.files, cli, cache.as_ref());
+    if let Some(cache) = cache {
+        if let Err(e) = cache.save() {
+            Output::new(cli).warning(format_args!("couldn't write the cache to {}: {}", cli.cache_location.display(), e));
         }
     }
     
-    if paths.is_empty() {
-        return Err("No SQL files found with the given pattern".into());
+    // Reports come back in input order, once every file is done
+    match cli.output {
+        OutputFormat::Human => {
+            report::print_human(&reports, cli);
+            report::print_summary(&reports, discovery.ignored, cli);
+        },
+        OutputFormat::Json => report::print_json(&reports, cli, started.elapsed()),
     }
     
-    Ok(paths)
+    let errors = reports.iter().filter(|report| report.outcome.is_err()).count();
+    let would_reformat = reports.iter().filter(|report| matches!(report.outcome, Ok(FileOutcome::Formatted { .. }))).count();
+    
+    if errors > 0 {
+        return Ok(ExitCode::from(EXIT_ERROR));
+    }
+    if cli.check && (would_reformat > 0 || discovery.unmatched > 0) {
+        return Ok(ExitCode::from(EXIT_CHECK_FAILED));
+    }
+    
+    Ok(ExitCode::SUCCESS)
 }
 
-fn format_sql_file(path: &Path) -> Result<(), Box<dyn Error>> {
-    // Read the file content
-    let mut file = File::open(path)?;
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
-
-    // Format the SQL content
-    let formatted_content = format_sql(&content);
-
-    // Write back to the file
-    let mut file = File::create(path)?;
-    file.write_all(formatted_content.as_bytes())?;
-
+/// Formats standard input to standard output. With `--stdin-filepath`, the
+/// content is treated as that file: a file excluded by `.sqlfmtignore` or
+/// `--exclude` is echoed back unchanged. So is content that doesn't balance,
+/// unless `--force` is given.
+fn format_stdin(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let name = cli.stdin_filepath.as_ref().map_or_else(|| "<stdin>".to_string(), |path| path.display().to_string());
+    
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
+    let content = String::from_utf8(bytes).map_err(|_| format!("{} is {}", name, FormatError::InvalidUtf8))?;
+    
+    let ignored = match &cli.stdin_filepath {
+        Some(path) if !cli.no_ignore => {
+            let rules = IgnoreRules::from_dir(path.parent().unwrap_or(Path::new("")));
+            matching_exclude(path, &cli.exclude).is_some() || rules.sqlfmtignore_rule(path).is_some()
+        },
+        Some(path) => matching_exclude(path, &cli.exclude).is_some(),
+        None => false,
+    };
+    let kind = cli.stdin_filepath.as_deref().map_or(SourceKind::Sql, SourceKind::of);
+    
+    let unbalanced = statements::imbalance(&content).filter(|_| !ignored && kind == SourceKind::Sql && !cli.force);
+    let output = if ignored {
+        content
+    } else if let Some((offset, problem)) = unbalanced {
+        let line = content[..offset].matches('\n').count() + 1;
+        Output::new(cli).warning(format_args!("{}: {} on line {}; leaving it as it is (--force formats it anyway)", name, problem, line));
+        content
+    } else {
+        let mut warnings = Vec::new();
+        let options = cli.format_options();
+        let mut incidents = Vec::new();
+        let formatted = format_file_content(&content, &options, kind, &mut warnings, &mut incidents).map_err(|e| format!("{}: {}", name, e))?.0;
+        report_incidents(&incidents, &name, cli, &mut warnings);
+        if cli.verify {
+            verify_idempotent(&formatted, &options, kind).map_err(|e| format!("{}: {}", name, e))?;
+        }
+        let out = Output::new(cli);
+        for warning in warnings {
+            out.warning(format_args!("{}: {}", name, warning));
+        }
+        formatted
+    };
+    io::stdout().write_all(output.as_bytes())?;
+    
     Ok(())
 }
 
-#[derive(Debug)]
-struct InsertStatement {
-    header: String,
-    values_keyword: String,
-    rows: Vec<Vec<String>>,
-    terminator: String,
+/// Runs `format` on `file`, turning a panic into an error report. A bug that
+/// panics on one file shouldn't take the rest of the run down with it, but
+/// it mustn't go unnoticed either: the crash is printed straight away and,
+/// under `--debug-dump`, the file is saved for the bug report.
+fn format_caught(file: &InputFile, cli: &Cli, format: impl FnOnce() -> FileReport) -> FileReport {
+    panic::catch_unwind(AssertUnwindSafe(format)).unwrap_or_else(|payload| {
+        let message = panic_message(payload.as_ref());
+        let saved = match (&cli.debug_dump, fs::read_to_string(&file.path)) {
+            (None, _) => String::new(),
+            (Some(dir), Ok(content)) => {
+                let incident = statements::Incident {
+                    span: 0..content.len(),
+                    error: StatementError { kind: "panic", offset: 0, line: 1, problem: message.clone() },
+                    text: content,
+                };
+                match dump::write(dir, &file.path.display().to_string(), &incident) {
+                    Ok(path) => format!(" (saved to {})", path.display()),
+                    Err(e) => format!(" (couldn't save it to {}: {})", dir.display(), e),
+                }
+            },
+            (Some(_), Err(e)) => format!(" (couldn't read it to save it: {})", e),
+        };
+        Output::new(cli).error(format_args!(
+            "error: the formatter crashed on {}: {}; the file was left untouched{}. This is a bug in sql-fmt, please report it",
+            file.path.display(), message, saved
+        ));
+        FileReport {
+            path: file.path.clone(),
+            outcome: Err(FormatError::Internal(message)),
+            reformatted: StatementCounts::default(),
+            warnings: Vec::new(),
+            statement_errors: Vec::new(),
+            duration: Duration::ZERO,
+        }
+    })
 }
 
-fn format_sql(sql: &str) -> String {
-    let mut result = String::new();
-    let mut current_insert: Option<InsertStatement> = None;
-    let mut buffer = Vec::new();
-    let mut is_first_statement = true;
+/// Formats `files` on a pool of `--jobs` threads and returns each file's
+/// report in the same order as `files`. A failing file doesn't stop the
+/// others.
+fn format_files(files: &[InputFile], cli: &Cli, cache: Option<&Cache>) -> Vec<FileReport> {
+    let jobs = cli.jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, files.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<FileReport>>> = files.iter().map(|_| Mutex::new(None)).collect();
     
-    // First pass: collect all INSERT statements
-    for line in sql.lines() {
-        let trimmed = line.trim();
-        
-        if line_contains_insert(trimmed) {
-            // Start of a new INSERT statement
-            if let Some(insert) = current_insert.take() {
-                // Format the previous INSERT statement
-                let formatted = format_insert_statement(insert);
-                
-                // Add a blank line between statements, but not before the first one
-                if !is_first_statement {
-                    result.push_str("\n");
-                }
-                is_first_statement = false;
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = files.get(index) else { break };
                 
-                result.push_str(&formatted);
-            } else {
-                // This is the first INSERT statement
-                is_first_statement = true;
-            }
-            
-            // Extract column names
-            let header = line.to_string();
-            current_insert = Some(InsertStatement {
-                header,
-                values_keyword: String::new(),
-                rows: Vec::new(),
-                terminator: String::new(),
+                let report = format_caught(file, cli, || format_sql_file(file, cli, cache));
+                *results[index].lock().unwrap() = Some(report);
             });
-        } else if let Some(ref mut insert) = current_insert {
-            if line_is_values_line(trimmed) {
-                // This is the VALUES line
-                insert.values_keyword = line.to_string();
-            } else if line_is_values_row(trimmed) {
-                // This is a values row
-                let values = parse_values_row(line);
-                insert.rows.push(values);
-                
-                // Check if this is the last row (has terminator)
-                if trimmed.ends_with(");") {
-                    insert.terminator = ");".to_string();
-                } else if trimmed.contains(";);") {
-                    // Handle malformed terminators
-                    insert.terminator = ");".to_string();
-                }
-            } else if !trimmed.is_empty() {
-                // Other line that's part of the INSERT statement
-                buffer.push(line.to_string());
+        }
+    });
+    
+    results.into_iter().map(|result| result.into_inner().unwrap().unwrap()).collect()
+}
+
+/// Resolves the positional arguments into the files to format: directories
+/// are searched recursively, glob patterns are expanded, and anything else is
+/// taken as a file name. Under `--changed`/`--staged` the files come from git
+/// instead.
+fn collect_input_files(cli: &Cli) -> Result<Discovery, Box<dyn Error>> {
+    let mut files: Vec<InputFile> = Vec::new();
+    let mut unmatched = 0;
+    let mut ignored = HashSet::new();
+    let mut ignore_rules = if cli.no_ignore { None } else { Some(IgnoreRules::new()) };
+    let out = Output::new(cli);
+    
+    let changes = match (&cli.changed, cli.staged) {
+        (Some(base), _) => Some(ChangeSet::Changed { base }),
+        (None, true) => Some(ChangeSet::Staged),
+        (None, false) => None,
+    };
+    if let Some(changes) = changes {
+        for path in git::changed_sql_files(changes, |path| is_sql_file(path, cli))? {
+            if is_excluded(&path, false, cli, &mut ignore_rules) {
+                ignored.insert(path);
+            } else {
+                files.push(InputFile { path, explicit: false });
             }
-        } else {
-            // Not part of an INSERT statement
-            result.push_str(line);
-            result.push('\n');
         }
+        return Ok(Discovery { files, unmatched, ignored: ignored.len() });
     }
     
-    // Format the last INSERT statement if any
-    if let Some(insert) = current_insert {
-        let formatted = format_insert_statement(insert);
+    for arg in &cli.paths {
+        let path = Path::new(arg);
+        let (candidates, explicit) = if path.is_dir() {
+            (walk_directory(path, cli, &mut ignore_rules)?, false)
+        } else if is_glob_pattern(arg) {
+            (expand_glob(arg, cli, out)?, false)
+        } else {
+            (vec![path.to_path_buf()], true)
+        };
         
-        // Add a blank line before the last statement if needed
-        if !is_first_statement {
-            result.push_str("\n");
+        if candidates.is_empty() {
+            out.warning(format_args!("no SQL files found in {}", arg));
+            unmatched += 1;
         }
         
-        result.push_str(&formatted);
+        for path in candidates.iter().map(|path| without_cur_dir(path)) {
+            if files.iter().any(|file| file.path == path) || ignored.contains(&path) {
+                continue;
+            }
+            if is_excluded(&path, explicit, cli, &mut ignore_rules) {
+                ignored.insert(path);
+                continue;
+            }
+            files.push(InputFile { path, explicit });
+        }
     }
     
-    // Add any remaining lines
-    for line in buffer {
-        result.push_str(&line);
-        result.push('\n');
+    Ok(Discovery { files, unmatched, ignored: ignored.len() })
+}
+
+/// Whether a candidate file is filtered out by `--exclude`, the backup suffix,
+/// or ignore rules. Files named explicitly are only checked against
+/// .sqlfmtignore; git ignore rules just filter what discovery finds.
+fn is_excluded(path: &Path, explicit: bool, cli: &Cli, ignore_rules: &mut Option<IgnoreRules>) -> bool {
+    let out = Output::new(cli);
+    if !explicit && is_backup_file(path, &cli.backup_suffix) {
+        return true;
+    }
+    if let Some(pattern) = matching_exclude(path, &cli.exclude) {
+        out.detail(format_args!("Excluding {} (--exclude {})", path.display(), pattern));
+        return true;
     }
     
-    // Remove trailing newline if the original doesn't have one
-    if !sql.ends_with('\n') && result.ends_with('\n') {
-        result.pop();
+    if explicit {
+        if let Some(rule) = ignore_rules.as_ref().and_then(|rules| rules.sqlfmtignore_rule(path)) {
+            out.warning(format_args!("skipping {}, which is excluded by {}", path.display(), rule));
+            return true;
+        }
+    } else if let Some(rule) = ignore_rules.as_mut().and_then(|rules| rules.matching_rule(path)) {
+        out.detail(format_args!("Ignoring {} ({})", path.display(), rule));
+        return true;
     }
     
-    result
-}
-
-fn line_contains_insert(line: &str) -> bool {
-    line.to_uppercase().contains("INSERT INTO")
-}
-
-fn line_is_values_line(line: &str) -> bool {
-    line.trim().to_uppercase() == "VALUES"
+    false
 }
 
-fn line_is_values_row(line: &str) -> bool {
-    let trimmed = line.trim();
-    trimmed.starts_with('(') && (
-        trimmed.ends_with("),") || 
-        trimmed.ends_with(");") || 
-        trimmed.ends_with("););") || 
-        trimmed.ends_with("););") || 
-        trimmed.ends_with(')') || 
-        trimmed.contains(";);")
-    )
-}
-
-fn parse_values_row(line: &str) -> Vec<String> {
-    let mut values = Vec::new();
-    let mut current = String::new();
-    let mut in_quotes = false;
-    let mut quote_char = ' ';
-    let mut escaped = false;
-    let mut paren_level = 0;
-    let mut first_paren_found = false;
-    
-    // Fix the line before processing - handle several common issues
-    let mut cleaned_line = line.trim().to_string();
+fn expand_glob(pattern: &str, cli: &Cli, out: Output) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
     
-    // Replace problematic endings
-    if cleaned_line.ends_with(";);") {
-        cleaned_line = cleaned_line.replace(";);", ");");
+    for entry in glob(pattern)? {
+        match entry {
+            Ok(path) => {
+                if path.is_file() && is_sql_file(&path, cli) {
+                    paths.push(path);
+                }
+            },
+            Err(e) => out.error(format_args!("Error with glob pattern: {}", e)),
+        }
     }
     
-    // Remove trailing commas before closing parentheses
-    cleaned_line = cleaned_line.replace(" ,)", ")").replace(",)", ")");
+    Ok(paths)
+}
+
+/// Collects the SQL files under `dir`, recursively and in a stable order.
+/// Directories the ignore rules exclude aren't searched at all.
+fn walk_directory(dir: &Path, cli: &Cli, ignore_rules: &mut Option<IgnoreRules>) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
     
-    for c in cleaned_line.chars() {
-        if !escaped && (c == '\'' || c == '"') {
-            current.push(c);
-            if !in_quotes {
-                in_quotes = true;
-                quote_char = c;
-            } else if c == quote_char {
-                in_quotes = false;
+    for entry in entries {
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if entry.file_name() == ".git" {
+                continue;
             }
-        } else if c == '(' && !in_quotes {
-            if !first_paren_found {
-                first_paren_found = true;
-                // Skip the opening parenthesis of the row
-            } else {
-                current.push(c);
-                paren_level += 1;
+            if let Some(rule) = ignore_rules.as_mut().and_then(|rules| rules.matching_dir_rule(&path)) {
+                Output::new(cli).detail(format_args!("Ignoring {} ({})", without_cur_dir(&path).display(), rule));
+                continue;
             }
-        } else if c == ')' && !in_quotes {
-            if paren_level == 0 {
-                // This is the closing parenthesis of the row
-                if !current.trim().is_empty() {
-                    values.push(current.trim().to_string());
-                    current = String::new();
-                }
-                // Stop processing after the closing parenthesis
-                break;
-            } else {
-                current.push(c);
-                paren_level -= 1;
-            }
-        } else if c == ',' && !in_quotes && paren_level == 0 {
-            values.push(current.trim().to_string());
-            current = String::new();
-        } else {
-            current.push(c);
+            paths.extend(walk_directory(&path, cli, ignore_rules)?);
+        } else if is_sql_file(&path, cli) {
+            paths.push(path);
         }
-        
-        escaped = !escaped && c == '\\';
     }
     
-    // Add the last value if there is one
-    if !current.trim().is_empty() {
-        values.push(current.trim().to_string());
+    Ok(paths)
+}
+
+/// Whether discovery picks up `path`: a `.sql` file, or a Rust or Markdown
+/// file under `--rust` or `--markdown`.
+fn is_sql_file(path: &Path, cli: &Cli) -> bool {
+    match SourceKind::of(path) {
+        SourceKind::Rust => cli.rust,
+        SourceKind::Markdown => cli.markdown,
+        SourceKind::Sql => path.extension().is_some_and(|ext| ext == "sql"),
     }
-    
-    values
 }
 
-fn format_insert_statement(insert: InsertStatement) -> String {
-    let mut result = String::new();
-    
-    // Add header
-    result.push_str(&insert.header);
-    result.push('\n');
+fn parse_exclude_pattern(value: &str) -> Result<Pattern, String> {
+    Pattern::new(value).map_err(|e| format!("invalid glob `{}`: {}", value, e))
+}
+
+/// Returns the first `--exclude` pattern matching `path`. Patterns are
+/// relative to the current directory, so `path` is made relative to it too.
+fn matching_exclude<'a>(path: &Path, patterns: &'a [Pattern]) -> Option<&'a Pattern> {
+    if patterns.is_empty() {
+        return None;
+    }
     
-    // Add VALUES keyword
-    result.push_str(&insert.values_keyword);
-    result.push('\n');
+    let cwd = std::env::current_dir().ok()?;
+    let relative = path.strip_prefix(&cwd).unwrap_or(path);
+    patterns.iter().find(|pattern| pattern.matches_path(&without_cur_dir(relative)))
+}
+
+/// Drops `.` components, so `./db/a.sql` and `db/a.sql` compare equal.
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components().filter(|component| *component != Component::CurDir).collect()
+}
+
+fn is_glob_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Parses a `--max-file-size` or `--max-statement-size` value: plain bytes, a number with a K/M/G
+/// suffix (powers of 1024), or `unlimited` (same as 0).
+fn parse_file_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("unlimited") {
+        return Ok(0);
+    }
     
-    // Calculate the maximum width for each column
-    let num_columns = insert.rows.iter().map(|row| row.len()).max().unwrap_or(0);
-    let mut column_widths = vec![0; num_columns];
+    let upper = value.to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, multiplier) = match digits.chars().last() {
+        Some('K') => (&digits[..digits.len() - 1], 1024),
+        Some('M') => (&digits[..digits.len() - 1], 1024 * 1024),
+        Some('G') => (&digits[..digits.len() - 1], 1024 * 1024 * 1024),
+        _ => (digits, 1),
+    };
     
-    for row in &insert.rows {
-        for (i, value) in row.iter().enumerate() {
-            if i < num_columns {
-                column_widths[i] = max(column_widths[i], value.len());
-            }
-        }
+    number.trim().parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size `{}`", value))
+}
+
+/// Parses a `--blank-lines` value: `preserve`, `collapse` or a number.
+fn parse_blank_lines(value: &str) -> Result<BlankLines, String> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "preserve" => Ok(BlankLines::Preserve),
+        "collapse" => Ok(BlankLines::Max(2)),
+        number => number.parse().map(BlankLines::Max)
+            .map_err(|_| format!("invalid blank line policy `{}`; expected `preserve`, `collapse` or a number", value)),
+    }
+}
+
+/// Parses a `--lines` value such as `120:180`.
+fn parse_line_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = value.split_once(':')
+        .and_then(|(start, end)| Some((start.trim().parse::<usize>().ok()?, end.trim().parse::<usize>().ok()?)))
+        .ok_or_else(|| format!("invalid line range `{}`; expected START:END, like 120:180", value))?;
+    if start == 0 {
+        return Err(format!("invalid line range `{}`; lines are counted from 1", value));
+    }
+    if end < start {
+        return Err(format!("invalid line range `{}`; it ends before it starts", value));
     }
+    Ok(start..=end)
+}
+
+/// Whether `path` looks like a backup written by `--backup`, so a suffix that
+/// itself ends in `.sql` doesn't get the formatter working on its own backups.
+fn is_backup_file(path: &Path, backup_suffix: &str) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().ends_with(backup_suffix))
+}
+
+/// Path of the backup kept for `path`: the file name with the suffix appended.
+fn backup_path(path: &Path, backup_suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(backup_suffix);
+    path.with_file_name(name)
+}
+
+/// Formats one file in place and reports what happened. Errors end up in the
+/// report rather than being returned.
+fn format_sql_file(file: &InputFile, cli: &Cli, cache: Option<&Cache>) -> FileReport {
+    let started = Instant::now();
+    let mut warnings = Vec::new();
     
-    // Format and add each row
-    for (i, row) in insert.rows.iter().enumerate() {
-        result.push('(');
-        
-        for (j, value) in row.iter().enumerate() {
-            result.push_str(value);
-            
-            // Add padding and comma if not the last column
-            if j < row.len() - 1 {
-                let padding = column_widths[j] - value.len() + 1;
-                for _ in 0..padding {
-                    result.push(' ');
-                }
-                result.push(',');
-                result.push(' ');
+    // Naming a file outright is a stronger signal than matching a pattern, so
+    // the default limit only applies to discovered files
+    let size_limit = match cli.max_file_size {
+        Some(0) => None,
+        Some(limit) => Some(limit),
+        None if file.explicit => {
+            let size = fs::metadata(&file.path).map(|metadata| metadata.len()).unwrap_or(0);
+            if size > DEFAULT_MAX_FILE_SIZE {
+                warnings.push(format!(
+                    "{} is {} bytes, above the default limit of {}; formatting it because it was named explicitly",
+                    file.path.display(), size, DEFAULT_MAX_FILE_SIZE
+                ));
             }
+            None
+        },
+        None => Some(DEFAULT_MAX_FILE_SIZE),
+    };
+    
+    let mut problems = Vec::new();
+    let mut statement_errors = Vec::new();
+    let (outcome, reformatted) = match rewrite_sql_file(&file.path, cli, size_limit, cache, &mut problems, &mut statement_errors) {
+        Ok((outcome, reformatted)) => (Ok(outcome), reformatted),
+        Err(e) => (Err(e), StatementCounts::default()),
+    };
+    warnings.extend(problems.into_iter().map(|problem| format!("{}: {}", file.path.display(), problem)));
+    
+    FileReport { path: file.path.clone(), outcome, reformatted, warnings, statement_errors, duration: started.elapsed() }
+}
+
+/// Formats the file in place, unless it is larger than `size_limit`. Under
+/// `--check` nothing is written, but the outcome still says whether the file
+/// would have changed. Anything suspicious in the SQL adds to `warnings`,
+/// and each statement a formatter fails on to `statement_errors` as well.
+/// With a `cache`, a file it knows to be formatted isn't formatted again, and
+/// a file that ends up formatted is recorded in it.
+fn rewrite_sql_file(path: &Path, cli: &Cli, size_limit: Option<u64>, cache: Option<&Cache>, warnings: &mut Vec<String>, statement_errors: &mut Vec<StatementError>) -> Result<(FileOutcome, StatementCounts), FormatError> {
+    // A commit takes the staged copy, so that is what `--check --staged` checks
+    let staged = if cli.check && cli.staged { Some(git::staged_content(path)?) } else { None };
+    if let Some(limit) = size_limit {
+        let size = match &staged {
+            Some(bytes) => bytes.len() as u64,
+            None => fs::metadata(path)?.len(),
+        };
+        if size > limit {
+            return Ok((FileOutcome::SkippedTooLarge { size, limit }, StatementCounts::default()));
         }
-        
-        // Add row terminator
-        if i == insert.rows.len() - 1 {
-            // Last row, add semicolon
-            result.push_str(");");
-        } else {
-            // Not the last row, add comma
-            result.push_str("),");
+    }
+
+    // Read the file content
+    let bytes = match staged {
+        Some(bytes) => bytes,
+        None => {
+            let mut file = File::open(path)?;
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            bytes
+        },
+    };
+    if cache.is_some_and(|cache| cache.is_formatted(path, &bytes)) {
+        return Ok((FileOutcome::Unchanged, StatementCounts::default()));
+    }
+    let content = match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(_) if cli.encoding_error == EncodingErrorMode::Fail => return Err(FormatError::InvalidUtf8),
+        Err(_) => return Ok((FileOutcome::SkippedNonUtf8, StatementCounts::default())),
+    };
+    let kind = SourceKind::of(path);
+    if let Some((offset, problem)) = statements::imbalance(&content).filter(|_| kind == SourceKind::Sql && !cli.force) {
+        let line = content[..offset].matches('\n').count() + 1;
+        return Ok((FileOutcome::SkippedUnbalanced { line, problem }, StatementCounts::default()));
+    }
+
+    let options = cli.format_options();
+    let mut incidents = Vec::new();
+    let (formatted_content, reformatted) = format_file_content(&content, &options, kind, warnings, &mut incidents)?;
+    report_incidents(&incidents, &path.display().to_string(), cli, warnings);
+    statement_errors.extend(incidents.into_iter().map(|incident| incident.error));
+    if cli.verify || cli.verify_only {
+        verify_idempotent(&formatted_content, &options, kind)?;
+    }
+    if formatted_content == content {
+        if let Some(cache) = cache {
+            cache.record(path, content.as_bytes());
         }
-        
-        result.push('\n');
+        return Ok((FileOutcome::Unchanged, reformatted));
+    }
+
+    if cli.is_dry_run() {
+        return Ok((FileOutcome::Formatted { backup: None }, reformatted));
+    }
+
+    // Keep the original around first, overwriting any earlier backup
+    let backup = if cli.backup {
+        let backup = backup_path(path, &cli.backup_suffix);
+        fs::write(&backup, &content)?;
+        Some(backup)
+    } else {
+        None
+    };
+
+    // Write back to the file
+    write_atomically(path, &formatted_content)?;
+    if let Some(cache) = cache {
+        cache.record(path, formatted_content.as_bytes());
+    }
+
+    Ok((FileOutcome::Formatted { backup }, reformatted))
+}
+
+/// Replaces the file at `path` with `content` without ever leaving it
+/// truncated: the content goes to a temporary file in the same directory,
+/// which is synced and then renamed over the original. If anything fails the
+/// original is untouched and the temporary file is removed.
+fn write_atomically(path: &Path, content: &str) -> io::Result<()> {
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.{}.sql-fmt.tmp", file_name, std::process::id()));
+
+    let result = (|| {
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(content.as_bytes())?;
+        temp.sync_all()?;
+        drop(temp);
+
+        fs::set_permissions(&temp_path, fs::metadata(path)?.permissions())?;
+        replace_file(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
     }
-    
     result
 }
 
-fn format_column_list(columns: &str) -> String {
-    let mut formatted = String::new();
-    let mut tokens = Vec::new();
-    let mut current = String::new();
-    let mut in_quotes = false;
-    let mut quote_char = ' ';
-    let mut escaped = false;
-    
-    // Split by commas, respecting quotes
-    for c in columns.chars() {
-        if !escaped && (c == '\'' || c == '"') {
-            current.push(c);
-            if !in_quotes {
-                in_quotes = true;
-                quote_char = c;
-            } else if c == quote_char {
-                in_quotes = false;
-            }
-        } else if c == ',' && !in_quotes {
-            tokens.push(current.trim().to_string());
-            current = String::new();
-        } else {
-            current.push(c);
-        }
-        
-        escaped = !escaped && c == '\\';
+#[cfg(not(windows))]
+fn replace_file(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)
+}
+
+/// Renaming over an existing file can fail on Windows while another process
+/// (an editor, a virus scanner) has it open, so retry once after removing the
+/// destination.
+#[cfg(windows)]
+fn replace_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
     }
-    
-    // Add the last token if there is one
-    if !current.trim().is_empty() {
-        tokens.push(current.trim().to_string());
+    fs::remove_file(to)?;
+    fs::rename(from, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(cli_args(args.iter().map(OsString::from)))
     }
-    
-    // Join with comma and space
-    formatted = tokens.join(", ");
-    
-    formatted
-}
\ No newline at end of file
+
+    fn error_kind(args: &[&str]) -> Option<ErrorKind> {
+        parse(args).err().map(|e| e.kind())
+    }
+
+    #[test]
+    fn cli_is_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn no_paths_is_a_usage_error() {
+        assert_eq!(error_kind(&["cargo-sql-fmt"]), Some(ErrorKind::MissingRequiredArgument));
+    }
+
+    #[test]
+    fn verbose_without_paths_is_a_usage_error() {
+        assert_eq!(error_kind(&["cargo-sql-fmt", "-v"]), Some(ErrorKind::MissingRequiredArgument));
+        assert_eq!(error_kind(&["cargo-sql-fmt", "sql-fmt", "-v"]), Some(ErrorKind::MissingRequiredArgument));
+    }
+
+    #[test]
+    fn file_argument() {
+        let cli = parse(&["cargo-sql-fmt", "file.sql"]).unwrap();
+        assert_eq!(cli.paths, ["file.sql"]);
+        assert!(!cli.verbose);
+    }
+
+    #[test]
+    fn cargo_subcommand_name_is_dropped() {
+        let cli = parse(&["/home/me/.cargo/bin/cargo-sql-fmt", "sql-fmt", "-v", "db/"]).unwrap();
+        assert_eq!(cli.paths, ["db/"]);
+        assert!(cli.verbose);
+    }
+
+    #[test]
+    fn sql_fmt_is_a_path_unless_run_through_cargo() {
+        let cli = parse(&["sql-fmt", "sql-fmt"]).unwrap();
+        assert_eq!(cli.paths, ["sql-fmt"]);
+        let cli = parse(&["cargo-sql-fmt", "sql-fmt", "sql-fmt"]).unwrap();
+        assert_eq!(cli.paths, ["sql-fmt"]);
+    }
+
+    #[test]
+    fn unknown_option_is_an_error() {
+        assert_eq!(error_kind(&["cargo-sql-fmt", "--chekc", "."]), Some(ErrorKind::UnknownArgument));
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert_eq!(error_kind(&["cargo-sql-fmt", "sql-fmt", "--help"]), Some(ErrorKind::DisplayHelp));
+        assert_eq!(error_kind(&["cargo-sql-fmt", "--version"]), Some(ErrorKind::DisplayVersion));
+    }
+
+    #[test]
+    fn crash_is_dumped_with_its_sidecar() {
+        let dir = std::env::temp_dir().join(format!("sql-fmt-crash-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("crash.sql");
+        fs::write(&path, "SELECT 1;\n").unwrap();
+        let dumps = dir.join("dumps");
+        let cli = parse(&["cargo-sql-fmt", "--debug-dump", dumps.to_str().unwrap(), path.to_str().unwrap()]).unwrap();
+        let file = InputFile { path: path.clone(), explicit: true };
+
+        let report = format_caught(&file, &cli, || panic!("forced crash"));
+
+        assert!(matches!(&report.outcome, Err(FormatError::Internal(message)) if message == "forced crash"));
+        assert_eq!(fs::read_to_string(dumps.join("0001.sql")).unwrap(), "SELECT 1;\n");
+        let sidecar = fs::read_to_string(dumps.join("0001.json")).unwrap();
+        assert!(sidecar.contains("\"pass\": \"panic\""), "{}", sidecar);
+        assert!(sidecar.contains("\"error\": \"forced crash\""), "{}", sidecar);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,162 @@
+use std::ops::Range;
+
+use crate::counts::StatementCounts;
+use crate::statements::{self, Incident};
+use crate::FormatOptions;
+
+/// Info strings of the fenced code blocks that hold SQL, in lowercase. They
+/// all get the same formatting; there is nothing dialect-specific to pick.
+const SQL_LANGUAGES: [&str; 8] = ["sql", "postgresql", "postgres", "pgsql", "mysql", "mariadb", "sqlite", "tsql"];
+
+/// A code fence: its indentation, marker character (`` ` `` or `~`) and how
+/// many of them there are.
+struct Fence {
+    indent: usize,
+    marker: char,
+    len: usize,
+}
+
+/// A fenced code block whose info string names SQL.
+struct Block {
+    fence: Fence,
+    /// The lines between the fences, newlines included
+    content: Range<usize>,
+    /// The line of the opening fence
+    line: usize,
+}
+
+/// Formats the SQL in the fenced code blocks of Markdown `source` whose info
+/// string is `sql` or a dialect of it, and leaves everything else, fences
+/// included, byte for byte as it is. The fence's indentation, as in a list
+/// item, is taken off each line of the block before formatting and put back
+/// after. A block that doesn't balance, or is never closed, is left as
+/// written; one the formatter fails on adds a warning too.
+pub fn format_source(source: &str, options: &FormatOptions, counts: &mut StatementCounts, warnings: &mut Vec<String>, incidents: &mut Vec<Incident>) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+
+    for block in sql_blocks(source) {
+        let content = &source[block.content.clone()];
+        let dedented: String = content.split_inclusive('\n').map(|line| strip_spaces(line, block.fence.indent)).collect();
+        if dedented.trim().is_empty() || statements::imbalance(&dedented).is_some() {
+            continue;
+        }
+
+        let formatted = match statements::format_embedded(&dedented, options, block.line, counts, warnings, incidents) {
+            Ok(formatted) if formatted != dedented => formatted,
+            Ok(_) => continue,
+            Err(e) => {
+                warnings.push(format!("line {}: SQL code block left as written: {}", block.line, e));
+                continue;
+            },
+        };
+
+        result.push_str(&source[copied..block.content.start]);
+        for line in formatted.split_inclusive('\n') {
+            if line != "\n" {
+                result.push_str(&" ".repeat(block.fence.indent));
+            }
+            result.push_str(line);
+        }
+        copied = block.content.end;
+    }
+
+    result.push_str(&source[copied..]);
+    result
+}
+
+/// The closed fenced code blocks of `source` that hold SQL. Other blocks are
+/// skipped whole, so a fence shown inside one isn't mistaken for a real one.
+fn sql_blocks(source: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(Fence, bool, usize, usize)> = None;
+    let mut offset = 0;
+
+    for (number, line) in source.split_inclusive('\n').enumerate() {
+        let start = offset;
+        offset += line.len();
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        match open.take() {
+            None => {
+                open = opening_fence(text).map(|(fence, info)| (fence, is_sql(info), offset, number + 1));
+            },
+            Some((fence, sql, content, line)) if closes(text, &fence) => {
+                if sql {
+                    blocks.push(Block { fence, content: content..start, line });
+                }
+            },
+            still_open => open = still_open,
+        }
+    }
+
+    blocks
+}
+
+/// The fence `line` opens a code block with, and its info string.
+fn opening_fence(line: &str) -> Option<(Fence, &str)> {
+    let rest = line.trim_start_matches(' ');
+    let marker = rest.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = rest.len() - rest.trim_start_matches(marker).len();
+    let info = rest[len..].trim();
+    if len < 3 || (marker == '`' && info.contains('`')) {
+        return None;
+    }
+    Some((Fence { indent: line.len() - rest.len(), marker, len }, info))
+}
+
+/// Whether `line` closes the code block `fence` opened: a run of the same
+/// marker at least as long, and nothing else.
+fn closes(line: &str, fence: &Fence) -> bool {
+    let rest = line.trim_start_matches(' ');
+    let len = rest.len() - rest.trim_start_matches(fence.marker).len();
+    len >= fence.len && rest[len..].trim().is_empty()
+}
+
+/// Whether a code block's info string, such as `sql` or `PostgreSQL title="x"`,
+/// says it holds SQL.
+fn is_sql(info: &str) -> bool {
+    info.split_whitespace().next().is_some_and(|language| SQL_LANGUAGES.contains(&language.to_ascii_lowercase().as_str()))
+}
+
+/// `line` without up to `width` leading spaces.
+fn strip_spaces(line: &str, width: usize) -> &str {
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    &line[spaces.min(width)..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNFORMATTED: &str = "insert into t (a,b) values (1,'x'),(22,'y');\n";
+
+    fn format(source: &str) -> String {
+        format_source(source, &FormatOptions::default(), &mut StatementCounts::default(), &mut Vec::new(), &mut Vec::new())
+    }
+
+    #[test]
+    fn sql_block_is_formatted_and_the_rest_left_alone() {
+        let source = format!("# Seeds\n\nText  with  spaces.\n\n```sql\n{}```\n", UNFORMATTED);
+        assert_eq!(format(&source), "# Seeds\n\nText  with  spaces.\n\n```sql\ninsert into t (a,b)\nvalues\n( 1 , 'x'),\n(22 , 'y');\n```\n");
+    }
+
+    #[test]
+    fn block_in_a_list_item_is_dedented_and_indented_again() {
+        let source = "1. Load the rows:\n\n   ```sql\n   insert into t (a,b) values (1,'x'),(22,'y');\n   ```\n";
+        let expected = "1. Load the rows:\n\n   ```sql\n   insert into t (a,b)\n   values\n   ( 1 , 'x'),\n   (22 , 'y');\n   ```\n";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn other_languages_and_plain_blocks_are_left_alone() {
+        let source = format!("```python\n{}```\n\n~~~\n{}~~~\n\n````\n```sql\n{}```\n````\n", UNFORMATTED, UNFORMATTED, UNFORMATTED);
+        assert_eq!(format(&source), source);
+    }
+
+    #[test]
+    fn unclosed_fence_is_left_as_written() {
+        let source = format!("```sql\n{}", UNFORMATTED);
+        assert_eq!(format(&source), source);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt::Display;
+
+use crate::{Cli, OutputFormat};
+
+/// Every message meant for the user goes through here, so `--quiet`,
+/// `--verbose` and `--output json` are honoured in one place. Formatted SQL
+/// written by `--stdin` is data, not a message, and doesn't.
+#[derive(Clone, Copy)]
+pub struct Output {
+    verbosity: Verbosity,
+    json: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl Output {
+    pub fn new(cli: &Cli) -> Self {
+        let verbosity = if cli.quiet {
+            Verbosity::Quiet
+        } else if cli.verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        };
+        Output { verbosity, json: cli.output == OutputFormat::Json }
+    }
+
+    /// Whether `--quiet` was given.
+    pub fn is_quiet(&self) -> bool {
+        self.verbosity == Verbosity::Quiet
+    }
+
+    /// Whether `--verbose` was given.
+    pub fn is_verbose(&self) -> bool {
+        self.verbosity == Verbosity::Verbose
+    }
+
+    /// A normal progress line on stdout. Left out with `--quiet` and with
+    /// `--output json`, where stdout carries the report.
+    pub fn info(&self, message: impl Display) {
+        if self.verbosity >= Verbosity::Normal && !self.json {
+            println!("{}", message);
+        }
+    }
+
+    /// Extra detail on stdout, only with `--verbose`.
+    pub fn detail(&self, message: impl Display) {
+        if self.verbosity >= Verbosity::Verbose && !self.json {
+            println!("{}", message);
+        }
+    }
+
+    /// Something worth knowing that didn't stop anything, on stderr. Left out
+    /// with `--quiet`.
+    pub fn warning(&self, message: impl Display) {
+        if self.verbosity >= Verbosity::Normal {
+            eprintln!("Warning: {}", message);
+        }
+    }
+
+    /// Something that failed, on stderr. Always shown.
+    pub fn error(&self, message: impl Display) {
+        eprintln!("{}", message);
+    }
+
+    /// A machine-readable document on stdout. Always shown: asking for one
+    /// overrides `--quiet`.
+    pub fn document(&self, document: impl Display) {
+        println!("{}", document);
+    }
+}
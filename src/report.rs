@@ -0,0 +1,309 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::counts::StatementCounts;
+use crate::error::{FormatError, StatementError};
+use crate::output::Output;
+use crate::Cli;
+
+/// Layout of the `--output json` document, shown in `--help`. Bump
+/// `REPORT_VERSION` whenever a field changes meaning or goes away; new fields
+/// may be added without a bump.
+pub const JSON_REPORT_HELP: &str = "\
+JSON report (--output json):
+  {
+    \"version\": 1,
+    \"check\": false,                 // whether this was a --check run
+    \"duration_ms\": 12.345,          // wall time of the whole run
+    \"files\": [
+      {
+        \"path\": \"db/seed.sql\",
+        \"status\": \"formatted\",      // formatted, would_reformat, unchanged, skipped or error
+        \"changed\": true,            // formatted or would_reformat
+        \"skip_reason\": null,        // \"non_utf8\", \"too_large\" or \"unbalanced\" when skipped
+        \"backup\": null,             // path of the --backup copy, if one was written
+        \"statements\": {             // statements reformatted, by kind
+          \"insert\": 2, \"create\": 0, \"select\": 0, \"update\": 0, \"delete\": 0, \"alter\": 0,
+          \"drop\": 0, \"truncate\": 0, \"grant\": 0, \"revoke\": 0, \"merge\": 0, \"copy\": 0
+        },
+        \"oversized_statements\": 0,  // left as written for being over --max-statement-size
+        \"warnings\": [],
+        \"statement_errors\": [       // statements left as written because their formatter failed
+          { \"kind\": \"INSERT\", \"offset\": 120, \"line\": 7, \"problem\": \"the output changes its tokens, starting at `,`\" }
+        ],
+        \"error\": null,              // message when status is error
+        \"error_kind\": null,         // io, invalid_utf8, unbalanced_sql, arity_mismatch,
+                                      // not_idempotent, tokens_changed, lines_out_of_range,
+                                      // statement or internal
+        \"duration_ms\": 1.234
+      }
+    ]
+  }";
+
+const REPORT_VERSION: u32 = 1;
+
+/// What happened to a single file.
+pub enum FileOutcome {
+    Formatted { backup: Option<PathBuf> },
+    Unchanged,
+    SkippedNonUtf8,
+    SkippedTooLarge { size: u64, limit: u64 },
+    /// Left alone because it doesn't look like complete SQL (see `--force`)
+    SkippedUnbalanced { line: usize, problem: &'static str },
+}
+
+/// Everything the run learned about one file. Formatting only produces
+/// reports; the functions below decide how they are shown.
+pub struct FileReport {
+    pub path: PathBuf,
+    pub outcome: Result<FileOutcome, FormatError>,
+    pub reformatted: StatementCounts,
+    pub warnings: Vec<String>,
+    /// Statements left as written because their formatter failed; each is
+    /// among `warnings` too
+    pub statement_errors: Vec<StatementError>,
+    pub duration: Duration,
+}
+
+impl FileReport {
+    fn status(&self, check: bool) -> &'static str {
+        match &self.outcome {
+            Ok(FileOutcome::Formatted { .. }) if check => "would_reformat",
+            Ok(FileOutcome::Formatted { .. }) => "formatted",
+            Ok(FileOutcome::Unchanged) => "unchanged",
+            Ok(FileOutcome::SkippedNonUtf8 | FileOutcome::SkippedTooLarge { .. } | FileOutcome::SkippedUnbalanced { .. }) => "skipped",
+            Err(_) => "error",
+        }
+    }
+}
+
+/// Prints one or two lines per file, in the order of `reports`.
+pub fn print_human(reports: &[FileReport], cli: &Cli) {
+    let out = Output::new(cli);
+    for report in reports {
+        let path = report.path.display();
+
+        out.info(format_args!("Processing file: {}", path));
+        for warning in &report.warnings {
+            out.warning(warning);
+        }
+        match &report.outcome {
+            Ok(FileOutcome::Formatted { .. }) if cli.is_dry_run() => out.info(format_args!("Would reformat {}", path)),
+            Ok(FileOutcome::Formatted { backup }) => match backup {
+                Some(backup) if out.is_verbose() => {
+                    out.info(format_args!("Successfully formatted {} (backup: {})", path, backup.display()))
+                },
+                _ => out.info(format_args!("Successfully formatted {}", path)),
+            },
+            Ok(FileOutcome::Unchanged) => out.info(format_args!("Already formatted {}", path)),
+            Ok(FileOutcome::SkippedNonUtf8) => out.warning(format_args!("skipping non-UTF-8 file {}", path)),
+            Ok(FileOutcome::SkippedTooLarge { size, limit }) => {
+                out.info(format_args!("Skipped {} (size {} > limit {})", path, size, limit))
+            },
+            Ok(FileOutcome::SkippedUnbalanced { line, problem }) => {
+                out.warning(format_args!("skipping {}: {} on line {} (--force formats it anyway)", path, problem, line))
+            },
+            Err(e) => out.error(format_args!("Error formatting {}: {}", path, e)),
+        }
+    }
+}
+
+/// Prints a one-line summary of the run, then lists the errors again so they
+/// aren't lost in scrollback. `--verbose` adds the reformatted statements by
+/// kind; `--quiet` leaves the whole summary out, since the errors were
+/// already printed once.
+pub fn print_summary(reports: &[FileReport], ignored: usize, cli: &Cli) {
+    let out = Output::new(cli);
+    if out.is_quiet() {
+        return;
+    }
+    
+    let mut changed = 0;
+    let mut skipped = ignored;
+    let mut statements = StatementCounts::default();
+    for report in reports {
+        match report.outcome {
+            Ok(FileOutcome::Formatted { .. }) => changed += 1,
+            Ok(FileOutcome::SkippedNonUtf8 | FileOutcome::SkippedTooLarge { .. } | FileOutcome::SkippedUnbalanced { .. }) => skipped += 1,
+            _ => {},
+        }
+        statements.add(report.reformatted);
+    }
+    let errors: Vec<&FileReport> = reports.iter().filter(|report| report.outcome.is_err()).collect();
+
+    out.info("");
+    out.info(format_args!(
+        "{} scanned, {} {}, {} skipped, {}",
+        plural(reports.len() + ignored, "file"),
+        changed,
+        if cli.is_dry_run() { "would be reformatted" } else { "reformatted" },
+        skipped,
+        plural(errors.len(), "error"),
+    ));
+    if statements.oversized > 0 {
+        out.info(format_args!("{} left as written for being over --max-statement-size", plural(statements.oversized, "statement")));
+    }
+    let failed: Vec<(&FileReport, &StatementError)> = reports.iter()
+        .flat_map(|report| report.statement_errors.iter().map(move |error| (report, error)))
+        .collect();
+    if !failed.is_empty() {
+        out.info(format_args!("{} left as written because their formatter failed:", plural(failed.len(), "statement")));
+        for (report, error) in failed {
+            out.info(format_args!("  {}: {}", report.path.display(), error));
+        }
+    }
+
+    if out.is_verbose() {
+        let kinds: Vec<String> = statements.by_kind()
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect();
+        if !kinds.is_empty() {
+            out.detail(format_args!("Statements reformatted: {}", kinds.join(", ")));
+        }
+    }
+
+    if !errors.is_empty() {
+        out.error("");
+        out.error("Errors:");
+        for report in errors {
+            if let Err(e) = &report.outcome {
+                out.error(format_args!("  {}: {}", report.path.display(), e));
+            }
+        }
+    }
+}
+
+/// Prints the whole run as a single JSON document, laid out as described in
+/// `JSON_REPORT_HELP`.
+pub fn print_json(reports: &[FileReport], cli: &Cli, elapsed: Duration) {
+    let mut json = String::new();
+    let _ = write!(json, "{{\n  \"version\": {},\n  \"check\": {},\n  \"duration_ms\": {},\n  \"files\": [", REPORT_VERSION, cli.check, millis(elapsed));
+
+    for (i, report) in reports.iter().enumerate() {
+        let (skip_reason, backup, error) = match &report.outcome {
+            Ok(FileOutcome::SkippedNonUtf8) => (Some("non_utf8"), None, None),
+            Ok(FileOutcome::SkippedTooLarge { .. }) => (Some("too_large"), None, None),
+            Ok(FileOutcome::SkippedUnbalanced { .. }) => (Some("unbalanced"), None, None),
+            Ok(FileOutcome::Formatted { backup }) => (None, backup.as_ref().map(|backup| backup.display().to_string()), None),
+            Ok(FileOutcome::Unchanged) => (None, None, None),
+            Err(e) => (None, None, Some(e)),
+        };
+        let error_kind = error.map(FormatError::kind);
+        let error = error.map(|e| e.to_string());
+        let counts = report.reformatted;
+        let warnings: Vec<String> = report.warnings.iter().map(|warning| string(warning)).collect();
+        let statement_errors: Vec<String> = report.statement_errors
+            .iter()
+            .map(|error| format!("{{ \"kind\": {}, \"offset\": {}, \"line\": {}, \"problem\": {} }}", string(error.kind), error.offset, error.line, string(&error.problem)))
+            .collect();
+
+        json.push_str(if i == 0 { "\n" } else { ",\n" });
+        let _ = write!(
+            json,
+            "    {{\n      \"path\": {},\n      \"status\": \"{}\",\n      \"changed\": {},\n      \"skip_reason\": {},\n      \"backup\": {},\n      \"statements\": {{ \"insert\": {}, \"create\": {}, \"select\": {}, \"update\": {}, \"delete\": {}, \"alter\": {}, \"drop\": {}, \"truncate\": {}, \"grant\": {}, \"revoke\": {}, \"merge\": {}, \"copy\": {} }},\n      \"oversized_statements\": {},\n      \"warnings\": [{}],\n      \"statement_errors\": [{}],\n      \"error\": {},\n      \"error_kind\": {},\n      \"duration_ms\": {}\n    }}",
+            string(&report.path.display().to_string()),
+            report.status(cli.is_dry_run()),
+            matches!(report.outcome, Ok(FileOutcome::Formatted { .. })),
+            optional(skip_reason),
+            optional(backup.as_deref()),
+            counts.insert, counts.create, counts.select, counts.update, counts.delete, counts.alter, counts.drop, counts.truncate, counts.grant, counts.revoke, counts.merge, counts.copy,
+            counts.oversized,
+            warnings.join(", "),
+            statement_errors.join(", "),
+            optional(error.as_deref()),
+            optional(error_kind),
+            millis(report.duration),
+        );
+    }
+
+    json.push_str(if reports.is_empty() { "]\n}" } else { "\n  ]\n}" });
+    Output::new(cli).document(json);
+}
+
+/// Milliseconds with microsecond precision, as a JSON number.
+fn millis(duration: Duration) -> String {
+    format!("{:.3}", duration.as_secs_f64() * 1000.0)
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+fn optional(value: Option<&str>) -> String {
+    value.map_or_else(|| "null".to_string(), string)
+}
+
+/// `value` as a JSON string literal.
+pub fn string(value: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            },
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    /// One error of each kind. The match fails to compile when a variant is
+    /// added, as a reminder to document its kind.
+    fn every_error() -> Vec<FormatError> {
+        let errors = vec![
+            FormatError::Io(io::Error::new(io::ErrorKind::NotFound, "gone")),
+            FormatError::InvalidUtf8,
+            FormatError::UnbalancedSql { line: 1 },
+            FormatError::ArityMismatch { line: 1, row: 1, expected: 2, actual: 1 },
+            FormatError::NotIdempotent { line: 1 },
+            FormatError::TokensChanged { line: 1, token: ",".to_string() },
+            FormatError::LinesOutOfRange { start: 1, end: 2, lines: 1 },
+            FormatError::Statement(StatementError { kind: "INSERT", offset: 0, line: 1, problem: String::new() }),
+            FormatError::Internal(String::new()),
+        ];
+        for error in &errors {
+            match error {
+                FormatError::Io(_)
+                | FormatError::InvalidUtf8
+                | FormatError::UnbalancedSql { .. }
+                | FormatError::ArityMismatch { .. }
+                | FormatError::NotIdempotent { .. }
+                | FormatError::TokensChanged { .. }
+                | FormatError::LinesOutOfRange { .. }
+                | FormatError::Statement(_)
+                | FormatError::Internal(_) => {},
+            }
+        }
+        errors
+    }
+
+    #[test]
+    fn help_documents_every_error_kind() {
+        let start = JSON_REPORT_HELP.find("\"error_kind\"").unwrap();
+        let end = JSON_REPORT_HELP.find("\"duration_ms\": 1.234").unwrap();
+        let documented: Vec<&str> = JSON_REPORT_HELP[start..end]
+            .split(|c: char| !c.is_ascii_lowercase() && !c.is_ascii_digit() && c != '_')
+            .collect();
+        for error in every_error() {
+            assert!(documented.contains(&error.kind()), "error_kind {} is missing from JSON_REPORT_HELP", error.kind());
+        }
+    }
+}
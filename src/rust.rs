@@ -0,0 +1,459 @@
+use std::ops::Range;
+
+use crate::counts::StatementCounts;
+use crate::lexer::{tokenize, TokenKind};
+use crate::statements::{self, Incident};
+use crate::FormatOptions;
+
+/// The calls whose string argument is SQL when `--rust-calls` isn't given.
+/// A name ending in `!` is a macro.
+pub const DEFAULT_CALLS: [&str; 10] = [
+    "query!",
+    "query_as!",
+    "query_scalar!",
+    "query_unchecked!",
+    "query_as_unchecked!",
+    "sql!",
+    "sqlx::query",
+    "sqlx::query_as",
+    "sqlx::query_scalar",
+    "sql_query",
+];
+
+/// What matters in Rust source for finding SQL: names, string literals and
+/// punctuation. Whitespace, comments and other literals are left out.
+enum Piece {
+    Name(Range<usize>),
+    Str(Literal),
+    Punct(char),
+}
+
+/// A string literal in Rust source.
+#[derive(Clone)]
+struct Literal {
+    /// The whole literal, quotes included
+    span: Range<usize>,
+    /// What's between the quotes
+    content: Range<usize>,
+    /// The number of `#`s of a raw string, or `None` for one with escapes
+    hashes: Option<usize>,
+}
+
+/// Formats the SQL in the string literals of Rust `source` that are passed
+/// to one of `options.rust_calls`, and leaves everything else byte for byte
+/// as it is. Each literal is formatted on its own, its lines indented to
+/// match the code around it. A literal whose SQL doesn't balance, spans
+/// lines inside a string or comment, or can't be written back in the same
+/// kind of literal is left as written, and so is one with an escape that
+/// [`escape`] wouldn't write the same way, such as `\n` or `\u{e9}`; one
+/// the formatter fails on adds a warning too.
+pub fn format_source(source: &str, options: &FormatOptions, counts: &mut StatementCounts, warnings: &mut Vec<String>, incidents: &mut Vec<Incident>) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut copied = 0;
+
+    for literal in sql_literals(source, &options.rust_calls) {
+        let line = source[..literal.content.start].matches('\n').count() + 1;
+        let line_start = source[..literal.span.start].rfind('\n').map_or(0, |newline| newline + 1);
+        let indent = leading_whitespace(&source[line_start..]);
+        let content = &source[literal.content.clone()];
+        let value = match literal.hashes {
+            Some(_) => content.to_string(),
+            None => match unescape(content) {
+                Some(value) if escape(&value) == content => value,
+                _ => continue,
+            },
+        };
+
+        let formatted = match format_literal_sql(&value, indent, options, counts, warnings, incidents, line) {
+            Ok(Some(formatted)) => formatted,
+            Ok(None) => continue,
+            Err(problem) => {
+                warnings.push(format!("line {}: string literal left as written: {}", line, problem));
+                continue;
+            },
+        };
+        let encoded = match literal.hashes {
+            Some(hashes) if formatted.contains(&format!("\"{}", "#".repeat(hashes))) => continue,
+            Some(_) => formatted,
+            None => escape(&formatted),
+        };
+
+        result.push_str(&source[copied..literal.content.start]);
+        result.push_str(&encoded);
+        copied = literal.content.end;
+    }
+
+    result.push_str(&source[copied..]);
+    result
+}
+
+/// Formats `value`, the SQL of a literal on `line` indented by `indent`,
+/// and returns the new value, or `None` if it should stay as it is. Lines
+/// after the first are indented like the first line of SQL when that starts
+/// a line of its own, and like the literal's line otherwise.
+#[allow(clippy::too_many_arguments)]
+fn format_literal_sql(value: &str, indent: &str, options: &FormatOptions, counts: &mut StatementCounts, warnings: &mut Vec<String>, incidents: &mut Vec<Incident>, line: usize) -> Result<Option<String>, String> {
+    let body = value.trim();
+    if body.is_empty() {
+        return Ok(None);
+    }
+    let prefix = &value[..value.len() - value.trim_start().len()];
+    let suffix = &value[prefix.len() + body.len()..];
+    let indent = match prefix.rfind('\n') {
+        Some(newline) => &prefix[newline + 1..],
+        None => indent,
+    };
+
+    let spans_lines = tokenize(body).iter().any(|token| matches!(token.kind, TokenKind::Quoted | TokenKind::Comment) && token.text.contains('\n'));
+    if spans_lines || statements::imbalance(body).is_some() {
+        return Ok(None);
+    }
+
+    let dedented = body.split('\n').enumerate()
+        .map(|(number, text)| if number == 0 { text } else { strip_indent(text, indent.len()) })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let offset = line - 1 + prefix.matches('\n').count();
+    let formatted = statements::format_embedded(&dedented, options, offset, counts, warnings, incidents).map_err(|e| e.to_string())?;
+    if formatted == dedented {
+        return Ok(None);
+    }
+
+    let mut result = String::from(prefix);
+    for (number, text) in formatted.split('\n').enumerate() {
+        if number > 0 {
+            result.push('\n');
+            if !text.is_empty() {
+                result.push_str(indent);
+            }
+        }
+        result.push_str(text);
+    }
+    result.push_str(suffix);
+    Ok(Some(result))
+}
+
+/// The string literals of `source` that are the SQL argument of a call to
+/// one of `calls`: the first argument that is nothing but a string literal,
+/// as in `query_as!(User, "SELECT ...")`.
+fn sql_literals(source: &str, calls: &[String]) -> Vec<Literal> {
+    let pieces = pieces(source);
+    let mut found = Vec::new();
+
+    for open in 0..pieces.len() {
+        if !matches!(pieces[open], Piece::Punct('(' | '[' | '{')) {
+            continue;
+        }
+        let Some(path) = callee(&pieces, source, open) else { continue };
+        if !calls.iter().any(|call| path == *call || path.ends_with(&format!("::{}", call))) {
+            continue;
+        }
+        if let Some(literal) = first_literal_argument(&pieces, open) {
+            found.push(literal.clone());
+        }
+    }
+
+    found
+}
+
+/// The path of what the bracket at `open` calls, such as `sqlx::query` or
+/// `query_as!` (macros keep their `!`). Generic arguments, as in
+/// `query_as::<_, User>`, are left out, and method calls don't count.
+fn callee(pieces: &[Piece], source: &str, open: usize) -> Option<String> {
+    let punct = |index: usize, c: char| matches!(pieces[index], Piece::Punct(p) if p == c);
+    let mut end = open;
+    let bang = end > 0 && punct(end - 1, '!');
+    if bang {
+        end -= 1;
+    } else if !punct(open, '(') {
+        return None;
+    } else if end > 0 && punct(end - 1, '>') {
+        let mut depth = 0;
+        loop {
+            end -= 1;
+            if punct(end, '>') {
+                depth += 1;
+            } else if punct(end, '<') {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            if end == 0 {
+                return None;
+            }
+        }
+        if end < 2 || !punct(end - 1, ':') || !punct(end - 2, ':') {
+            return None;
+        }
+        end -= 2;
+    }
+
+    let mut names = Vec::new();
+    let mut start = end;
+    loop {
+        match pieces.get(start.checked_sub(1)?)? {
+            Piece::Name(range) => names.push(&source[range.clone()]),
+            _ => return None,
+        }
+        start -= 1;
+        if start >= 3 && punct(start - 1, ':') && punct(start - 2, ':') && matches!(pieces[start - 3], Piece::Name(_)) {
+            start -= 2;
+        } else {
+            break;
+        }
+    }
+    if !bang && start > 0 && punct(start - 1, '.') {
+        return None;
+    }
+
+    names.reverse();
+    Some(format!("{}{}", names.join("::"), if bang { "!" } else { "" }))
+}
+
+/// The first argument of the call opened at `open` that is a lone string
+/// literal.
+fn first_literal_argument(pieces: &[Piece], open: usize) -> Option<&Literal> {
+    let mut depth = 0;
+    let mut argument_start = open + 1;
+    for (index, piece) in pieces.iter().enumerate().skip(open + 1) {
+        match piece {
+            Piece::Punct('(' | '[' | '{') => depth += 1,
+            Piece::Punct(')' | ']' | '}') if depth > 0 => depth -= 1,
+            Piece::Punct(',' | ')' | ']' | '}') if depth == 0 => {
+                if let (true, Piece::Str(literal)) = (index == argument_start + 1, &pieces[argument_start]) {
+                    return Some(literal);
+                }
+                if !matches!(piece, Piece::Punct(',')) {
+                    return None;
+                }
+                argument_start = index + 1;
+            },
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Splits Rust `source` into [`Piece`]s. Scanning stops at anything left
+/// open, such as an unterminated string.
+fn pieces(source: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut i = 0;
+
+    while let Some(c) = source[i..].chars().next() {
+        let rest = &source[i..];
+        if rest.starts_with("//") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if rest.starts_with("/*") {
+            match block_comment_len(rest) {
+                Some(len) => i += len,
+                None => break,
+            }
+        } else if c == '"' {
+            let Some(len) = escaped_string_len(rest) else { break };
+            pieces.push(Piece::Str(Literal { span: i..i + len, content: i + 1..i + len - 1, hashes: None }));
+            i += len;
+        } else if c == '\'' {
+            i += char_literal_len(rest);
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+            let name = &rest[..len];
+            let after = &rest[len..];
+            if (name == "r" || name == "br") && (after.starts_with('"') || after.starts_with("#\"") || after.starts_with("##")) {
+                let hashes = after.len() - after.trim_start_matches('#').len();
+                let open = len + hashes + 1;
+                let close = format!("\"{}", "#".repeat(hashes));
+                let Some(end) = rest[open..].find(&close) else { break };
+                if name == "r" {
+                    pieces.push(Piece::Str(Literal { span: i..i + open + end + close.len(), content: i + open..i + open + end, hashes: Some(hashes) }));
+                }
+                i += open + end + close.len();
+            } else if name == "b" && after.starts_with('"') {
+                let Some(string) = escaped_string_len(after) else { break };
+                i += len + string;
+            } else if name == "b" && after.starts_with('\'') {
+                i += len + char_literal_len(after);
+            } else if name == "r" && after.starts_with('#') {
+                // A raw identifier such as `r#type`
+                i += len + 1;
+            } else {
+                pieces.push(Piece::Name(i..i + len));
+                i += len;
+            }
+        } else if c.is_ascii_digit() {
+            i += rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+        } else {
+            if !c.is_whitespace() {
+                pieces.push(Piece::Punct(c));
+            }
+            i += c.len_utf8();
+        }
+    }
+
+    pieces
+}
+
+/// The length of the (possibly nested) block comment `rest` starts with.
+fn block_comment_len(rest: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < rest.len() {
+        if rest[i..].starts_with("/*") {
+            depth += 1;
+            i += 2;
+        } else if rest[i..].starts_with("*/") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return Some(i);
+            }
+        } else {
+            i += rest[i..].chars().next()?.len_utf8();
+        }
+    }
+    None
+}
+
+/// The length of the string literal with escapes `rest` starts with.
+fn escaped_string_len(rest: &str) -> Option<usize> {
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            },
+            '"' => return Some(i + 1),
+            _ => {},
+        }
+    }
+    None
+}
+
+/// The length of the character literal `rest` starts with, or 1 for the
+/// quote of a lifetime or label.
+fn char_literal_len(rest: &str) -> usize {
+    let mut chars = rest.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '\\')) => {
+            chars.next();
+            chars.find(|&(_, c)| c == '\'').map_or(rest.len(), |(i, _)| i + 1)
+        },
+        Some((_, c)) if rest[1 + c.len_utf8()..].starts_with('\'') => 2 + c.len_utf8(),
+        _ => 1,
+    }
+}
+
+/// The value of the contents of a string literal with escapes, or `None` if
+/// it has an escape Rust wouldn't accept.
+fn unescape(content: &str) -> Option<String> {
+    let mut value = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            '0' => value.push('\0'),
+            c @ ('\\' | '\'' | '"') => value.push(c),
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                let code = u8::from_str_radix(&digits, 16).ok().filter(|code| code.is_ascii())?;
+                value.push(char::from(code));
+            },
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let digits: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                value.push(char::from_u32(u32::from_str_radix(&digits.replace('_', ""), 16).ok()?)?);
+            },
+            '\n' => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            },
+            _ => return None,
+        }
+    }
+    Some(value)
+}
+
+/// `value` as the contents of a string literal with escapes. Newlines are
+/// written as they are, so the SQL keeps its layout in the source.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() && c != '\n' => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// `line` without up to `width` bytes of leading spaces and tabs.
+fn strip_indent(line: &str, width: usize) -> &str {
+    let whitespace = leading_whitespace(line).len().min(width);
+    &line[whitespace..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        format_source(source, &FormatOptions::default(), &mut StatementCounts::default(), &mut Vec::new(), &mut Vec::new())
+    }
+
+    #[test]
+    fn raw_string_is_formatted_at_the_indent_of_its_first_line() {
+        let source = "fn load() {\n    let rows = sqlx::query(r#\"\n        insert into t (a,b) values (1,'x'),(22,\"y\")\n    \"#);\n}\n";
+        let expected = "fn load() {\n    let rows = sqlx::query(r#\"\n        insert into t (a,b)\n        values\n        ( 1 , 'x'),\n        (22 , \"y\")\n    \"#);\n}\n";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn string_with_quote_escapes_keeps_them() {
+        let source = "let q = sqlx::query(\"insert into \\\"t\\\" (a,b) values (1,'x'),(22,'y')\");\n";
+        let expected = "let q = sqlx::query(\"insert into \\\"t\\\" (a,b)\nvalues\n( 1 , 'x'),\n(22 , 'y')\");\n";
+        assert_eq!(format(source), expected);
+    }
+
+    #[test]
+    fn string_with_escapes_that_would_not_round_trip_is_left_as_written() {
+        let source = "let q = sqlx::query(\"insert into t (a,b) values (1,'it''s'),(22,\\\"y\\\")\\n\");\n\
+                      let r = sqlx::query(\"insert into t (a,b) values (1,'\\u{e9}'),(22,'y')\");\n\
+                      let s = sqlx::query(\"insert into t (a,b) values (1,'\\'x\\''),(22,'y')\");\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn string_outside_a_sql_call_is_left_alone() {
+        let source = "let s = format!(\"insert into t (a,b) values (1,'x'),(22,'y')\");\nlet t = db.query(\"insert into t (a,b) values (1,'x'),(22,'y')\");\n";
+        assert_eq!(format(source), source);
+    }
+
+    #[test]
+    fn code_around_a_formatted_literal_is_byte_identical() {
+        let before = "// query(\"not sql\")\nfn  f( ) {\n\tlet x =   /* sqlx::query(\"x\") */ sqlx::query(r\"";
+        let after = "\" ) ;  \r\n    'a: loop { break 'a; }\n}\n";
+        let formatted = format(&format!("{}insert into t (a,b) values (1,'x'),(22,'y'){}", before, after));
+        assert!(formatted.starts_with(before) && formatted.ends_with(after), "{}", formatted);
+        assert!(formatted.contains("( 1 , 'x'),"), "{}", formatted);
+    }
+}
@@ -0,0 +1,787 @@
+use unicode_width::UnicodeWidthStr;
+
+use crate::lexer::{matching_paren, render, space_operators, split_top_level, strip_terminator, tokenize, top_level, Token, TokenKind, Words};
+use crate::statements::INDENT;
+use crate::FormatOptions;
+
+/// Keywords that start a top-level clause after the select list, in the
+/// order they appear in a query.
+const CLAUSES: [&[&str]; 12] = [
+    &["INTO"],
+    &["FROM"],
+    &["WHERE"],
+    &["GROUP", "BY"],
+    &["HAVING"],
+    &["WINDOW"],
+    &["ORDER", "BY"],
+    &["LIMIT"],
+    &["OFFSET"],
+    &["FETCH"],
+    &["FOR"],
+    &["LOCK", "IN", "SHARE", "MODE"],
+];
+
+/// Words that can come before `JOIN`.
+const JOIN_MODIFIERS: [&str; 7] = ["NATURAL", "INNER", "LEFT", "RIGHT", "FULL", "OUTER", "CROSS"];
+
+/// Keywords that start a part of a window specification.
+const WINDOW_CLAUSES: [&[&str]; 5] = [
+    &["PARTITION", "BY"],
+    &["ORDER", "BY"],
+    &["ROWS"],
+    &["RANGE"],
+    &["GROUPS"],
+];
+
+/// Operators that combine the results of two queries.
+const SET_OPERATIONS: [&str; 3] = ["UNION", "INTERSECT", "EXCEPT"];
+
+/// Formats a SELECT statement with [`format_query`], keeping its terminator.
+pub fn format_select_statement(tokens: &[Token], options: &FormatOptions, max_width: usize) -> Option<String> {
+    let (body, terminator) = strip_terminator(tokens);
+    format_query(body, options, max_width).map(|query| query + terminator)
+}
+
+/// Formats a query: a SELECT, laid out by [`format_select`], optionally
+/// preceded by `WITH` and its common table expressions. The CTEs go one level
+/// in under `WITH`, so each closing parenthesis lines up with its name, and
+/// each CTE's query is formatted the same way and indented one more level:
+///
+/// ```sql
+/// WITH
+///     active AS (
+///         SELECT id, name
+///         FROM users
+///         WHERE deleted_at IS NULL
+///     ),
+///     recent AS (
+///         SELECT user_id
+///         FROM orders
+///         WHERE created_at > now() - interval '7 days'
+///     )
+/// SELECT a.name
+/// FROM active a
+/// ```
+///
+/// Returns `None` for queries with comments, for those with a string that
+/// spans lines (indenting would change its value), and for anything it
+/// doesn't recognize.
+pub fn format_query(tokens: &[Token], options: &FormatOptions, max_width: usize) -> Option<String> {
+    if tokens.iter().any(|token| token.kind == TokenKind::Comment || token.kind == TokenKind::Quoted && token.text.contains('\n')) {
+        return None;
+    }
+    let mut words = Words::new(tokens);
+    if !words.skip(&["WITH"]) {
+        return format_set_operation(tokens, options, max_width);
+    }
+    words.skip(&["RECURSIVE"]);
+    let with = render(&tokens[..words.peek()?.0]);
+    let mut ctes = String::new();
+
+    // name [(columns)] AS [[NOT] MATERIALIZED] (query), ...
+    loop {
+        let name_start = words.peek()?.0;
+        words.object_name()?;
+        words.skip_group();
+        words.expect(&["AS"])?;
+        words.skip(&["NOT"]);
+        words.skip(&["MATERIALIZED"]);
+        let open = words.peek().filter(|(_, token)| token.is_symbol("("))?.0;
+        let close = words.skip_group()?;
+
+        let query = format_query(&tokens[open + 1..close], options, max_width.saturating_sub(2 * INDENT.len()))?;
+        ctes.push_str(&format!("{} (\n{}\n)", render(&tokens[name_start..open]), indented(&query)));
+
+        if !words.peek().is_some_and(|(_, token)| token.is_symbol(",")) {
+            break;
+        }
+        words.next();
+        ctes.push_str(",\n");
+    }
+
+    let main = words.peek()?.0;
+    Some(format!("{}\n{}\n{}", with, indented(&ctes), format_query(&tokens[main..], options, max_width)?))
+}
+
+/// Formats queries combined with `UNION`, `INTERSECT` or `EXCEPT`: each
+/// branch is formatted on its own, with the operator on a line between them.
+/// A parenthesized branch keeps its parentheses, with its query indented:
+///
+/// ```sql
+/// SELECT id
+/// FROM customers
+/// UNION ALL
+/// (
+///     SELECT id
+///     FROM suppliers
+///     LIMIT 10
+/// )
+/// ORDER BY id
+/// ```
+///
+/// An `ORDER BY` or `LIMIT` after the last branch applies to the whole
+/// result, and stays at the end at the same indent as the branches.
+fn format_set_operation(tokens: &[Token], options: &FormatOptions, max_width: usize) -> Option<String> {
+    let mut depth = 0usize;
+    let mut operators = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && SET_OPERATIONS.iter().any(|operation| token.is_keyword(operation)) {
+            operators.push(i);
+        }
+    }
+
+    let mut formatted = Vec::new();
+    let mut branch_start = 0;
+    for operator in operators {
+        formatted.push(format_branch(&tokens[branch_start..operator], options, max_width)?);
+
+        // UNION [ALL | DISTINCT]
+        let mut words = Words::new(&tokens[operator..]);
+        words.next();
+        let _ = words.skip(&["ALL"]) || words.skip(&["DISTINCT"]);
+        branch_start = operator + words.peek()?.0;
+        formatted.push(render(&tokens[operator..branch_start]));
+    }
+    formatted.push(format_branch(&tokens[branch_start..], options, max_width)?);
+
+    Some(formatted.join("\n"))
+}
+
+/// Formats one branch of a set operation: a SELECT, or a parenthesized query
+/// followed by any clauses that apply to the whole result.
+fn format_branch(tokens: &[Token], options: &FormatOptions, max_width: usize) -> Option<String> {
+    let mut words = Words::new(tokens);
+    let open = match words.peek() {
+        Some((open, token)) if token.is_symbol("(") => open,
+        _ => return format_select(tokens, options, max_width),
+    };
+    let close = words.skip_group()?;
+    let query = format_query(&tokens[open + 1..close], options, max_width.saturating_sub(INDENT.len()))?;
+
+    let mut lines = vec![format!("(\n{}\n)", indented(&query))];
+    let starts = clause_starts(tokens, close + 1)?;
+    if !tokens[close + 1..starts.first().copied().unwrap_or(tokens.len())].iter().all(Token::is_trivia) {
+        return None;
+    }
+    let mut bounds = starts;
+    bounds.push(tokens.len());
+    for pair in bounds.windows(2) {
+        lines.push(format_clause(&tokens[pair[0]..pair[1]], options, max_width));
+    }
+
+    Some(lines.join("\n"))
+}
+
+/// Formats a single SELECT with each top-level clause on its own line. The
+/// select list stays on the `SELECT` line if that fits in `max_width`, and
+/// otherwise goes one column per line:
+///
+/// ```sql
+/// SELECT id, name, email
+/// FROM users
+/// WHERE deleted_at IS NULL
+/// ORDER BY name
+/// ```
+///
+/// Subqueries, long `CASE` expressions, `IN` lists and window specifications
+/// are laid out by [`expression`]; other parenthesized parts, like function
+/// arguments, stay on one line. Returns `None` for queries with set
+/// operations, and for anything that doesn't start with `SELECT`.
+fn format_select(tokens: &[Token], options: &FormatOptions, max_width: usize) -> Option<String> {
+    let mut words = Words::new(tokens);
+    let (select, _) = words.next().filter(|(_, token)| token.is_keyword("SELECT"))?;
+
+    // SELECT [ALL | DISTINCT [ON (...)]]
+    let mut distinct_on = None;
+    if words.skip(&["DISTINCT", "ON"]) {
+        let open = words.peek()?.0;
+        distinct_on = Some((open, words.skip_group()?));
+    } else if !words.skip(&["DISTINCT"]) {
+        words.skip(&["ALL"]);
+    }
+    let list_start = words.peek().map_or(tokens.len(), |(index, _)| index);
+
+    let starts = clause_starts(tokens, list_start)?;
+    let list_end = starts.first().copied().unwrap_or(tokens.len());
+    let column_width = max_width.saturating_sub(INDENT.len());
+    let columns: Vec<String> = split_top_level(&tokens[list_start..list_end], ",")
+        .into_iter()
+        .map(|column| expression(column, options, column_width))
+        .collect();
+    if columns.iter().any(String::is_empty) {
+        return None;
+    }
+
+    let header = match distinct_on {
+        Some((open, close)) => {
+            let expressions: Vec<String> = split_top_level(&tokens[open + 1..close], ",").into_iter().map(render).collect();
+            format!("{} ({})", render(&tokens[select..open]), expressions.join(", "))
+        },
+        None => render(&tokens[select..list_start]),
+    };
+    let one_line = format!("{} {}", header, columns.join(", "));
+    let mut lines = vec![if fits(&one_line, max_width) || columns.len() == 1 && !one_line.contains('\n') {
+        one_line
+    } else {
+        let columns = if options.align_aliases { aligned_aliases(&tokens[list_start..list_end], options, column_width) } else { columns };
+        format!("{}\n{}", header, indented(&columns.join(",\n")))
+    }];
+
+    let mut bounds = starts.clone();
+    bounds.push(tokens.len());
+    for pair in bounds.windows(2) {
+        lines.push(format_clause(&tokens[pair[0]..pair[1]], options, max_width));
+    }
+
+    Some(lines.join("\n"))
+}
+
+/// The columns of a select list with their `AS` lined up, for
+/// `--align-aliases`:
+///
+/// ```sql
+/// SELECT
+///     customer_id,
+///     SUM(amount) AS total_amount,
+///     COUNT(*)    AS order_count
+/// ```
+///
+/// Columns without an `AS`, and those whose expression spans several lines,
+/// are left as they are and don't count towards the width.
+fn aligned_aliases(tokens: &[Token], options: &FormatOptions, max_width: usize) -> Vec<String> {
+    let columns: Vec<(String, Option<String>)> = split_top_level(tokens, ",")
+        .into_iter()
+        .map(|column| match top_level(column, 0).into_iter().find(|&i| column[i].is_keyword("AS")) {
+            Some(alias) => (expression(&column[..alias], options, max_width), Some(render(&column[alias..]))),
+            None => (expression(column, options, max_width), None),
+        })
+        .collect();
+
+    let width = columns
+        .iter()
+        .filter(|(expression, alias)| alias.is_some() && !expression.contains('\n'))
+        .map(|(expression, _)| expression.width())
+        .max()
+        .unwrap_or(0);
+    columns
+        .into_iter()
+        .map(|(expression, alias)| match alias {
+            Some(alias) if !expression.contains('\n') => format!("{}{} {}", expression, " ".repeat(width - expression.width()), alias),
+            Some(alias) => format!("{} {}", expression, alias),
+            None => expression,
+        })
+        .collect()
+}
+
+/// Formats one clause of a SELECT, starting with its keyword.
+pub fn format_clause(tokens: &[Token], options: &FormatOptions, max_width: usize) -> String {
+    if tokens[0].is_keyword("FROM") {
+        return format_from(tokens, options, max_width);
+    }
+    if tokens[0].is_keyword("WHERE") || tokens[0].is_keyword("HAVING") {
+        return format_condition(tokens, options, max_width);
+    }
+    let mut words = Words::new(tokens);
+    // LIMIT takes MySQL's `LIMIT offset, count` too
+    if words.skip(&["GROUP", "BY"]) || words.skip(&["ORDER", "BY"]) || words.skip(&["LIMIT"]) {
+        if let Some((start, _)) = words.peek() {
+            return format_list(tokens, start, options, max_width);
+        }
+    }
+    expression(tokens, options, max_width)
+}
+
+/// Formats a clause whose items, from `start` on, are a comma-separated list
+/// such as `GROUP BY`, `ORDER BY` or `LIMIT`. Items are split at top-level commas, so
+/// `ORDER BY COALESCE(a, b), CAST(c AS DECIMAL(10,2))` has two, and are
+/// separated by a comma and a single space.
+fn format_list(tokens: &[Token], start: usize, options: &FormatOptions, max_width: usize) -> String {
+    let items: Vec<String> = split_top_level(&tokens[start..], ",")
+        .into_iter()
+        .map(|item| expression(item, options, max_width))
+        .collect();
+    if items.iter().any(String::is_empty) {
+        return expression(tokens, options, max_width);
+    }
+    format!("{} {}", render(&tokens[..start]), items.join(", "))
+}
+
+/// Formats a `WHERE` or `HAVING` clause, starting with its keyword. If the
+/// clause is longer than `max_width`, or combines more than two conditions,
+/// each top-level `AND` and `OR` starts a line of its own, right-aligned with
+/// the keyword so the conditions line up:
+///
+/// ```sql
+/// WHERE o.status = 'open'
+///   AND o.total BETWEEN 10 AND 100
+///    OR (o.priority = 'high' AND o.flagged)
+/// ```
+///
+/// Conditions in parentheses and the `AND` of `BETWEEN ... AND` are never
+/// split. The lines of a condition that runs over several, such as a
+/// subquery, move right with its operator, so the closing parenthesis lines
+/// up with the line that opened it:
+///
+/// ```sql
+/// WHERE a = 1
+///   AND id IN (
+///       SELECT user_id
+///       FROM banned
+///   )
+/// ```
+pub fn format_condition(tokens: &[Token], options: &FormatOptions, max_width: usize) -> String {
+    let tokens = &operator_spacing(tokens, options);
+    let one_line = expression(tokens, options, max_width);
+
+    let mut operators = Vec::new();
+    let mut between = false;
+    for i in top_level(tokens, 1) {
+        if tokens[i].is_keyword("BETWEEN") {
+            between = true;
+        } else if tokens[i].is_keyword("AND") && between {
+            between = false;
+        } else if tokens[i].is_keyword("AND") || tokens[i].is_keyword("OR") {
+            operators.push(i);
+        }
+    }
+    if operators.is_empty() || operators.len() == 1 && fits(&one_line, max_width) {
+        return one_line;
+    }
+
+    let keyword_width = tokens[0].text.width();
+    let mut lines = vec![expression(&tokens[..operators[0]], options, max_width)];
+    let mut bounds = operators;
+    bounds.push(tokens.len());
+    for pair in bounds.windows(2) {
+        let operator = tokens[pair[0]].text;
+        let padding = " ".repeat(keyword_width.saturating_sub(operator.width()));
+        lines.push(format!("{}{}", padding, shifted(&expression(&tokens[pair[0]..pair[1]], options, max_width), &padding)));
+    }
+
+    lines.join("\n")
+}
+
+/// Formats a FROM clause with each join on its own line, indented under
+/// `FROM`. A join's `ON` or `USING` condition stays on its line unless that
+/// gets longer than `max_width`, in which case it goes one level further in:
+///
+/// ```sql
+/// FROM orders o
+///     JOIN customers c ON c.id = o.customer_id
+///     LEFT JOIN addresses a
+///         ON a.customer_id = c.id AND a.kind = 'shipping' AND a.deleted_at IS NULL
+/// ```
+pub fn format_from(tokens: &[Token], options: &FormatOptions, max_width: usize) -> String {
+    let mut joins = Vec::new();
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate() {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && token.is_keyword("JOIN") {
+            // The join starts at its first modifier: NATURAL LEFT OUTER JOIN
+            let mut start = i;
+            for (j, previous) in tokens[..i].iter().enumerate().rev() {
+                if JOIN_MODIFIERS.iter().any(|modifier| previous.is_keyword(modifier)) {
+                    start = j;
+                } else if !previous.is_trivia() {
+                    break;
+                }
+            }
+            joins.push(start);
+        }
+    }
+
+    let mut lines = vec![expression(&tokens[..joins.first().copied().unwrap_or(tokens.len())], options, max_width)];
+    let mut bounds = joins;
+    bounds.push(tokens.len());
+    let join_width = max_width.saturating_sub(INDENT.len());
+    for pair in bounds.windows(2) {
+        let mut join = tokens[pair[0]..pair[1]].to_vec();
+        let condition = top_level(&join, 0).into_iter().find(|&i| join[i].is_keyword("ON") || join[i].is_keyword("USING"));
+        if let Some(condition) = condition.filter(|&i| join[i].is_keyword("ON")) {
+            let spaced = operator_spacing(&join[condition..], options);
+            join.splice(condition.., spaced);
+        }
+        let join = &join[..];
+        let one_line = expression(join, options, join_width);
+        match condition {
+            Some(condition) if !fits(&one_line, join_width) && !one_line.contains('\n') => {
+                lines.push(indented(&expression(&join[..condition], options, join_width)));
+                lines.push(indented(&indented(&expression(&join[condition..], options, join_width.saturating_sub(INDENT.len())))));
+            },
+            _ => lines.push(indented(&one_line)),
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// `tokens`, with the operators outside parentheses spaced out by
+/// [`space_operators`] if `options.normalize_operator_spacing` asks for it.
+pub fn operator_spacing<'a>(tokens: &[Token<'a>], options: &FormatOptions) -> Vec<Token<'a>> {
+    if options.normalize_operator_spacing {
+        space_operators(tokens)
+    } else {
+        tokens.to_vec()
+    }
+}
+
+/// Renders `tokens` like [`render`], except that each parenthesized subquery
+/// is formatted with [`format_query`] and indented one level under the line
+/// it starts on, with its closing parenthesis back at that line's indent:
+///
+/// ```sql
+/// WHERE id IN (
+///     SELECT user_id
+///     FROM banned
+/// )
+/// ```
+fn expression(tokens: &[Token], options: &FormatOptions, max_width: usize) -> String {
+    let mut text = String::new();
+    // Width of the current line of `text` up to `measured`
+    let (mut column, mut measured) = (0, 0);
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        if let Some((close, query)) = subquery(tokens, i, options, max_width) {
+            text.push_str(&format!("(\n{}\n)", indented(&query)));
+            i = close + 1;
+            continue;
+        }
+        if let Some((end, case)) = case_expression(tokens, i, options, max_width) {
+            text.push_str(&case);
+            i = end + 1;
+            continue;
+        }
+        // Only what was added since the last `(` is measured, so that long
+        // lines don't take quadratic time
+        if token.is_symbol("(") {
+            if let Some(newline) = text[measured..].rfind('\n') {
+                measured += newline + 1;
+                column = 0;
+            }
+            column += text[measured..].width();
+            measured = text.len();
+            if let Some((close, list)) = in_list(tokens, i, column, options, max_width) {
+                text.push_str(&list);
+                i = close + 1;
+                continue;
+            }
+            if let Some((close, window)) = window_spec(tokens, i, column, options, max_width) {
+                text.push_str(&window);
+                i = close + 1;
+                continue;
+            }
+        }
+
+        if token.kind == TokenKind::Whitespace {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+        } else {
+            text.push_str(token.text);
+        }
+        i += 1;
+    }
+    text.truncate(text.trim_end().len());
+    text
+}
+
+/// The index of the closing parenthesis and the formatted query, if the
+/// token at `open` opens a parenthesized SELECT.
+fn subquery(tokens: &[Token], open: usize, options: &FormatOptions, max_width: usize) -> Option<(usize, String)> {
+    if !tokens[open].is_symbol("(") {
+        return None;
+    }
+    let close = matching_paren(tokens, open)?;
+    let inner = &tokens[open + 1..close];
+    let (_, first) = Words::new(inner).peek()?;
+    if !first.is_keyword("SELECT") && !first.is_keyword("WITH") {
+        return None;
+    }
+    Some((close, format_query(inner, options, max_width.saturating_sub(INDENT.len()))?))
+}
+
+/// The index of the `END` and the formatted expression, if the token at
+/// `case` starts a CASE expression longer than `--inline-case-width`. Each
+/// `WHEN` and the `ELSE` go on a line of their own, one level in, and `END`
+/// comes back to the indent of the line `CASE` is on:
+///
+/// ```sql
+/// CASE
+///     WHEN total > 1000 THEN 'large'
+///     WHEN total > 100 THEN 'medium'
+///     ELSE 'small'
+/// END AS size
+/// ```
+fn case_expression(tokens: &[Token], case: usize, options: &FormatOptions, max_width: usize) -> Option<(usize, String)> {
+    if !tokens[case].is_keyword("CASE") {
+        return None;
+    }
+
+    // WHEN and ELSE of this CASE, not of one nested in it
+    let mut branches = Vec::new();
+    let mut nesting = 0usize;
+    let mut depth = 0usize;
+    let mut end = None;
+    for (i, token) in tokens.iter().enumerate().skip(case) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth = depth.saturating_sub(1);
+        } else if token.is_keyword("CASE") {
+            nesting += 1;
+        } else if token.is_keyword("END") {
+            nesting -= 1;
+            if nesting == 0 {
+                end = Some(i);
+                break;
+            }
+        } else if nesting == 1 && depth == 0 && (token.is_keyword("WHEN") || token.is_keyword("ELSE")) {
+            branches.push(i);
+        }
+    }
+    let end = end?;
+    if branches.is_empty() || render(&tokens[case..=end]).width() <= options.inline_case_width {
+        return None;
+    }
+
+    let head = render(&tokens[case..branches[0]]);
+    let mut bounds = branches;
+    bounds.push(end);
+    let lines: Vec<String> = bounds
+        .windows(2)
+        .map(|pair| expression(&tokens[pair[0]..pair[1]], options, max_width.saturating_sub(INDENT.len())))
+        .collect();
+
+    Some((end, format!("{}\n{}\n{}", head, indented(&lines.join("\n")), tokens[end].text)))
+}
+
+/// The index of the closing parenthesis and the formatted window, if the
+/// token at `open` opens the window of an `OVER`, or of a named window in a
+/// `WINDOW` clause, that would go past `max_width` when it starts at
+/// `column`. `PARTITION BY`, `ORDER BY` and the frame each go on a line of
+/// their own, one level in:
+///
+/// ```sql
+/// ROW_NUMBER() OVER (
+///     PARTITION BY customer_id
+///     ORDER BY created_at DESC
+///     ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+/// ) AS rn
+/// ```
+fn window_spec(tokens: &[Token], open: usize, column: usize, options: &FormatOptions, max_width: usize) -> Option<(usize, String)> {
+    if !tokens[open].is_symbol("(") {
+        return None;
+    }
+    let previous = tokens[..open].iter().rev().find(|token| !token.is_trivia())?;
+    if !previous.is_keyword("OVER") && !previous.is_keyword("AS") {
+        return None;
+    }
+    let close = matching_paren(tokens, open)?;
+    let inner = &tokens[open + 1..close];
+    let starts: Vec<usize> = top_level(inner, 0)
+        .into_iter()
+        .filter(|&i| {
+            let mut words = Words::new(&inner[i..]);
+            WINDOW_CLAUSES.iter().any(|keywords| words.skip(keywords))
+        })
+        .collect();
+    if starts.is_empty() || fits(&render(&tokens[open..=close]), max_width.saturating_sub(column)) {
+        return None;
+    }
+
+    // An existing window the spec builds on goes first: OVER (w ORDER BY ...)
+    let mut lines = Vec::new();
+    if !inner[..starts[0]].iter().all(Token::is_trivia) {
+        lines.push(render(&inner[..starts[0]]));
+    }
+    let mut bounds = starts;
+    bounds.push(inner.len());
+    for pair in bounds.windows(2) {
+        lines.push(expression(&inner[pair[0]..pair[1]], options, max_width.saturating_sub(INDENT.len())));
+    }
+
+    Some((close, format!("(\n{}\n)", indented(&lines.join("\n")))))
+}
+
+/// The index of the closing parenthesis and the formatted list, if the token
+/// at `open` opens the value list of an `IN` with more than
+/// `--inline-in-values` values, or one that would go past `max_width` when
+/// it starts at `column`. The values go `--in-values-per-line` to a line
+/// (fewer if that many don't fit), one level in, with each column of values
+/// lined up:
+///
+/// ```sql
+/// WHERE id IN (
+///     1,  2,  3,  5,  8,
+///     13, 21, 34, 55, 89
+/// )
+/// ```
+fn in_list(tokens: &[Token], open: usize, column: usize, options: &FormatOptions, max_width: usize) -> Option<(usize, String)> {
+    if !tokens[open].is_symbol("(") || !tokens[..open].iter().rev().find(|token| !token.is_trivia())?.is_keyword("IN") {
+        return None;
+    }
+    let close = matching_paren(tokens, open)?;
+    let inner = &tokens[open + 1..close];
+    let (_, first) = Words::new(inner).peek()?;
+    if first.is_keyword("SELECT") || first.is_keyword("WITH") {
+        return None;
+    }
+
+    let values: Vec<String> = split_top_level(inner, ",").into_iter().map(render).collect();
+    if values.iter().any(|value| value.is_empty() || value.contains('\n')) {
+        return None;
+    }
+    if values.len() <= options.inline_in_values && fits(&format!("({})", values.join(", ")), max_width.saturating_sub(column)) {
+        return None;
+    }
+
+    let mut per_line = options.in_values_per_line.max(1);
+    loop {
+        let lines = value_lines(&values, per_line);
+        if per_line == 1 || lines.iter().all(|line| line.width() <= max_width.saturating_sub(INDENT.len())) {
+            return Some((close, format!("(\n{}\n)", indented(&lines.join("\n")))));
+        }
+        per_line -= 1;
+    }
+}
+
+/// `values` laid out `per_line` to a line, with each column lined up and a
+/// comma after all but the last.
+fn value_lines(values: &[String], per_line: usize) -> Vec<String> {
+    let mut widths = vec![0; per_line];
+    for (i, value) in values.iter().enumerate() {
+        widths[i % per_line] = widths[i % per_line].max(value.width());
+    }
+    let count = values.len();
+    values
+        .chunks(per_line)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let mut line = String::new();
+            for (column, value) in chunk.iter().enumerate() {
+                line.push_str(value);
+                if row * per_line + column + 1 < count {
+                    line.push(',');
+                }
+                if column + 1 < chunk.len() {
+                    line.push_str(&" ".repeat(widths[column] - value.width() + 1));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Whether `text` is a single line no wider than `max_width`.
+pub fn fits(text: &str, max_width: usize) -> bool {
+    !text.contains('\n') && text.width() <= max_width
+}
+
+/// Ends each of `lines` with its comment, if it has one, after a space.
+/// With `options.align_trailing_comments` the comments start in one column
+/// instead, two spaces past the widest line that has one; a line that would
+/// then run past `options.max_width` keeps the single space and leaves the
+/// column alone. `indent` is the width the lines are indented by. A line
+/// with a newline in it, from a string, is measured by its last line.
+pub fn end_with_comments(lines: &mut [String], comments: &[Option<&str>], indent: usize, options: &FormatOptions) {
+    let width = |line: &str| match line.rsplit_once('\n') {
+        Some((_, last)) => last.width(),
+        None => indent + line.width(),
+    };
+    let column = if options.align_trailing_comments {
+        lines.iter()
+            .zip(comments)
+            .filter_map(|(line, comment)| comment.map(|comment| (width(line) + 2, comment)))
+            .filter(|&(column, comment)| column + comment.width() <= options.max_width)
+            .map(|(column, _)| column)
+            .max()
+            .unwrap_or(0)
+    } else {
+        0
+    };
+    for (line, comment) in lines.iter_mut().zip(comments) {
+        if let Some(comment) = comment {
+            let padding = column.saturating_sub(width(line)).max(1);
+            line.push_str(&" ".repeat(padding));
+            line.push_str(comment);
+        }
+    }
+}
+
+/// Indices of the tokens that start each top-level clause after `from`.
+/// Returns `None` if the query has a set operation.
+fn clause_starts(tokens: &[Token], from: usize) -> Option<Vec<usize>> {
+    let mut starts = Vec::new();
+    let mut depth = 0usize;
+
+    for (i, token) in tokens.iter().enumerate().skip(from) {
+        if token.is_symbol("(") {
+            depth += 1;
+        } else if token.is_symbol(")") {
+            depth = depth.saturating_sub(1);
+        } else if depth == 0 && token.kind == TokenKind::Word {
+            if SET_OPERATIONS.iter().any(|operation| token.is_keyword(operation)) {
+                return None;
+            }
+            // `a IS DISTINCT FROM b` is a comparison, not a FROM clause
+            let after_distinct = tokens[..i].iter().rev().find(|token| !token.is_trivia()).is_some_and(|previous| previous.is_keyword("DISTINCT"));
+            if !after_distinct && CLAUSES.iter().any(|keywords| Words::new(&tokens[i..]).expect(keywords).is_some()) {
+                starts.push(i);
+            }
+        }
+    }
+
+    Some(starts)
+}
+
+/// `text` with `padding` after each newline between its tokens, so its
+/// lines follow the first when that is moved right. Newlines in strings and
+/// block comments belong to them and get nothing.
+fn shifted(text: &str, padding: &str) -> String {
+    if padding.is_empty() || !text.contains('\n') {
+        return text.to_string();
+    }
+    tokenize(text)
+        .iter()
+        .map(|token| if token.kind == TokenKind::Whitespace { token.text.replace('\n', &format!("\n{}", padding)) } else { token.text.to_string() })
+        .collect()
+}
+
+/// `text` with every non-empty line indented one level.
+pub fn indented(text: &str) -> String {
+    text.lines()
+        .map(|line| if line.is_empty() { String::new() } else { format!("{}{}", INDENT, line) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(sql: &str) -> String {
+        format_select_statement(&tokenize(sql), &FormatOptions::default(), 100).unwrap()
+    }
+
+    #[test]
+    fn subquery_closes_under_the_line_that_opens_it() {
+        let sql = "SELECT a FROM t WHERE a = 1 AND id IN (SELECT user_id FROM banned) AND b = 2";
+        let expected = "SELECT a\nFROM t\nWHERE a = 1\n  AND id IN (\n      SELECT user_id\n      FROM banned\n  )\n  AND b = 2";
+        assert_eq!(format(sql), expected);
+    }
+
+    #[test]
+    fn case_ends_under_the_line_that_starts_it() {
+        let sql = "SELECT a FROM t WHERE a = 1 OR b = CASE WHEN x > 1000000000000000 THEN 'large' WHEN x > 100 THEN 'medium' ELSE 'small' END";
+        let expected = "SELECT a\nFROM t\nWHERE a = 1\n   OR b = CASE\n       WHEN x > 1000000000000000 THEN 'large'\n       WHEN x > 100 THEN 'medium'\n       ELSE 'small'\n   END";
+        assert_eq!(format(sql), expected);
+    }
+}
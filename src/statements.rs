@@ -0,0 +1,1466 @@
+use std::ops::{Range, RangeInclusive};
+
+use crate::counts::{StatementCounts, StatementKind};
+use crate::ddl;
+use crate::dml;
+use crate::error::{arity_message, FormatError, StatementError};
+use crate::grant;
+use crate::select;
+use crate::template::Masked;
+use crate::lexer::{self, tokenize, Token, TokenKind};
+use crate::{BlankLines, FinalNewline, FormatOptions, TypeCase};
+
+/// Indentation of the lines under a statement's first line.
+pub const INDENT: &str = "    ";
+
+/// Reformats every statement some formatter knows how to handle and leaves
+/// the rest of `sql` (other statements, comments and blank lines between
+/// statements) exactly as it was. Fails, leaving the file alone, if a VALUES
+/// row of an INSERT never closes. A row with a different number of values
+/// than its column list adds to `warnings`, or fails with `options.strict`.
+///
+/// With `options.merge_inserts`, runs of INSERTs into the same table are
+/// merged into one before anything is formatted, and with
+/// `options.split_inserts` INSERTs with too many rows are split into several.
+///
+/// With `options.line_ranges`, only statements overlapping one of the ranges
+/// are checked and formatted; a range past the end of `sql` is an error.
+/// Statements of a kind `options.only_kinds` and `options.skip_kinds` rule
+/// out aren't either, and neither are statements protected by a
+/// `-- sql-fmt: off` or `-- sql-fmt: skip` comment.
+///
+/// With `options.indent_transactions`, what's between `BEGIN` and `COMMIT`
+/// is indented one level.
+///
+/// Jinja template syntax is masked before anything else and put back at the
+/// end, as [`Masked`] describes.
+pub fn format_statements(sql: &str, options: &FormatOptions, counts: &mut StatementCounts, warnings: &mut Vec<String>, incidents: &mut Vec<Incident>) -> Result<String, FormatError> {
+    if let Some(masked) = Masked::new(sql) {
+        let first_incident = incidents.len();
+        let formatted = format_statements(&masked.sql, options, counts, warnings, incidents)?;
+        for incident in &mut incidents[first_incident..] {
+            incident.text = masked.restore(&incident.text).unwrap_or_else(|| incident.text.clone());
+        }
+        return masked.restore(&formatted).ok_or_else(|| FormatError::Internal("a template placeholder was changed by formatting".to_string()));
+    }
+
+    // Transactions are indented afresh on every run, so their statements
+    // reach the formatters flat however the file was indented before
+    let indent_transactions = options.indent_transactions && options.line_ranges.is_empty();
+    let dedented;
+    let sql = if indent_transactions {
+        dedented = shift_transactions(sql, false);
+        dedented.as_str()
+    } else {
+        sql
+    };
+
+    let statements = split_statements(sql);
+    let protected = protected_statements(sql, &statements);
+    let lines = LineIndex::new(sql);
+    if let Some(outside) = options.line_ranges.iter().find(|selected| *selected.end() > lines.count()) {
+        return Err(FormatError::LinesOutOfRange { start: *outside.start(), end: *outside.end(), lines: lines.count() });
+    }
+    for (range, &protected) in statements.iter().zip(&protected) {
+        // Statements left alone, such as oversized ones or those outside the
+        // lines to format, keep their problems too
+        let text = &sql[range.clone()];
+        if protected || is_oversized(range, options) || !is_selected(&lines, range, options) || !is_enabled(text, options) {
+            continue;
+        }
+        if let Some(row) = dml::unclosed_row(text) {
+            return Err(FormatError::UnbalancedSql { line: sql[..range.start + row].matches('\n').count() + 1 });
+        }
+        for mismatch in dml::arity_mismatches(text) {
+            let line = sql[..range.start + mismatch.offset].matches('\n').count() + 1;
+            if options.strict {
+                return Err(FormatError::ArityMismatch { line, row: mismatch.row, expected: mismatch.expected, actual: mismatch.actual });
+            }
+            warnings.push(format!("line {}: {}", line, arity_message(mismatch.row, mismatch.expected, mismatch.actual)));
+        }
+    }
+
+    // Both transforms change the statements, so the result is split afresh.
+    // Protected statements are left out, which also ends a run of merged
+    // INSERTs at one.
+    let mut transformed: Option<String> = None;
+    if options.merge_inserts {
+        transformed = Some(dml::merge_inserts(sql, &unprotected_statements(sql), options));
+    }
+    if let Some(batch) = options.split_inserts.filter(|&batch| batch > 0) {
+        let current = transformed.as_deref().unwrap_or(sql);
+        let statements = unprotected_statements(current);
+        transformed = Some(dml::split_inserts(current, &statements, &delimiters(current, &statements), batch));
+    }
+    let (input, formatted) = match transformed.as_deref().filter(|&transformed| transformed != sql) {
+        Some(transformed) => (transformed, format_split(transformed, split_statements(transformed), options, counts, warnings, incidents)?),
+        None => (sql, format_split(sql, statements, options, counts, warnings, incidents)?),
+    };
+    let formatted = if indent_transactions { shift_transactions(&formatted, true) } else { formatted };
+
+    // Blank lines, trailing whitespace and the end of the file are
+    // whole-file matters, so `--lines` leaves them be
+    let mut formatted = match options.blank_lines {
+        BlankLines::Max(max) if options.line_ranges.is_empty() => space_statements(&formatted, max),
+        _ => formatted,
+    };
+    if options.line_ranges.is_empty() {
+        if options.trim_trailing_whitespace {
+            formatted = trim_trailing_whitespace(&formatted);
+        }
+        end_file(&mut formatted, options.final_newline);
+    }
+
+    // Everything but whitespace must come out as it went in. The transforms
+    // above change statements on purpose, so it's their result that counts.
+    if options.verify_tokens {
+        if let Some((offset, token)) = changed_token(input, &formatted, options) {
+            return Err(FormatError::TokensChanged { line: input[..offset].matches('\n').count() + 1, token });
+        }
+    }
+    Ok(formatted)
+}
+
+/// Formats SQL taken from another kind of file, such as a string literal in
+/// Rust or a code block in Markdown, like [`format_statements`], except that
+/// it always ends as it did and `options.line_ranges` don't apply. Warnings
+/// and incidents are moved down `offset` lines, to point into the file, and
+/// `counts` only grows if the SQL changed.
+pub fn format_embedded(sql: &str, options: &FormatOptions, offset: usize, counts: &mut StatementCounts, warnings: &mut Vec<String>, incidents: &mut Vec<Incident>) -> Result<String, FormatError> {
+    let options = FormatOptions { final_newline: FinalNewline::Preserve, line_ranges: Vec::new(), ..options.clone() };
+    let (first_warning, first_incident) = (warnings.len(), incidents.len());
+    let mut reformatted = StatementCounts::default();
+    let formatted = format_statements(sql, &options, &mut reformatted, warnings, incidents)?;
+
+    for warning in &mut warnings[first_warning..] {
+        *warning = shift_line(warning, offset);
+    }
+    for incident in &mut incidents[first_incident..] {
+        incident.error.line += offset;
+    }
+    if formatted != sql {
+        counts.add(reformatted);
+    }
+    Ok(formatted)
+}
+
+/// Moves the `line N:` a warning starts with down by `offset` lines.
+fn shift_line(warning: &str, offset: usize) -> String {
+    let shifted = warning.strip_prefix("line ")
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(number, rest)| Some((number.parse::<usize>().ok()?, rest)));
+    match shifted {
+        Some((number, rest)) => format!("line {}:{}", number + offset, rest),
+        None => warning.to_string(),
+    }
+}
+
+/// Where the lines of a text start, to turn byte offsets into 1-based line
+/// numbers without counting from the start every time.
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        LineIndex { starts }
+    }
+
+    /// The number of lines, counting the empty one after a final newline,
+    /// as editors do.
+    pub fn count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The line `offset` is on.
+    pub fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset)
+    }
+
+    /// The lines the statement at `range`, as found by [`split_statements`],
+    /// covers.
+    pub fn span(&self, range: &Range<usize>) -> RangeInclusive<usize> {
+        self.line(range.start)..=self.line(range.end.saturating_sub(1).max(range.start))
+    }
+}
+
+/// Whether the statement at `range` overlaps `options.line_ranges`, or there
+/// are none, so every statement is formatted.
+fn is_selected(lines: &LineIndex, range: &Range<usize>, options: &FormatOptions) -> bool {
+    if options.line_ranges.is_empty() {
+        return true;
+    }
+    let span = lines.span(range);
+    options.line_ranges.iter().any(|selected| span.start() <= selected.end() && selected.start() <= span.end())
+}
+
+/// Moves the lines between each `BEGIN` (or `START TRANSACTION`) and the
+/// `COMMIT`, `ROLLBACK` or `END` that closes it one level in, or with
+/// `indent` false one level back out. A `BEGIN` inside a transaction and
+/// savepoints add no level, and a transaction that is never closed isn't
+/// indented. Lines that start inside a string or comment, `COPY` data,
+/// meta-commands and protected statements stay as they are.
+fn shift_transactions(sql: &str, indent: bool) -> String {
+    let statements = split_statements(sql);
+    let mut transactions = Vec::new();
+    let mut open = None;
+    for range in &statements {
+        match transaction_control(&sql[range.clone()]) {
+            Some(true) if open.is_none() => open = Some(range.end),
+            Some(false) => {
+                if let Some(start) = open.take() {
+                    transactions.push(start..range.start);
+                }
+            },
+            _ => {},
+        }
+    }
+    if transactions.is_empty() {
+        return sql.to_string();
+    }
+
+    let protected = protected_statements(sql, &statements);
+    let mut kept: Vec<Range<usize>> = statements.iter().zip(&protected).filter(|&(_, &protected)| protected).map(|(range, _)| range.clone()).collect();
+    kept.extend(opaque_blocks(sql).into_iter().map(|block| block.range));
+    let mut continued = Vec::new();
+    for piece in scan(sql) {
+        if let Scanned::Token(offset, token) = piece {
+            if matches!(token.kind, TokenKind::Quoted | TokenKind::Comment) {
+                continued.extend(token.text.match_indices('\n').map(|(i, _)| offset + i + 1));
+            }
+        }
+    }
+
+    let mut shifted = String::with_capacity(sql.len() + sql.len() / 8);
+    let mut line_start = 0;
+    for line in sql.split_inclusive('\n') {
+        let code = line_start + line.len() - line.trim_start().len();
+        let moves = !line.trim().is_empty()
+            && transactions.iter().any(|transaction| transaction.contains(&code))
+            && !kept.iter().any(|range| range.contains(&code))
+            && continued.binary_search(&line_start).is_err();
+        if moves && indent {
+            shifted.push_str(INDENT);
+            shifted.push_str(line);
+        } else if moves {
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            shifted.push_str(&line[spaces.min(INDENT.len())..]);
+        } else {
+            shifted.push_str(line);
+        }
+        line_start += line.len();
+    }
+    shifted
+}
+
+/// Whether the statement `text` starts a transaction (`Some(true)`) or
+/// ends one (`Some(false)`). `ROLLBACK TO` a savepoint does neither.
+fn transaction_control(text: &str) -> Option<bool> {
+    let tokens = tokenize(text);
+    let words: Vec<&Token> = tokens.iter().filter(|token| token.kind == TokenKind::Word).collect();
+    let first = words.first()?;
+    if first.is_keyword("BEGIN") || first.is_keyword("START") && words.get(1).is_some_and(|word| word.is_keyword("TRANSACTION")) {
+        return Some(true);
+    }
+    let ends = ["COMMIT", "END", "ABORT"].iter().any(|keyword| first.is_keyword(keyword))
+        || first.is_keyword("ROLLBACK") && !words.iter().any(|word| word.is_keyword("TO"));
+    ends.then_some(false)
+}
+
+/// Caps each run of blank lines between the statements of `sql`, and
+/// before the first and after the last, at `max`. Where two statements of
+/// different kinds follow each other on lines of their own, makes sure at
+/// least one blank line separates them (unless `max` is 0), but not when a
+/// psql meta-command sits between them. Blank lines next to a protected
+/// statement, inside statements, and in `COPY` data are left as they are.
+fn space_statements(sql: &str, max: usize) -> String {
+    let statements = split_statements(sql);
+    let protected = protected_statements(sql, &statements);
+    let data = opaque_blocks(sql);
+
+    let mut spaced = String::with_capacity(sql.len());
+    for i in 0..=statements.len() {
+        let start = if i == 0 { 0 } else { statements[i - 1].end };
+        let end = statements.get(i).map_or(sql.len(), |range| range.start);
+        if i > 0 {
+            spaced.push_str(&sql[statements[i - 1].clone()]);
+        }
+        if (i > 0 && protected[i - 1]) || protected.get(i).copied().unwrap_or(false) {
+            spaced.push_str(&sql[start..end]);
+            continue;
+        }
+        let blocks: Vec<&OpaqueBlock> = data.iter().filter(|block| block.range.start >= start && block.range.end <= end).collect();
+        let separate = max > 0
+            && i > 0
+            && i < statements.len()
+            && !blocks.iter().any(|block| block.kind == OpaqueKind::MetaCommand)
+            && statement_kind(&sql[statements[i - 1].clone()]) != statement_kind(&sql[statements[i].clone()]);
+
+        // COPY data splits the gap into pieces, and only the last is next to
+        // the statement after it
+        let mut segment_start = start;
+        for block in blocks {
+            spaced.push_str(&space_gap(&sql[segment_start..block.range.start], segment_start == 0, max, false));
+            spaced.push_str(&sql[block.range.clone()]);
+            segment_start = block.range.end;
+        }
+        spaced.push_str(&space_gap(&sql[segment_start..end], segment_start == 0, max, separate));
+    }
+
+    spaced
+}
+
+/// How many blank lines the whitespace `gap` between two statements has
+/// once `options.blank_lines` has capped it, so that statements grouped by
+/// the blank lines between them group the same way on a second run.
+pub fn spaced_blank_lines(gap: &str, options: &FormatOptions) -> usize {
+    let blank_lines = gap.matches('\n').count().saturating_sub(1);
+    match options.blank_lines {
+        BlankLines::Max(max) if options.line_ranges.is_empty() => blank_lines.min(max),
+        _ => blank_lines,
+    }
+}
+
+/// Caps the runs of blank lines in `gap`, the text between two statements,
+/// at `max`, and with `separate` adds one if there is none. A `gap` that
+/// starts a line (at the start of the file) can start with a blank line;
+/// otherwise its first line is the end of the line of a statement.
+fn space_gap(gap: &str, at_line_start: bool, max: usize, separate: bool) -> String {
+    let pieces: Vec<&str> = gap.split('\n').collect();
+    let last = pieces.len() - 1;
+    let mut kept = Vec::with_capacity(pieces.len() + 1);
+    let mut run = 0;
+    let mut blank_lines = 0;
+    for (i, piece) in pieces.iter().enumerate() {
+        let is_line = i < last && (i > 0 || at_line_start);
+        if is_line && piece.trim().is_empty() {
+            run += 1;
+            if run > max {
+                continue;
+            }
+            blank_lines += 1;
+        } else {
+            run = 0;
+        }
+        kept.push(*piece);
+    }
+    if separate && blank_lines == 0 && last > 0 {
+        kept.insert(if at_line_start { 0 } else { 1 }, "");
+    }
+    kept.join("\n")
+}
+
+/// `sql` with `indent` after each newline between its tokens, so the lines of
+/// a statement that starts indented line up under its first. Newlines in
+/// strings, block comments and COPY data belong to them and get nothing.
+fn indent_lines(sql: &str, indent: &str) -> String {
+    if indent.is_empty() {
+        return sql.to_string();
+    }
+    let mut indented = String::with_capacity(sql.len());
+    for piece in scan(sql) {
+        match piece {
+            Scanned::Token(_, token) if token.kind == TokenKind::Whitespace => {
+                indented.push_str(&token.text.replace('\n', &format!("\n{}", indent)));
+            },
+            Scanned::Token(_, token) => indented.push_str(token.text),
+            Scanned::Data(range) => indented.push_str(&sql[range]),
+        }
+    }
+    indented
+}
+
+/// Removes the spaces and tabs at the end of each line of `sql`, except in
+/// string literals, block comments and `COPY` data, where they may mean
+/// something, and on the lines of protected statements, which stay as they
+/// are.
+fn trim_trailing_whitespace(sql: &str) -> String {
+    let kept = protected_lines(sql);
+    let mut trimmed = String::with_capacity(sql.len());
+    for piece in scan(sql) {
+        match piece {
+            Scanned::Token(offset, token) if token.kind == TokenKind::Whitespace => {
+                let mut start = offset;
+                let mut lines = token.text.split('\n').peekable();
+                while let Some(line) = lines.next() {
+                    let end = start + line.len();
+                    let at_end = lines.peek().is_none();
+                    let keep = kept.iter().any(|range| range.contains(&end));
+                    if !at_end {
+                        trimmed.push_str(if keep { line } else { line.trim_end_matches([' ', '\t']) });
+                        trimmed.push('\n');
+                    } else if end == sql.len() && !keep {
+                        trimmed.push_str(line.trim_end_matches([' ', '\t']));
+                    } else {
+                        trimmed.push_str(line);
+                    }
+                    start = end + 1;
+                }
+            },
+            Scanned::Token(offset, token) if token.kind == TokenKind::Comment && token.text.starts_with("--") && !kept.iter().any(|range| range.contains(&offset)) => {
+                trimmed.push_str(token.text.trim_end_matches([' ', '\t']));
+            },
+            Scanned::Token(_, token) => trimmed.push_str(token.text),
+            Scanned::Data(range) => trimmed.push_str(&sql[range]),
+        }
+    }
+    trimmed
+}
+
+/// The stretches of `sql` that directive comments protect: each run of
+/// protected statements, from the start of the first to the end of the line
+/// the last one ends on, newline included.
+fn protected_lines(sql: &str) -> Vec<Range<usize>> {
+    let statements = split_statements(sql);
+    let protected = protected_statements(sql, &statements);
+    let mut lines: Vec<Range<usize>> = Vec::new();
+    let mut in_run = false;
+    for (range, protected) in statements.iter().zip(protected) {
+        if !protected {
+            in_run = false;
+            continue;
+        }
+        let end = sql[range.end..].find('\n').map_or(sql.len(), |newline| range.end + newline + 1);
+        match lines.last_mut() {
+            Some(last) if in_run => last.end = end.max(last.end),
+            _ => lines.push(range.start..end),
+        }
+        in_run = true;
+    }
+    lines
+}
+
+/// Makes `sql` end in exactly one newline, or in none, as `policy` asks,
+/// dropping any blank lines and whitespace before it.
+/// An empty file stays empty, and so does the end of `COPY` data that runs
+/// to the end of the file.
+fn end_file(sql: &mut String, policy: FinalNewline) {
+    let ends_in_data = opaque_blocks(sql).last().is_some_and(|block| block.kind == OpaqueKind::CopyData && block.range.end == sql.len());
+    if policy == FinalNewline::Preserve || ends_in_data {
+        return;
+    }
+    let content = sql.trim_end().len();
+    sql.truncate(content);
+    if policy == FinalNewline::True && content > 0 {
+        sql.push('\n');
+    }
+}
+
+/// Whether `--only` and `--skip` leave the formatter for `text`'s kind of
+/// statement switched on.
+fn is_enabled(text: &str, options: &FormatOptions) -> bool {
+    if options.only_kinds.is_empty() && options.skip_kinds.is_empty() {
+        return true;
+    }
+    statement_kind(text).is_none_or(|kind| {
+        (options.only_kinds.is_empty() || options.only_kinds.contains(&kind)) && !options.skip_kinds.contains(&kind)
+    })
+}
+
+/// Whether the statement at `range` is over `options.max_statement_size`.
+fn is_oversized(range: &Range<usize>, options: &FormatOptions) -> bool {
+    options.max_statement_size.is_some_and(|limit| range.len() > limit)
+}
+
+/// Formats the `statements` of `sql`, as split by [`split_statements`].
+/// Statements over `options.max_statement_size` are copied as they are,
+/// with a warning, and so are those whose formatter fails, with an incident.
+fn format_split(sql: &str, statements: Vec<Range<usize>>, options: &FormatOptions, counts: &mut StatementCounts, warnings: &mut Vec<String>, incidents: &mut Vec<Incident>) -> Result<String, FormatError> {
+    let mut result = String::with_capacity(sql.len());
+    let mut copied = 0;
+
+    let mut neighbours: Vec<Neighbours> = statements.iter().map(|_| Neighbours::default()).collect();
+    if options.align_grants {
+        for (slot, widths) in neighbours.iter_mut().zip(grant::aligned_widths(sql, &statements, options)) {
+            slot.grant = widths;
+        }
+    }
+    if options.align_across_statements {
+        for (slot, layout) in neighbours.iter_mut().zip(dml::aligned_layouts(sql, &statements, options)) {
+            slot.grid = layout;
+        }
+        for (slot, widths) in neighbours.iter_mut().zip(ddl::aligned_column_widths(sql, &statements, options)) {
+            slot.columns = widths;
+        }
+    }
+    let mut previous_end = None;
+    let lines = LineIndex::new(sql);
+    let protected = protected_statements(sql, &statements);
+
+    for ((range, neighbours), protected) in statements.into_iter().zip(neighbours).zip(protected) {
+        let text = &sql[range.clone()];
+        let previous = previous_end.replace(range.end);
+        if protected || !is_selected(&lines, &range, options) || !is_enabled(text, options) {
+            continue;
+        }
+        if is_oversized(&range, options) {
+            let line = lines.line(range.start);
+            warnings.push(format!("line {}: statement of {} bytes is over --max-statement-size; leaving it as it is", line, range.len()));
+            counts.oversized += 1;
+            continue;
+        }
+        let (kind, formatted) = match format_checked(sql, &range, &lines, options, &neighbours) {
+            Ok(Some(formatted)) => formatted,
+            Ok(None) => continue,
+            Err(FormatError::Statement(error)) => {
+                incidents.push(Incident { span: range.clone(), text: text.to_string(), error });
+                continue;
+            },
+            Err(e) => return Err(e),
+        };
+
+        // DROP and TRUNCATE statements sharing a line with the statement
+        // before them move to a line of their own
+        let break_line = matches!(kind, StatementKind::Drop | StatementKind::Truncate)
+            && previous.is_some_and(|end| sql[end..range.start].chars().all(|c| c == ' ' || c == '\t'));
+
+        // Continuation lines line up with an indented first line
+        let indent = if break_line { line_indentation(sql, range.start) } else { indentation(sql, range.start) };
+        let formatted = trim_trailing_whitespace(&indent_lines(&formatted, indent));
+        if formatted == text && !break_line {
+            continue;
+        }
+
+        match previous {
+            Some(end) if break_line => {
+                result.push_str(&sql[copied..end]);
+                result.push('\n');
+                result.push_str(indent);
+            },
+            _ => result.push_str(&sql[copied..range.start]),
+        }
+        result.push_str(&formatted);
+        copied = range.end;
+        counts.record(kind);
+    }
+    result.push_str(&sql[copied..]);
+
+    Ok(result)
+}
+
+/// Finds the first thing that leaves `sql` unbalanced, as
+/// [`lexer::imbalance`] does, skipping the data of `COPY ... FROM stdin`,
+/// psql meta-commands and MySQL `DELIMITER` lines and delimiters, which
+/// aren't SQL, and masking template syntax.
+/// Returns its byte offset and what it is.
+pub fn imbalance(sql: &str) -> Option<(usize, &'static str)> {
+    if let Some(masked) = Masked::new(sql) {
+        return imbalance(&masked.sql).map(|(offset, problem)| (masked.source_offset(offset), problem));
+    }
+    let mut start = 0;
+    for block in opaque_blocks(sql) {
+        if let Some((offset, problem)) = lexer::imbalance(&sql[start..block.range.start]) {
+            return Some((start + offset, problem));
+        }
+        start = block.range.end;
+    }
+    lexer::imbalance(&sql[start..]).map(|(offset, problem)| (start + offset, problem))
+}
+
+/// A piece of SQL text, as [`scan`] finds it.
+enum Scanned<'a> {
+    /// A token, at this offset
+    Token(usize, Token<'a>),
+    /// The data of a `COPY ... FROM stdin`, a psql meta-command or a MySQL
+    /// `DELIMITER` line or delimiter, which isn't SQL
+    Data(Range<usize>),
+}
+
+/// Where the strings, quoted identifiers and dollar-quoted bodies of `sql`
+/// are, whose bytes formatting never changes.
+pub fn quoted_ranges(sql: &str) -> Vec<Range<usize>> {
+    scan(sql).into_iter()
+        .filter_map(|piece| match piece {
+            Scanned::Token(offset, token) if token.kind == TokenKind::Quoted => Some(offset..offset + token.text.len()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Tokenizes `sql` like [`tokenize`], with offsets, but keeps each opaque
+/// block, such as the data of a `COPY ... FROM stdin`, in one piece, so a
+/// stray quote in it can't swallow the SQL after it.
+fn scan(sql: &str) -> Vec<Scanned<'_>> {
+    let mut scanned = Vec::new();
+    let mut start = 0;
+    let data = opaque_blocks(sql).into_iter().map(|block| block.range);
+    for range in data.chain(std::iter::once(sql.len()..sql.len())) {
+        let mut offset = start;
+        for token in tokenize(&sql[start..range.start]) {
+            let length = token.text.len();
+            scanned.push(Scanned::Token(offset, token));
+            offset += length;
+        }
+        if !range.is_empty() {
+            scanned.push(Scanned::Data(range.clone()));
+        }
+        start = range.end;
+    }
+    scanned
+}
+
+/// A `-- sql-fmt: off`, `-- sql-fmt: on` or `-- sql-fmt: skip` comment.
+#[derive(Clone, Copy, PartialEq)]
+enum Directive {
+    Off,
+    On,
+    Skip,
+}
+
+impl Directive {
+    /// Reads the directive in a line comment, if there is one, in any case
+    /// and with or without spaces.
+    fn parse(comment: &str) -> Option<Self> {
+        let body = comment.strip_prefix("--")?.trim().to_ascii_lowercase();
+        match body.strip_prefix("sql-fmt")?.trim_start().strip_prefix(':')?.trim() {
+            "off" => Some(Directive::Off),
+            "on" => Some(Directive::On),
+            "skip" => Some(Directive::Skip),
+            _ => None,
+        }
+    }
+}
+
+/// Which of the `statements` of `sql` directive comments protect: those
+/// after a `-- sql-fmt: off` until the next `-- sql-fmt: on`, and the first
+/// one after a `-- sql-fmt: skip`. A directive inside a statement applies
+/// from the statement after it.
+fn protected_statements(sql: &str, statements: &[Range<usize>]) -> Vec<bool> {
+    let mut directives = scan(sql)
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Scanned::Token(offset, token) if token.kind == TokenKind::Comment => Directive::parse(token.text).map(|directive| (offset, directive)),
+            _ => None,
+        })
+        .peekable();
+
+    let mut off = false;
+    let mut skip = false;
+    statements.iter()
+        .map(|range| {
+            while let Some((_, directive)) = directives.next_if(|(offset, _)| *offset < range.start) {
+                match directive {
+                    Directive::Off => off = true,
+                    Directive::On => off = false,
+                    Directive::Skip => skip = true,
+                }
+            }
+            off || std::mem::take(&mut skip)
+        })
+        .collect()
+}
+
+/// The statements of `sql`, as split by [`split_statements`], that no
+/// directive comment protects.
+fn unprotected_statements(sql: &str) -> Vec<Range<usize>> {
+    let statements = split_statements(sql);
+    let protected = protected_statements(sql, &statements);
+    statements.into_iter().zip(protected).filter(|&(_, protected)| !protected).map(|(range, _)| range).collect()
+}
+
+/// What ends each of the `statements` of `sql`: the custom delimiter after
+/// it, where a MySQL `DELIMITER` line set one, or else `;`.
+fn delimiters<'a>(sql: &'a str, statements: &[Range<usize>]) -> Vec<&'a str> {
+    let custom: Vec<Range<usize>> = opaque_blocks(sql).into_iter().filter(|block| block.kind == OpaqueKind::Delimiter).map(|block| block.range).collect();
+    statements.iter()
+        .map(|range| {
+            let next = custom.partition_point(|delimiter| delimiter.start < range.end);
+            custom.get(next)
+                .filter(|delimiter| tokenize(&sql[range.end..delimiter.start]).iter().all(Token::is_trivia))
+                .map_or(";", |delimiter| &sql[delimiter.clone()])
+        })
+        .collect()
+}
+
+/// Words that start a routine definition after `CREATE`.
+const ROUTINES: [&str; 4] = ["FUNCTION", "PROCEDURE", "TRIGGER", "EVENT"];
+
+/// Words that can come between `CREATE` and the kind of routine.
+const ROUTINE_MODIFIERS: [&str; 6] = ["OR", "REPLACE", "DEFINER", "CURRENT_USER", "CONSTRAINT", "AGGREGATE"];
+
+/// Finds the statements in `sql`. Each range runs from the statement's first
+/// token to its terminating semicolon, or to the last token of the file for
+/// a final statement without one. Comments and whitespace before a statement
+/// are not part of it.
+///
+/// Semicolons inside the `BEGIN ... END` body of a routine don't end it.
+/// After a MySQL `DELIMITER //` line, statements end at `//` instead, until
+/// the `DELIMITER ;` line; the delimiter is left out of the statement, so
+/// formatters only ever see one that ends in a semicolon or in nothing. The
+/// `DELIMITER` lines, the data rows of `COPY ... FROM stdin`, psql
+/// meta-command lines such as `\set` and T-SQL `GO` lines are not part of
+/// any statement.
+pub fn split_statements(sql: &str) -> Vec<Range<usize>> {
+    let mut statements = Vec::new();
+    let mut segment_start = 0;
+    let mut delimited = false;
+
+    // Opaque blocks are cut out before tokenizing, so a stray quote in them
+    // can't swallow the statements that follow
+    for block in opaque_blocks(sql) {
+        split_segment(sql, segment_start..block.range.start, delimited, &mut statements);
+        match block.kind {
+            OpaqueKind::DelimiterCommand => delimited = delimiter_of(&sql[block.range.clone()]) != ";",
+            OpaqueKind::Delimiter => delimited = true,
+            OpaqueKind::CopyData | OpaqueKind::MetaCommand => {},
+        }
+        segment_start = block.range.end;
+    }
+    split_segment(sql, segment_start..sql.len(), delimited, &mut statements);
+
+    statements
+}
+
+/// Adds the statements in the `segment` of `sql` to `statements`. A
+/// `delimited` segment, which a custom delimiter ends, is one statement
+/// whatever semicolons it has.
+fn split_segment(sql: &str, segment: Range<usize>, delimited: bool, statements: &mut Vec<Range<usize>>) {
+    let tokens = tokenize(&sql[segment.clone()]);
+    let mut start = None;
+    let mut first_token = 0;
+    let mut depth = 0usize;
+    let mut end = 0;
+    let mut offset = segment.start;
+
+    for (i, token) in tokens.iter().enumerate() {
+        let token_start = offset;
+        offset += token.text.len();
+        if token.is_trivia() {
+            continue;
+        }
+
+        if start.is_none() {
+            first_token = i;
+            depth = 0;
+        }
+        let statement_start = *start.get_or_insert(token_start);
+        end = offset;
+
+        // Blocks only nest inside routines; elsewhere BEGIN starts a transaction
+        if token.is_keyword("BEGIN") || token.is_keyword("CASE") || token.is_keyword("END") {
+            if is_routine(&tokens[first_token..i]) {
+                depth = block_depth(depth, &tokens[first_token..i], token, tokens[i + 1..].iter().find(|token| !token.is_trivia()));
+            }
+        } else if token.is_symbol(";") && depth == 0 && !delimited {
+            statements.push(statement_start..end);
+            start = None;
+        }
+    }
+    if let Some(statement_start) = start {
+        statements.push(statement_start..end);
+    }
+}
+
+/// Whether the statement starting with `tokens` defines a routine or runs an
+/// anonymous block.
+fn is_routine(tokens: &[Token]) -> bool {
+    let mut words = tokens.iter().filter(|token| token.kind == TokenKind::Word);
+    match words.next() {
+        Some(first) if first.is_keyword("DO") => true,
+        Some(first) if first.is_keyword("CREATE") => words
+            .find(|word| !ROUTINE_MODIFIERS.iter().any(|modifier| word.is_keyword(modifier)))
+            .is_some_and(|word| ROUTINES.iter().any(|routine| word.is_keyword(routine))),
+        _ => false,
+    }
+}
+
+/// Nesting of `BEGIN`/`CASE ... END` in a routine body after `token`, which
+/// follows the `before` tokens of its statement. `END IF`, `END LOOP` and the
+/// like close blocks that were never counted.
+fn block_depth(depth: usize, before: &[Token], token: &Token, next: Option<&Token>) -> usize {
+    if !token.is_keyword("END") {
+        // The CASE of `END CASE` closes a block rather than opening one
+        let after_end = before.iter().rev().find(|token| !token.is_trivia()).is_some_and(|previous| previous.is_keyword("END"));
+        return if after_end { depth } else { depth + 1 };
+    }
+    let closes_uncounted = next.is_some_and(|next| ["IF", "LOOP", "WHILE", "REPEAT"].iter().any(|keyword| next.is_keyword(keyword)));
+    if closes_uncounted {
+        depth
+    } else {
+        depth.saturating_sub(1)
+    }
+}
+
+/// A part of a file that isn't split into tokens and statements.
+struct OpaqueBlock {
+    range: Range<usize>,
+    kind: OpaqueKind,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OpaqueKind {
+    /// A MySQL `DELIMITER` line, which sets what ends the statements after it
+    DelimiterCommand,
+    /// A custom delimiter, such as `//`, ending the statement before it
+    Delimiter,
+    /// The data of `COPY ... FROM stdin`, which goes with the statement
+    /// before it
+    CopyData,
+    /// A line for the client rather than the server: a psql meta-command
+    /// such as `\set` or `\copy`, or a T-SQL `GO`
+    MetaCommand,
+}
+
+/// Finds, in order, the MySQL `DELIMITER` lines in `sql` and the custom
+/// delimiters, outside strings and comments, after a line that sets one; the
+/// data of `COPY ... FROM stdin` statements (from the line after the
+/// statement to the `\.` line that ends it); and psql meta-commands (lines
+/// starting with a backslash) and T-SQL `GO [count]` lines outside strings
+/// and block comments. COPY data that is never closed runs to the end of
+/// `sql`.
+fn opaque_blocks(sql: &str) -> Vec<OpaqueBlock> {
+    let mut blocks = Vec::new();
+    // The custom delimiter in force, and where the SQL it ends starts
+    let mut delimiter_start: Option<(&str, usize)> = None;
+    let mut copy_start = None;
+    let mut code_end = 0;
+    let mut offset = 0;
+
+    for line in sql.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let line_end = line_start + line.trim_end().len();
+        let trimmed = line.trim();
+
+        if let Some(block_start) = copy_start {
+            if trimmed == "\\." {
+                blocks.push(OpaqueBlock { range: block_start..line_end, kind: OpaqueKind::CopyData });
+                copy_start = None;
+            }
+            continue;
+        }
+
+        // The mysql client takes any whitespace after the keyword
+        let is_delimiter_command = trimmed.split_once(char::is_whitespace).is_some_and(|(keyword, _)| keyword.eq_ignore_ascii_case("DELIMITER"));
+        if is_delimiter_command {
+            let command_start = line_start + line.len() - line.trim_start().len();
+            if let Some((active, region_start)) = delimiter_start.take() {
+                push_delimiters(sql, region_start..command_start, active, &mut blocks);
+            }
+            blocks.push(OpaqueBlock { range: command_start..line_end, kind: OpaqueKind::DelimiterCommand });
+            let delimiter = delimiter_of(trimmed);
+            if delimiter != ";" {
+                delimiter_start = Some((delimiter, offset));
+            }
+            continue;
+        } else if delimiter_start.is_none() && line_start >= code_end && (trimmed.starts_with('\\') || is_batch_separator(trimmed)) {
+            blocks.push(OpaqueBlock { range: line_start + line.len() - line.trim_start().len()..line_end, kind: OpaqueKind::MetaCommand });
+            // `\copy ... from stdin` reads its data from the lines after it, like COPY
+            let command = trimmed.to_ascii_lowercase();
+            if command.starts_with("\\copy ") && command.contains(" from stdin") {
+                copy_start = Some(offset);
+            }
+            continue;
+        } else if delimiter_start.is_none() && is_copy_from_stdin(trimmed) {
+            copy_start = Some(offset);
+        }
+        code_end = skip_code(sql, code_end.max(line_start), line_end);
+    }
+    if let Some((active, region_start)) = delimiter_start {
+        push_delimiters(sql, region_start..sql.len(), active, &mut blocks);
+    }
+    if let Some(block_start) = copy_start {
+        blocks.push(OpaqueBlock { range: block_start..sql.len(), kind: OpaqueKind::CopyData });
+    }
+
+    blocks
+}
+
+/// The delimiter a MySQL `DELIMITER` line sets: its first word after the
+/// keyword, as the mysql client reads it.
+fn delimiter_of(command: &str) -> &str {
+    command.split_whitespace().nth(1).unwrap_or(";")
+}
+
+/// Adds a block for each `delimiter` in the `region` of `sql` that is
+/// outside strings and comments.
+fn push_delimiters(sql: &str, region: Range<usize>, delimiter: &str, blocks: &mut Vec<OpaqueBlock>) {
+    let mut i = region.start;
+    while i < region.end {
+        let rest = &sql[i..region.end];
+        if rest.starts_with(delimiter) {
+            blocks.push(OpaqueBlock { range: i..i + delimiter.len(), kind: OpaqueKind::Delimiter });
+            i += delimiter.len();
+        } else if rest.starts_with("--") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            i += body.find("*/").map_or(rest.len(), |close| close + 4);
+        } else if let Some(end) = lexer::quoted_region_end(sql, i).filter(|_| !rest.starts_with('$')) {
+            // MySQL has no dollar quotes, and `$$` is a common delimiter
+            i = end;
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+}
+
+/// Where the SQL from `start` to the end of its line at `line_end` leaves
+/// off: at `line_end`, or past it if a string or block comment opened on the
+/// line runs on.
+fn skip_code(sql: &str, start: usize, line_end: usize) -> usize {
+    let mut i = start;
+    while i < line_end {
+        let rest = &sql[i..];
+        if let Some(end) = lexer::quoted_region_end(sql, i) {
+            i = end;
+        } else if rest.starts_with("--") {
+            return line_end;
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            i += body.find("*/").map_or(rest.len(), |close| close + 4);
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    i
+}
+
+/// Whether `line` is a T-SQL `GO`, which ends a batch, with or without a
+/// count.
+fn is_batch_separator(line: &str) -> bool {
+    let (keyword, count) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    keyword.eq_ignore_ascii_case("GO") && count.trim().chars().all(|c| c.is_ascii_digit())
+}
+
+/// Whether `line` is a whole `COPY ... FROM stdin` statement, as `pg_dump`
+/// writes them, so that its data follows on the next line.
+fn is_copy_from_stdin(line: &str) -> bool {
+    let line = line.to_ascii_uppercase();
+    line.starts_with("COPY ") && line.contains(" FROM STDIN") && line.ends_with(';')
+}
+
+/// What a statement lines up on with the statements around it, worked out
+/// before any of them is formatted.
+#[derive(Default)]
+struct Neighbours {
+    /// The privileges and object of a GRANT or REVOKE
+    grant: Option<(usize, usize)>,
+    /// The VALUES grid of an INSERT
+    grid: Option<dml::GridLayout>,
+    /// The columns added by an ALTER TABLE
+    columns: Option<ddl::ColumnWidths>,
+}
+
+/// A statement whose formatting went wrong, and which was left as written.
+pub struct Incident {
+    /// Where the statement is in the text that was formatted
+    pub span: Range<usize>,
+    pub text: String,
+    pub error: StatementError,
+}
+
+/// Formats the statement at `range` of `sql` like [`format_statement`]:
+/// `Ok(None)` if no formatter takes it, which leaves it as written. With
+/// `options.verify_tokens`, a formatter whose output has other tokens than
+/// the statement (whitespace aside) is an error, naming the kind of
+/// statement and where it is; the statement is left as written then too.
+fn format_checked(sql: &str, range: &Range<usize>, lines: &LineIndex, options: &FormatOptions, neighbours: &Neighbours) -> Result<Option<(StatementKind, String)>, FormatError> {
+    let Some((kind, formatted)) = format_statement(&sql[range.clone()], options, neighbours) else { return Ok(None) };
+    if !options.verify_tokens {
+        return Ok(Some((kind, formatted)));
+    }
+    verified(sql, range, lines, kind, formatted, options).map(|formatted| Some((kind, formatted)))
+}
+
+/// `formatted`, the output of the `kind` formatter for the statement at
+/// `range` of `sql`, if it has the statement's tokens, whitespace aside.
+fn verified(sql: &str, range: &Range<usize>, lines: &LineIndex, kind: StatementKind, formatted: String, options: &FormatOptions) -> Result<String, FormatError> {
+    match changed_token(&sql[range.clone()], &formatted, options) {
+        None => Ok(formatted),
+        Some((_, token)) => Err(FormatError::Statement(StatementError {
+            kind: kind.name(),
+            offset: range.start,
+            line: lines.line(range.start),
+            problem: format!("the output changes its tokens, starting at `{}`", token),
+        })),
+    }
+}
+
+/// The first token of `original` that `formatted` doesn't have in the same
+/// place, ignoring whitespace, if there is one, with its offset in
+/// `original`. Tokens must match exactly, except that comments only need
+/// the same text once trimmed, and the data type words `options.type_case`
+/// recases may come out in that case.
+fn changed_token(original: &str, formatted: &str, options: &FormatOptions) -> Option<(usize, String)> {
+    let recased = match options.type_case {
+        TypeCase::Upper => str::to_ascii_uppercase,
+        TypeCase::Lower => str::to_ascii_lowercase,
+        TypeCase::Preserve => str::to_string,
+    };
+    let type_words = if options.type_case == TypeCase::Preserve { Vec::new() } else { type_word_offsets(original) };
+    let mut before = significant_tokens(original).into_iter();
+    let mut after = significant_tokens(formatted).into_iter();
+    loop {
+        match (before.next(), after.next()) {
+            (None, None) => return None,
+            (Some((_, old)), Some((_, new))) if old == new => continue,
+            (Some((offset, old)), Some((_, new))) if type_words.binary_search(&offset).is_ok() && recased(old) == new => continue,
+            (Some((offset, old)), _) => return Some((offset, excerpt(old))),
+            (None, Some(_)) => return Some((original.len(), "the end".to_string())),
+        }
+    }
+}
+
+/// The tokens of `sql` that [`changed_token`] compares, with their offsets.
+/// The data of `COPY ... FROM stdin` counts as one token.
+fn significant_tokens(sql: &str) -> Vec<(usize, &str)> {
+    scan(sql)
+        .into_iter()
+        .filter_map(|piece| match piece {
+            Scanned::Token(_, token) if token.kind == TokenKind::Whitespace => None,
+            Scanned::Token(offset, token) if token.kind == TokenKind::Comment => Some((offset, token.text.trim())),
+            Scanned::Token(offset, token) => Some((offset, token.text)),
+            Scanned::Data(range) => Some((range.start, &sql[range])),
+        })
+        .collect()
+}
+
+/// The offsets in `sql`, in order, of the data type words `type_case` can
+/// recase, as [`ddl::type_words`] finds them in each statement.
+fn type_word_offsets(sql: &str) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    for range in split_statements(sql) {
+        let tokens = tokenize(&sql[range.clone()]);
+        let starts: Vec<usize> = tokens.iter().scan(range.start, |offset, token| {
+            let start = *offset;
+            *offset += token.text.len();
+            Some(start)
+        }).collect();
+        offsets.extend(ddl::type_words(&tokens).into_iter().map(|i| starts[i]));
+    }
+    offsets
+}
+
+/// The start of `token`, short enough for a message.
+fn excerpt(token: &str) -> String {
+    let first_line = token.lines().next().unwrap_or("");
+    match first_line.char_indices().nth(40) {
+        Some((end, _)) => format!("{}...", &first_line[..end]),
+        None if first_line.len() < token.len() => format!("{}...", first_line),
+        None => token.to_string(),
+    }
+}
+
+/// The kind a statement counts as, from its first keyword, whether or not
+/// there is a formatter for it. `WITH` counts as a SELECT.
+pub fn statement_kind(text: &str) -> Option<StatementKind> {
+    const KINDS: [(&str, StatementKind); 15] = [
+        ("INSERT", StatementKind::Insert),
+        ("REPLACE", StatementKind::Insert),
+        ("CREATE", StatementKind::Create),
+        ("SELECT", StatementKind::Select),
+        ("WITH", StatementKind::Select),
+        ("UPDATE", StatementKind::Update),
+        ("DELETE", StatementKind::Delete),
+        ("ALTER", StatementKind::Alter),
+        ("DROP", StatementKind::Drop),
+        ("TRUNCATE", StatementKind::Truncate),
+        ("GRANT", StatementKind::Grant),
+        ("REVOKE", StatementKind::Revoke),
+        ("MERGE", StatementKind::Merge),
+        ("COPY", StatementKind::Copy),
+        ("VALUES", StatementKind::Select),
+    ];
+    let tokens = tokenize(text);
+    let first = tokens.iter().find(|token| token.kind == TokenKind::Word)?;
+    KINDS.iter().find(|(keyword, _)| first.is_keyword(keyword)).map(|&(_, kind)| kind)
+}
+
+/// Formats one statement, if it is of a kind that has a formatter,
+/// lined up with its `neighbours`.
+fn format_statement(text: &str, options: &FormatOptions, neighbours: &Neighbours) -> Option<(StatementKind, String)> {
+    let tokens = tokenize(text);
+    let mut words = tokens.iter().filter(|token| token.kind == TokenKind::Word);
+    let first = words.next()?;
+    let second = words.next();
+    let second_is = |keyword: &str| second.is_some_and(|token: &Token| token.is_keyword(keyword));
+
+    if first.is_keyword("INSERT") || first.is_keyword("REPLACE") {
+        return dml::format_insert(&tokens, options, neighbours.grid.as_ref()).or_else(|| dml::format_upsert(&tokens)).map(|formatted| (StatementKind::Insert, formatted));
+    }
+    if first.is_keyword("UPDATE") {
+        return dml::format_update(&tokens, options).map(|formatted| (StatementKind::Update, formatted));
+    }
+    if first.is_keyword("DELETE") {
+        return dml::format_delete(&tokens, options).map(|formatted| (StatementKind::Delete, formatted));
+    }
+    if first.is_keyword("COPY") {
+        return dml::format_copy(&tokens).map(|formatted| (StatementKind::Copy, formatted));
+    }
+    if first.is_keyword("MERGE") {
+        return dml::format_merge(&tokens).map(|formatted| (StatementKind::Merge, formatted));
+    }
+    if first.is_keyword("ALTER") && second_is("TABLE") {
+        return ddl::format_alter_table(&tokens, options, neighbours.columns).map(|formatted| (StatementKind::Alter, formatted));
+    }
+    if first.is_keyword("CREATE") && (second_is("INDEX") || second_is("UNIQUE")) {
+        return ddl::format_create_index(&tokens, options.max_width).map(|formatted| (StatementKind::Create, formatted));
+    }
+    if first.is_keyword("CREATE") && second_is("TYPE") {
+        return ddl::format_create_type(&tokens, options.inline_enum_values).map(|formatted| (StatementKind::Create, formatted));
+    }
+    if first.is_keyword("CREATE") && is_view(&tokens) {
+        return ddl::format_create_view(&tokens, options).map(|formatted| (StatementKind::Create, formatted));
+    }
+    if first.is_keyword("CREATE") && is_table(&tokens) {
+        return ddl::format_create_table(&tokens, options).map(|formatted| (StatementKind::Create, formatted));
+    }
+    if first.is_keyword("DROP") {
+        return ddl::format_drop(&tokens).map(|formatted| (StatementKind::Drop, formatted));
+    }
+    if first.is_keyword("TRUNCATE") {
+        return ddl::format_drop(&tokens).map(|formatted| (StatementKind::Truncate, formatted));
+    }
+    if first.is_keyword("GRANT") {
+        return grant::format_grant(&tokens, options.max_width, neighbours.grant).map(|formatted| (StatementKind::Grant, formatted));
+    }
+    if first.is_keyword("REVOKE") {
+        return grant::format_grant(&tokens, options.max_width, neighbours.grant).map(|formatted| (StatementKind::Revoke, formatted));
+    }
+    if first.is_keyword("SELECT") || first.is_keyword("WITH") {
+        return select::format_select_statement(&tokens, options, options.max_width).map(|formatted| (StatementKind::Select, formatted));
+    }
+    if first.is_keyword("EXPLAIN") {
+        return format_explain(text, &tokens, options);
+    }
+
+    None
+}
+
+/// Keywords that start the statement an `EXPLAIN` explains.
+const EXPLAINED: [&str; 7] = ["SELECT", "WITH", "VALUES", "INSERT", "REPLACE", "UPDATE", "DELETE"];
+
+/// Formats `EXPLAIN [ANALYZE] [(options)] statement`: the `EXPLAIN` line
+/// keeps its options, spaced as `(ANALYZE, FORMAT JSON)`, and the statement
+/// is formatted as it would be on its own, indented under it. Counts as the
+/// kind of that statement. Returns `None` if the statement isn't formatted,
+/// or has a string with a newline in it, which indenting would change.
+fn format_explain(text: &str, tokens: &[Token], options: &FormatOptions) -> Option<(StatementKind, String)> {
+    let start = lexer::top_level(tokens, 0).into_iter().find(|&i| EXPLAINED.iter().any(|keyword| tokens[i].is_keyword(keyword)))?;
+    let (prefix, statement) = tokens.split_at(start);
+    if prefix.iter().any(|token| token.kind == TokenKind::Comment) || statement.iter().any(|token| token.kind == TokenKind::Quoted && token.text.contains('\n')) {
+        return None;
+    }
+
+    let mut header = String::new();
+    let mut i = 0;
+    while i < prefix.len() {
+        let token = &prefix[i];
+        if token.kind == TokenKind::Whitespace {
+            if !header.is_empty() {
+                header.push(' ');
+            }
+        } else if token.is_symbol("(") {
+            let close = lexer::matching_paren(prefix, i)?;
+            let items: Vec<String> = lexer::split_top_level(&prefix[i + 1..close], ",").into_iter().map(lexer::render).collect();
+            if !header.ends_with(' ') {
+                header.push(' ');
+            }
+            header.push_str(&format!("({})", items.join(", ")));
+            i = close;
+        } else {
+            header.push_str(token.text);
+        }
+        i += 1;
+    }
+    header.truncate(header.trim_end().len());
+
+    let offset: usize = prefix.iter().map(|token| token.text.len()).sum();
+    let (kind, formatted) = format_statement(&text[offset..], options, &Neighbours::default())?;
+    Some((kind, format!("{}\n{}", header, select::indented(&formatted))))
+}
+
+/// Whether a CREATE statement creates a table: `TABLE` comes after nothing
+/// but the modifiers a table can have.
+fn is_table(tokens: &[Token]) -> bool {
+    tokens.iter()
+        .filter(|token| token.kind == TokenKind::Word)
+        .skip(1)
+        .find(|token| !["OR", "REPLACE", "TEMP", "TEMPORARY", "UNLOGGED", "GLOBAL", "LOCAL"].iter().any(|modifier| token.is_keyword(modifier)))
+        .is_some_and(|token| token.is_keyword("TABLE"))
+}
+
+/// Whether a CREATE statement creates a view: `VIEW` comes before `AS`,
+/// after nothing but the modifiers a view can have.
+fn is_view(tokens: &[Token]) -> bool {
+    tokens.iter()
+        .filter(|token| token.kind == TokenKind::Word)
+        .skip(1)
+        .find(|token| !["OR", "REPLACE", "TEMP", "TEMPORARY", "RECURSIVE", "MATERIALIZED"].iter().any(|modifier| token.is_keyword(modifier)))
+        .is_some_and(|token| token.is_keyword("VIEW"))
+}
+
+/// The whitespace before byte offset `start` on its line, or nothing if
+/// something else precedes it on that line.
+fn indentation(sql: &str, start: usize) -> &str {
+    let line_start = sql[..start].rfind('\n').map_or(0, |newline| newline + 1);
+    let before = &sql[line_start..start];
+    if before.chars().all(char::is_whitespace) {
+        before
+    } else {
+        ""
+    }
+}
+
+/// The whitespace at the start of the line containing byte offset `start`.
+fn line_indentation(sql: &str, start: usize) -> &str {
+    let line_start = sql[..start].rfind('\n').map_or(0, |newline| newline + 1);
+    let line = &sql[line_start..start];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(sql: &str) -> String {
+        format_with(sql, &FormatOptions::default())
+    }
+
+    fn format_with(sql: &str, options: &FormatOptions) -> String {
+        format_statements(sql, options, &mut StatementCounts::default(), &mut Vec::new(), &mut Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn indented_statement_keeps_newlines_in_strings() {
+        let sql = "    INSERT INTO notes (id, body) VALUES (1, 'line one\nline two'), (22, 'x');\n";
+        let expected = "    INSERT INTO notes (id, body)\n    VALUES\n    ( 1 , 'line one\nline two'),\n    (22 , 'x');\n";
+        assert_eq!(format(sql), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn verifier_compares_punctuation() {
+        let options = FormatOptions::default();
+        assert_eq!(changed_token("INSERT INTO t VALUES (1, 2,);", "INSERT INTO t VALUES (1, 2);", &options), Some((26, ",".to_string())));
+    }
+
+    #[test]
+    fn verifier_allows_recasing_data_types_only() {
+        let options = FormatOptions { type_case: TypeCase::Upper, ..FormatOptions::default() };
+        let original = "CREATE TABLE t (id integer, name varchar(20));\nALTER TABLE t ADD COLUMN born timestamp with time zone;";
+        let recased = "CREATE TABLE t (id INTEGER, name VARCHAR(20));\nALTER TABLE t ADD COLUMN born TIMESTAMP WITH TIME ZONE;";
+        assert_eq!(changed_token(original, recased, &options), None);
+        assert_eq!(changed_token(original, &recased.replace("TABLE t (", "TABLE T ("), &options), Some((13, "t".to_string())));
+        assert_eq!(changed_token(original, &recased.replace("INTEGER", "Integer"), &options), Some((19, "integer".to_string())));
+        let options = FormatOptions::default();
+        assert_eq!(changed_token(original, recased, &options), Some((19, "integer".to_string())));
+    }
+
+    #[test]
+    fn type_case_passes_verification() {
+        let options = FormatOptions { type_case: TypeCase::Upper, ..FormatOptions::default() };
+        let sql = "CREATE TABLE t (id integer, name varchar(20) NOT NULL);\n";
+        assert_eq!(format_with(sql, &options), "CREATE TABLE t (\n    id   INTEGER,\n    name VARCHAR(20) NOT NULL\n);\n");
+    }
+
+    #[test]
+    fn values_row_with_trailing_comma_is_left_as_written() {
+        let sql = "INSERT INTO t (a, b) VALUES (1, 2,), (33, 4);\n";
+        assert_eq!(format(sql), sql);
+    }
+
+    #[test]
+    fn indented_copy_keeps_its_data() {
+        let sql = "    COPY notes (id,   body) FROM stdin;\n1\tx\n\\.\n";
+        assert_eq!(format(sql), "    COPY notes (id, body) FROM stdin;\n1\tx\n\\.\n");
+    }
+
+    #[test]
+    fn formatter_output_with_other_tokens_is_a_statement_error() {
+        let sql = "SELECT 1;\n\nINSERT INTO t (a) VALUES (1), (2);\n";
+        let range = 11..sql.len() - 1;
+        let options = FormatOptions::default();
+        let error = verified(sql, &range, &LineIndex::new(sql), StatementKind::Insert, "INSERT INTO t (a) VALUES (1) (2);".to_string(), &options).unwrap_err();
+        let FormatError::Statement(error) = error else { panic!("not a statement error: {}", error) };
+        assert_eq!((error.kind, error.offset, error.line), ("INSERT", 11, 3));
+        assert_eq!(error.problem, "the output changes its tokens, starting at `,`");
+        assert!(verified(sql, &range, &LineIndex::new(sql), StatementKind::Insert, "INSERT INTO t (a)\nVALUES (1), (2);".to_string(), &options).is_ok());
+    }
+
+    #[test]
+    fn transactions_are_indented_only_with_the_option() {
+        let sql = "BEGIN;\nSELECT 1;\nBEGIN;\n  SELECT 2;\nROLLBACK;\nSELECT 3;\n";
+        let indented = "BEGIN;\n    SELECT 1;\n    BEGIN;\n    SELECT 2;\nROLLBACK;\nSELECT 3;\n";
+        let options = FormatOptions { indent_transactions: true, ..FormatOptions::default() };
+        assert_eq!(format_with(sql, &options), indented);
+        assert_eq!(format_with(indented, &options), indented);
+        assert_eq!(format(sql), sql);
+        assert_eq!(format(indented), indented);
+    }
+
+    #[test]
+    fn explained_statement_is_formatted_under_its_prefix() {
+        let sql = "EXPLAIN ANALYZE select a,b from t where a > 1;\n";
+        let expected = "EXPLAIN ANALYZE\n    select a, b\n    from t\n    where a > 1;\n";
+        assert_eq!(format(sql), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn explain_options_are_spaced_like_a_list() {
+        let sql = "EXPLAIN (FORMAT JSON,ANALYZE)   SELECT a FROM t;\n";
+        let expected = "EXPLAIN (FORMAT JSON, ANALYZE)\n    SELECT a\n    FROM t;\n";
+        assert_eq!(format(sql), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn blank_lines_collapse_between_groups() {
+        let sql = "\n\n\n-- users\nINSERT INTO users (id) VALUES (1);\nINSERT INTO users (id) VALUES (2);\n\n\n\n\n-- roles\nINSERT INTO roles (id) VALUES (1);\nGRANT SELECT ON users TO viewer;\n\n\n\n";
+        let collapse = FormatOptions { blank_lines: BlankLines::Max(2), ..FormatOptions::default() };
+        let expected = "\n\n-- users\nINSERT INTO users (id) VALUES (1);\nINSERT INTO users (id) VALUES (2);\n\n\n-- roles\nINSERT INTO roles (id) VALUES (1);\n\nGRANT SELECT ON users TO viewer;\n\n\n";
+        assert_eq!(format_with(sql, &collapse), expected);
+        assert_eq!(format_with(expected, &collapse), expected);
+        assert_eq!(format(sql), sql);
+    }
+
+    #[test]
+    fn apostrophes_in_comments_are_balanced() {
+        assert_eq!(imbalance("-- don't split this\nSELECT 1;\n"), None);
+        assert_eq!(imbalance("SELECT 1; /* it's (fine */\nSELECT 2;\n"), None);
+        assert_eq!(imbalance("/* a\n * multi-line comment that isn't closed until here */ SELECT 1;"), None);
+    }
+
+    #[test]
+    fn parentheses_in_strings_are_balanced() {
+        assert_eq!(imbalance("SELECT ')' AS a, '((' AS b;\n"), None);
+        assert_eq!(imbalance("INSERT INTO t (a) VALUES ('it''s (open');\n"), None);
+        assert_eq!(imbalance("SELECT \"odd)name\" FROM t;\n"), None);
+    }
+
+    #[test]
+    fn dollar_quoted_bodies_are_balanced() {
+        let sql = "CREATE FUNCTION f() RETURNS void AS $$\nBEGIN\n  RAISE NOTICE 'it''s (';  -- don't\nEND;\n$$ LANGUAGE plpgsql;\n";
+        assert_eq!(imbalance(sql), None);
+        assert_eq!(imbalance("SELECT $tag$ ') $$ ( $tag$;\n"), None);
+    }
+
+    #[test]
+    fn unbalanced_sql_is_found() {
+        assert_eq!(imbalance("SELECT 1;\nSELECT 'oops;\nSELECT 2;\n"), Some((17, "unclosed quote")));
+        assert_eq!(imbalance("INSERT INTO t (a) VALUES (1;\n"), Some((25, "unclosed parenthesis")));
+        assert_eq!(imbalance("SELECT 1);\n"), Some((8, "unmatched closing parenthesis")));
+        assert_eq!(imbalance("SELECT 1; /* never closed\n"), Some((10, "unclosed comment")));
+        assert_eq!(imbalance("SELECT $$ never closed;\n"), Some((7, "unclosed quote")));
+    }
+
+    #[test]
+    fn statements_end_at_the_custom_delimiter() {
+        let sql = "SELECT 1;\nDELIMITER //\nCREATE PROCEDURE p()\nBEGIN\n  SELECT 2;\nEND //\nSELECT 3//\nDELIMITER ;\nSELECT 4;\n";
+        let texts: Vec<&str> = split_statements(sql).into_iter().map(|range| &sql[range]).collect();
+        assert_eq!(texts, ["SELECT 1;", "CREATE PROCEDURE p()\nBEGIN\n  SELECT 2;\nEND", "SELECT 3", "SELECT 4;"]);
+    }
+
+    #[test]
+    fn delimiter_inside_a_string_or_comment_does_not_end_a_statement() {
+        let sql = "DELIMITER $$\nSELECT '$$' -- $$\n  FROM t $$\nDELIMITER ;\n";
+        let texts: Vec<&str> = split_statements(sql).into_iter().map(|range| &sql[range]).collect();
+        assert_eq!(texts, ["SELECT '$$' -- $$\n  FROM t"]);
+        assert_eq!(imbalance(sql), None);
+    }
+
+    #[test]
+    fn statements_between_delimiter_lines_are_formatted() {
+        let sql = "DELIMITER //\nCREATE TRIGGER tr BEFORE INSERT ON t FOR EACH ROW BEGIN SET NEW.a = 1; END//\nselect a,b from t//\nDELIMITER ;\n";
+        let expected = "DELIMITER //\nCREATE TRIGGER tr BEFORE INSERT ON t FOR EACH ROW BEGIN SET NEW.a = 1; END//\nselect a, b\nfrom t//\nDELIMITER ;\n";
+        assert_eq!(format(sql), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn split_inserts_keep_each_statement_delimiter() {
+        let options = FormatOptions { split_inserts: Some(2), ..FormatOptions::default() };
+        let sql = "DELIMITER //\nINSERT INTO t (a) VALUES (1), (2), (3)//\nDELIMITER ;\nINSERT INTO t (a) VALUES (4), (5), (6)";
+        let expected = "DELIMITER //\nINSERT INTO t (a)\nVALUES\n(1),\n(2)//\nINSERT INTO t (a) VALUES (3)//\nDELIMITER ;\nINSERT INTO t (a)\nVALUES\n(4),\n(5);\nINSERT INTO t (a) VALUES (6)";
+        assert_eq!(format_with(sql, &options), expected);
+        assert_eq!(format_with(expected, &options), expected);
+    }
+
+    #[test]
+    fn rows_of_the_wrong_length_are_warnings() {
+        let sql = "INSERT INTO t (a, b) VALUES (1, 2), (3);
+
+INSERT INTO t (a) VALUES (4), (5, 6);
+";
+        let mut warnings = Vec::new();
+        format_statements(sql, &FormatOptions::default(), &mut StatementCounts::default(), &mut warnings, &mut Vec::new()).unwrap();
+        assert_eq!(warnings, [
+            "line 1: row 2 of the INSERT has 1 values, but its column list has 2",
+            "line 3: row 2 of the INSERT has 2 values, but its column list has 1",
+        ]);
+    }
+
+    #[test]
+    fn rows_of_the_wrong_length_fail_with_strict() {
+        let options = FormatOptions { strict: true, ..FormatOptions::default() };
+        let sql = "SELECT 1;
+INSERT INTO t (a, b)
+VALUES (1, 2),
+(3);
+";
+        let error = format_statements(sql, &options, &mut StatementCounts::default(), &mut Vec::new(), &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, FormatError::ArityMismatch { line: 4, row: 2, expected: 2, actual: 1 }), "{}", error);
+        assert_eq!(error.to_string(), "line 4: row 2 of the INSERT has 1 values, but its column list has 2");
+    }
+
+    #[test]
+    fn consecutive_inserts_align_across_statements() {
+        let options = FormatOptions { align_across_statements: true, ..FormatOptions::default() };
+        let sql = "INSERT INTO users (id, name, active) VALUES (1, 'alice', true);\n\
+                   INSERT INTO users (id, name, active) VALUES (22, 'bob', false);\n\
+                   INSERT INTO users (id, name, active) VALUES (333, 'carol', true);\n\
+                   INSERT INTO users (id, name, active) VALUES (4, 'dave', NULL);\n\
+                   INSERT INTO users (id, name, active) VALUES (55, 'eve', false);\n";
+        let expected = "INSERT INTO users (id, name, active)\nVALUES\n(  1 , 'alice' , true);\n\
+                        INSERT INTO users (id, name, active)\nVALUES\n( 22 , 'bob'   , false);\n\
+                        INSERT INTO users (id, name, active)\nVALUES\n(333 , 'carol' , true);\n\
+                        INSERT INTO users (id, name, active)\nVALUES\n(  4 , 'dave'  , NULL);\n\
+                        INSERT INTO users (id, name, active)\nVALUES\n( 55 , 'eve'   , false);\n";
+        assert_eq!(format_with(sql, &options), expected);
+        assert_eq!(format_with(expected, &options), expected);
+        assert_eq!(format(sql), sql);
+    }
+
+    #[test]
+    fn runs_of_inserts_merge() {
+        let options = FormatOptions { merge_inserts: true, ..FormatOptions::default() };
+        let sql = "INSERT INTO users (id, name) VALUES (1, 'alice');\n\
+                   INSERT INTO users (id,name) VALUES (22, 'bob');\n\
+                   INSERT INTO users (id, name) VALUES (333, 'carol');\n\
+                   \n\
+                   INSERT INTO roles (id) VALUES (1);\n\
+                   INSERT INTO roles (id) VALUES (2);\n";
+        let expected = "INSERT INTO users (id, name)\nVALUES\n(  1 , 'alice'),\n( 22 , 'bob'),\n(333 , 'carol');\n\
+                        \n\
+                        INSERT INTO roles (id)\nVALUES\n(1),\n(2);\n";
+        assert_eq!(format_with(sql, &options), expected);
+        assert_eq!(format_with(expected, &options), expected);
+    }
+
+    #[test]
+    fn different_column_list_breaks_a_merge_run() {
+        let options = FormatOptions { merge_inserts: true, ..FormatOptions::default() };
+        let sql = "INSERT INTO users (id, name) VALUES (1, 'alice');\n\
+                   INSERT INTO users (id, name) VALUES (22, 'bob');\n\
+                   INSERT INTO users (name, id) VALUES ('carol', 333);\n\
+                   INSERT INTO users (id, name) VALUES (4, 'dave');\n";
+        let expected = "INSERT INTO users (id, name)\nVALUES\n( 1 , 'alice'),\n(22 , 'bob');\n\
+                        INSERT INTO users (name, id) VALUES ('carol', 333);\n\
+                        INSERT INTO users (id, name) VALUES (4, 'dave');\n";
+        assert_eq!(format_with(sql, &options), expected);
+    }
+
+    #[test]
+    fn comment_breaks_a_merge_run() {
+        let options = FormatOptions { merge_inserts: true, ..FormatOptions::default() };
+        let sql = "INSERT INTO users (id, name) VALUES (1, 'alice');\n\
+                   -- test accounts\n\
+                   INSERT INTO users (id, name) VALUES (22, 'bob');\n\
+                   INSERT INTO users (id, name) VALUES (333, 'carol');\n";
+        let expected = "INSERT INTO users (id, name) VALUES (1, 'alice');\n\
+                        -- test accounts\n\
+                        INSERT INTO users (id, name)\nVALUES\n( 22 , 'bob'),\n(333 , 'carol');\n";
+        assert_eq!(format_with(sql, &options), expected);
+    }
+}
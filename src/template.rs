@@ -0,0 +1,269 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::lexer;
+
+/// Starts and ends the number of each placeholder. SQL that already has one
+/// isn't masked, so a placeholder can't be mistaken for anything else.
+const MARK: char = 'ǂ';
+
+/// SQL with its Jinja template syntax, as in dbt models and templated seed
+/// files, swapped for placeholders the formatters can work around:
+///
+/// - a `{{ ... }}` expression becomes a word as wide as it, so it's a single
+///   value in a VALUES row and a single name after `FROM`, and columns line
+///   up around it
+/// - a `{% ... %}` tag, a `{# ... #}` comment, a whole `{% raw %} ...
+///   {% endraw %}` block and a `{{ ... }}` on a line of its own between
+///   statements, like dbt's `{{ config(...) }}`, become comments spanning as
+///   many lines
+///
+/// So a statement with a tag inside it, such as an `{% if %}` around one of
+/// its columns, is left as written like any statement with a comment inside.
+pub struct Masked<'a> {
+    pub sql: String,
+    templates: Vec<Template<'a>>,
+}
+
+/// A piece of template syntax and the placeholder standing in for it.
+struct Template<'a> {
+    original: &'a str,
+    /// Where `original` is in the unmasked text
+    source: Range<usize>,
+    placeholder: String,
+    /// Where `placeholder` is in the masked SQL
+    at: usize,
+}
+
+impl<'a> Masked<'a> {
+    /// Masks the template syntax in `source`, or returns `None` if it has
+    /// none. Braces in SQL strings and comments aren't template syntax, and
+    /// scanning stops at a tag or block that is never closed.
+    pub fn new(source: &'a str) -> Option<Self> {
+        if source.contains(MARK) {
+            return None;
+        }
+        let mut masked = Masked { sql: String::with_capacity(source.len()), templates: Vec::new() };
+        let mut copied = 0;
+        let mut from = 0;
+
+        while let Some(start) = next_brace(source, from) {
+            let rest = &source[start..];
+            let end = if rest.starts_with("{#") {
+                rest.find("#}").map(|close| start + close + 2)
+            } else if rest.starts_with("{{") || rest.starts_with("{%") {
+                tag_end(source, start)
+            } else {
+                from = start + 1;
+                continue;
+            };
+            let Some(mut end) = end else { break };
+            if is_tag(&source[start..end], "raw") {
+                let Some(block_end) = raw_block_end(source, end) else { break };
+                end = block_end;
+            }
+
+            masked.sql.push_str(&source[copied..start]);
+            let comment = !rest.starts_with("{{") || masked.between_statements() && stands_alone(source, start..end);
+            masked.push(&source[start..end], start..end, comment);
+            copied = end;
+            from = end;
+        }
+
+        if masked.templates.is_empty() {
+            return None;
+        }
+        masked.sql.push_str(&source[copied..]);
+        Some(masked)
+    }
+
+    /// `text`, taken from the masked SQL, with its placeholders swapped back
+    /// for the templates, or `None` if one of them was changed.
+    pub fn restore(&self, text: &str) -> Option<String> {
+        let mut restored = String::with_capacity(text.len());
+        let mut copied = 0;
+
+        while let Some(mark) = text[copied..].find(MARK).map(|i| copied + i) {
+            let digits = &text[mark + MARK.len_utf8()..];
+            let len = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+            let template = self.templates.get(digits[..len].parse::<usize>().ok()?)?;
+            let start = if template.placeholder.starts_with("/*") { mark.checked_sub(2)? } else { mark };
+            if !text.get(start..)?.starts_with(&template.placeholder) {
+                return None;
+            }
+            restored.push_str(&text[copied..start]);
+            restored.push_str(template.original);
+            copied = start + template.placeholder.len();
+        }
+
+        restored.push_str(&text[copied..]);
+        Some(restored)
+    }
+
+    /// Where `offset` in the masked SQL is in the unmasked text. An offset
+    /// inside a placeholder is taken to the start of its template.
+    pub fn source_offset(&self, offset: usize) -> usize {
+        let mut mapped = offset;
+        for template in &self.templates {
+            let end = template.at + template.placeholder.len();
+            if offset < template.at {
+                break;
+            }
+            if offset < end {
+                return template.source.start;
+            }
+            mapped = template.source.end + (offset - end);
+        }
+        mapped
+    }
+
+    fn push(&mut self, original: &'a str, source: Range<usize>, comment: bool) {
+        let id = format!("{}{}{}", MARK, self.templates.len(), MARK);
+        let lines = original.matches('\n').count();
+        let width = if lines == 0 { original.width() } else { 0 };
+        let placeholder = if comment {
+            format!("/*{}{}{}*/", id, "_".repeat(width.saturating_sub(id.width() + 4)), "\n".repeat(lines))
+        } else {
+            format!("{}{}", id, "_".repeat(width.saturating_sub(id.width())))
+        };
+        let at = self.sql.len();
+        self.sql.push_str(&placeholder);
+        self.templates.push(Template { original, source, placeholder, at });
+    }
+
+    /// Whether the SQL masked so far ends between statements: it's blank, or
+    /// it ends with a semicolon or a comment.
+    fn between_statements(&self) -> bool {
+        let before = self.sql.trim_end();
+        let last_line = before.rsplit('\n').next().unwrap_or("").trim_start();
+        before.is_empty() || before.ends_with(';') || before.ends_with("*/") || last_line.starts_with("--")
+    }
+}
+
+/// The offset of the first `{` in `source` from `from` on that is outside
+/// SQL strings, quoted identifiers and comments.
+fn next_brace(source: &str, from: usize) -> Option<usize> {
+    let mut i = from;
+    while i < source.len() {
+        let rest = &source[i..];
+        if rest.starts_with('{') {
+            return Some(i);
+        } else if rest.starts_with("--") {
+            i += rest.find('\n').unwrap_or(rest.len());
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            i += body.find("*/").map_or(rest.len(), |close| close + 4);
+        } else if let Some(end) = lexer::quoted_region_end(source, i) {
+            i = end;
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    None
+}
+
+/// The end of the `{{ ... }}` or `{% ... %}` at `start` of `source`. Quoted
+/// strings inside it can hold the closing braces.
+fn tag_end(source: &str, start: usize) -> Option<usize> {
+    let close = if source[start..].starts_with("{{") { "}}" } else { "%}" };
+    let body = start + 2;
+    let mut quote = None;
+    let mut chars = source[body..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match quote {
+            Some(_) if c == '\\' => {
+                chars.next();
+            },
+            Some(open) if c == open => quote = None,
+            Some(_) => {},
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if source[body + i..].starts_with(close) => return Some(body + i + close.len()),
+            None => {},
+        }
+    }
+    None
+}
+
+/// Whether `tag` is `{% name %}`, with or without whitespace control.
+fn is_tag(tag: &str, name: &str) -> bool {
+    tag.strip_prefix("{%")
+        .and_then(|tag| tag.strip_suffix("%}"))
+        .is_some_and(|body| body.trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace()) == name)
+}
+
+/// The end of the `{% endraw %}` that closes a raw block whose content
+/// starts at `from`.
+fn raw_block_end(source: &str, from: usize) -> Option<usize> {
+    let mut from = from;
+    while let Some(start) = source[from..].find("{%").map(|i| from + i) {
+        match tag_end(source, start) {
+            Some(end) if is_tag(&source[start..end], "endraw") => return Some(end),
+            _ => from = start + 2,
+        }
+    }
+    None
+}
+
+/// Whether the template at `range` of `source` has its lines to itself.
+fn stands_alone(source: &str, range: Range<usize>) -> bool {
+    let line_start = source[..range.start].rfind('\n').map_or(0, |newline| newline + 1);
+    let line_end = source[range.end..].find('\n').map_or(source.len(), |newline| range.end + newline);
+    source[line_start..range.start].trim().is_empty() && source[range.end..line_end].trim().is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sql_without_template_syntax_is_not_masked() {
+        assert!(Masked::new("SELECT '{{ x }}' FROM t; -- {% if %}\n").is_none());
+    }
+
+    #[test]
+    fn expression_becomes_a_word_as_wide_as_it() {
+        let source = "SELECT a FROM {{ ref('x') }} WHERE b = 1;";
+        let masked = Masked::new(source).unwrap();
+        let placeholder = &masked.sql["SELECT a FROM ".len()..masked.sql.find(" WHERE").unwrap()];
+        assert!(placeholder.starts_with(MARK) && !placeholder.contains(' '), "{}", masked.sql);
+        assert_eq!(placeholder.width(), "{{ ref('x') }}".width());
+        assert_eq!(masked.restore(&masked.sql).as_deref(), Some(source));
+    }
+
+    #[test]
+    fn tags_and_standalone_expressions_become_comments() {
+        let source = "{{ config(materialized='view') }}\n{% raw %}\nSELECT {{ x }};\n{% endraw %}\nSELECT 1;\n";
+        let masked = Masked::new(source).unwrap();
+        let lines: Vec<&str> = masked.sql.lines().collect();
+        assert_eq!(lines.len(), 5, "{}", masked.sql);
+        assert!(lines[0].starts_with("/*") && lines[0].ends_with("*/"), "{}", masked.sql);
+        assert!(lines[1].starts_with(&format!("/*{}1{}", MARK, MARK)) && lines[2].is_empty() && lines[3] == "*/", "{}", masked.sql);
+        assert_eq!(lines[4], "SELECT 1;");
+        assert_eq!(masked.templates.len(), 2);
+        assert_eq!(masked.restore(&masked.sql).as_deref(), Some(source));
+    }
+
+    #[test]
+    fn restore_moves_placeholders_with_the_text_around_them() {
+        let masked = Masked::new("insert into t (a) values ({{ v }});").unwrap();
+        let moved = masked.sql.replace("values (", "values\n(");
+        assert_eq!(masked.restore(&moved).as_deref(), Some("insert into t (a) values\n({{ v }});"));
+    }
+
+    #[test]
+    fn restore_fails_when_a_placeholder_was_changed() {
+        let masked = Masked::new("SELECT {{ column }} FROM t;").unwrap();
+        assert_eq!(masked.restore(&masked.sql.replacen('_', "", 1)), None);
+        assert_eq!(masked.restore(&masked.sql.replace(&format!("{}0{}", MARK, MARK), &format!("{}7{}", MARK, MARK))), None);
+    }
+
+    #[test]
+    fn source_offset_maps_past_and_into_placeholders() {
+        let source = "SELECT {{ long_expression }}, b FROM t;";
+        let masked = Masked::new(source).unwrap();
+        let b = masked.sql.find(", b").unwrap() + 2;
+        assert_eq!(masked.source_offset(b), source.find(", b").unwrap() + 2);
+        assert_eq!(masked.source_offset(8), 7);
+        assert_eq!(masked.source_offset(3), 3);
+    }
+}
@@ -0,0 +1,624 @@
+//! Runs the `cargo-sql-fmt` binary on small projects written to temporary
+//! directories, for what only shows from the outside: which files a run
+//! picks up, what it prints and how it exits.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// An INSERT the formatter always rewrites.
+const UNFORMATTED: &str = "insert into t (a,b) values (1,'x'),(22,'y');\n";
+
+/// A scratch directory, removed again when the test is done.
+struct Project {
+    dir: PathBuf,
+}
+
+impl Project {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("sql-fmt-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Project { dir }
+    }
+
+    fn file(&self, path: &str, content: &str) -> &Self {
+        let path = self.dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        self
+    }
+
+    fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.dir.join(path)).unwrap()
+    }
+
+    /// Runs the formatter with `args` from the project directory.
+    fn run(&self, args: &[&str]) -> Output {
+        self.run_in(".", args)
+    }
+
+    /// Runs the formatter with `args` from `dir` inside the project.
+    fn run_in(&self, dir: &str, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_cargo-sql-fmt"))
+            .args(args)
+            .current_dir(self.dir.join(dir))
+            .output()
+            .expect("the binary runs")
+    }
+
+    /// Runs the formatter with `args` from the project directory, writing
+    /// `input` to its standard input.
+    fn run_with_input(&self, args: &[&str], input: &str) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_cargo-sql-fmt"))
+            .args(args)
+            .current_dir(&self.dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("the binary runs");
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().expect("the binary runs")
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// The paths a `--check --output json` run reports as needing a reformat, in
+/// report order.
+fn would_reformat(output: &Output) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut path = None;
+    for line in stdout(output).lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("\"path\": ") {
+            path = Some(value.trim_end_matches(',').trim_matches('"').to_string());
+        } else if line.starts_with("\"status\": \"would_reformat\"") {
+            paths.extend(path.take());
+        }
+    }
+    paths
+}
+
+#[test]
+fn sqlfmtignore_applies_to_nested_directories() {
+    let project = Project::new("ignore-nested");
+    project
+        .file(".sqlfmtignore", "migrations/archive/**\nschema_dump.sql\n")
+        .file("migrations/001.sql", UNFORMATTED)
+        .file("migrations/archive/2019/old.sql", UNFORMATTED)
+        .file("db/dumps/schema_dump.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "--output", "json", "."]);
+
+    assert_eq!(would_reformat(&output), ["migrations/001.sql"], "{}", stdout(&output));
+}
+
+#[test]
+fn ignored_directories_are_not_searched() {
+    let project = Project::new("ignore-directories");
+    project
+        .file(".gitignore", "node_modules/\n")
+        .file(".sqlfmtignore", "dist/\n")
+        .file("node_modules/pkg/schema.sql", UNFORMATTED)
+        .file("dist/build.sql", UNFORMATTED)
+        .file("query.sql", UNFORMATTED);
+    git(&project, &["init", "--quiet"]);
+
+    let output = project.run(&["--check", "--verbose", "."]);
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Ignoring dist (") && stdout.contains("Ignoring node_modules ("), "{}", stdout);
+    assert!(!stdout.contains("schema.sql") && !stdout.contains("build.sql"), "{}", stdout);
+    assert!(stdout.contains("query.sql"), "{}", stdout);
+}
+
+#[test]
+fn sqlfmtignore_negation_brings_a_file_back() {
+    let project = Project::new("ignore-negation");
+    project
+        .file(".sqlfmtignore", "generated/*.sql\n!generated/seed.sql\n")
+        .file("generated/tables.sql", UNFORMATTED)
+        .file("generated/seed.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "--output", "json", "."]);
+
+    assert_eq!(would_reformat(&output), ["generated/seed.sql"], "{}", stdout(&output));
+}
+
+#[test]
+fn no_ignore_formats_ignored_files() {
+    let project = Project::new("no-ignore");
+    project
+        .file(".sqlfmtignore", "archive/\n")
+        .file("archive/old.sql", UNFORMATTED)
+        .file("new.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "--no-ignore", "--output", "json", "."]);
+
+    assert_eq!(would_reformat(&output), ["archive/old.sql", "new.sql"], "{}", stdout(&output));
+}
+
+#[test]
+fn explicitly_named_ignored_file_is_skipped_with_a_warning() {
+    let project = Project::new("ignore-explicit");
+    project
+        .file(".sqlfmtignore", "archive/\n")
+        .file("archive/old.sql", UNFORMATTED);
+
+    let output = project.run(&["archive/old.sql"]);
+
+    let stderr = stderr(&output);
+    assert!(stderr.contains("skipping archive/old.sql, which is excluded by"), "{}", stderr);
+    assert!(stderr.contains(".sqlfmtignore: archive/"), "{}", stderr);
+    assert_eq!(project.read("archive/old.sql"), UNFORMATTED);
+}
+
+#[test]
+fn exclude_can_be_repeated() {
+    let project = Project::new("exclude-repeated");
+    project
+        .file("seeds/users.sql", UNFORMATTED)
+        .file("legacy/2019/old.sql", UNFORMATTED)
+        .file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "--output", "json", "--exclude", "seeds/*", "--exclude", "legacy/**", "."]);
+
+    assert_eq!(would_reformat(&output), ["schema.sql"], "{}", stdout(&output));
+}
+
+#[test]
+fn exclude_is_relative_to_the_current_directory() {
+    let project = Project::new("exclude-relative");
+    project
+        .file("db/seeds/users.sql", UNFORMATTED)
+        .file("db/schema.sql", UNFORMATTED);
+
+    let output = project.run_in("db", &["--check", "--output", "json", "--exclude", "seeds/*", "."]);
+
+    assert_eq!(would_reformat(&output), ["schema.sql"], "{}", stdout(&output));
+}
+
+#[test]
+fn verbose_run_names_the_exclude_pattern() {
+    let project = Project::new("exclude-verbose");
+    project.file("seeds/users.sql", UNFORMATTED);
+
+    let output = project.run(&["--verbose", "--exclude", "seeds/*", "."]);
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Excluding seeds/users.sql (--exclude seeds/*)"), "{}", stdout);
+    assert_eq!(project.read("seeds/users.sql"), UNFORMATTED);
+}
+
+#[test]
+fn invalid_exclude_glob_is_a_usage_error_naming_it() {
+    let project = Project::new("exclude-invalid");
+    project.file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["--exclude", "seeds/[abc", "."]);
+
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("invalid glob `seeds/[abc`"), "{}", stderr);
+    assert_eq!(project.read("schema.sql"), UNFORMATTED);
+}
+
+#[test]
+fn directories_patterns_and_files_can_be_mixed() {
+    let project = Project::new("mixed-paths");
+    project
+        .file("migrations/001.sql", UNFORMATTED)
+        .file("migrations/nested/002.sql", UNFORMATTED)
+        .file("seeds/users.sql", UNFORMATTED)
+        .file("seeds/notes.txt", UNFORMATTED)
+        .file("schema.sql", UNFORMATTED)
+        .file("other.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "--output", "json", "migrations", "seeds/*", "schema.sql"]);
+
+    assert_eq!(
+        would_reformat(&output),
+        ["migrations/001.sql", "migrations/nested/002.sql", "seeds/users.sql", "schema.sql"],
+        "{}",
+        stdout(&output),
+    );
+}
+
+#[test]
+fn file_found_twice_is_formatted_once() {
+    let project = Project::new("overlapping-paths");
+    project.file("seeds/users.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "--output", "json", "seeds", "seeds/*.sql", "seeds/users.sql"]);
+
+    assert_eq!(would_reformat(&output), ["seeds/users.sql"], "{}", stdout(&output));
+}
+
+#[test]
+fn unmatched_pattern_warns_without_failing_a_normal_run() {
+    let project = Project::new("unmatched-format");
+    project.file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["missing/*.sql", "schema.sql"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("no SQL files found in missing/*.sql"), "{}", stderr);
+    assert_ne!(project.read("schema.sql"), UNFORMATTED);
+}
+
+#[test]
+fn unmatched_pattern_fails_a_check() {
+    let project = Project::new("unmatched-check");
+    project.file("schema.sql", "SELECT 1;\n");
+
+    let output = project.run(&["--check", "missing/*.sql", "schema.sql"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("no SQL files found in missing/*.sql"), "{}", stderr);
+}
+
+#[test]
+fn check_of_formatted_files_exits_0() {
+    let project = Project::new("exit-clean");
+    project.file("schema.sql", "SELECT 1;\n");
+
+    let output = project.run(&["--check", "."]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+}
+
+#[test]
+fn check_with_a_file_to_reformat_exits_1_and_writes_nothing() {
+    let project = Project::new("exit-check");
+    project.file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "."]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert_eq!(project.read("schema.sql"), UNFORMATTED);
+}
+
+#[test]
+fn failed_file_exits_2_even_when_others_would_be_reformatted() {
+    let project = Project::new("exit-error");
+    project.file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "schema.sql", "missing.sql"]);
+
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+}
+
+#[test]
+fn jobs_report_files_in_input_order_and_keep_going_past_a_failure() {
+    let project = Project::new("jobs");
+    project.file("a.sql", UNFORMATTED).file("b.sql", UNFORMATTED).file("c.sql", UNFORMATTED);
+
+    let serial = project.run(&["--check", "--jobs", "1", "a.sql", "missing.sql", "b.sql", "c.sql"]);
+    let parallel = project.run(&["--check", "--jobs", "4", "a.sql", "missing.sql", "b.sql", "c.sql"]);
+
+    assert_eq!(parallel.status.code(), Some(2), "{}", stderr(&parallel));
+    assert_eq!(stdout(&parallel), stdout(&serial));
+    let stdout = stdout(&parallel);
+    let order: Vec<_> = ["a.sql", "missing.sql", "b.sql", "c.sql"]
+        .iter()
+        .map(|path| stdout.find(&format!("Processing file: {}\n", path)).unwrap_or_else(|| panic!("{} not reported:\n{}", path, stdout)))
+        .collect();
+    assert!(order.windows(2).all(|pair| pair[0] < pair[1]), "{}", stdout);
+    assert!(stdout.contains("4 files scanned, 3 would be reformatted, 0 skipped, 1 error\n"), "{}", stdout);
+}
+
+#[test]
+fn summary_counts_the_run_and_repeats_the_errors() {
+    let project = Project::new("summary");
+    project.file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["--verbose", "schema.sql", "missing.sql"]);
+
+    assert_eq!(output.status.code(), Some(2), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("2 files scanned, 1 reformatted, 0 skipped, 1 error\n"), "{}", stdout);
+    assert!(stdout.contains("Statements reformatted: 1 INSERT\n"), "{}", stdout);
+    let stderr = stderr(&output);
+    assert!(stderr.contains("\nErrors:\n  missing.sql: "), "{}", stderr);
+}
+
+#[test]
+fn quiet_prints_nothing_and_leaves_the_result_to_the_exit_status() {
+    let project = Project::new("quiet");
+    project.file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["--check", "--quiet", "."]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "");
+
+    let output = project.run(&["--quiet", "--verbose", "."]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("'--quiet' cannot be used with '--verbose'"), "{}", stderr(&output));
+}
+
+#[test]
+fn usage_error_exits_2() {
+    let project = Project::new("exit-usage");
+
+    let output = project.run(&["--no-such-flag", "."]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn completions_are_printed_for_each_shell() {
+    let project = Project::new("completions");
+
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = project.run(&["completions", shell]);
+
+        assert_eq!(output.status.code(), Some(0), "{}: {}", shell, stderr(&output));
+        let script = stdout(&output);
+        assert!(script.contains("cargo-sql-fmt"), "{} completions don't name the binary:\n{}", shell, script);
+    }
+}
+
+#[test]
+fn version_names_the_binary() {
+    let project = Project::new("version");
+
+    let output = project.run(&["--version"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("cargo-sql-fmt "), "{}", stdout(&output));
+}
+
+#[test]
+fn unbalanced_file_is_skipped_and_left_alone() {
+    let project = Project::new("unbalanced");
+    let unbalanced = "insert into t (a,b) values (1,'x'),(22,'y');\nselect 'oops from t;\n";
+    project
+        .file("broken.sql", unbalanced)
+        .file("comments.sql", "-- don't worry (really\ninsert into t (a,b) values (1,'x'),(22,'y');\n");
+
+    let output = project.run(&["."]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let stderr = stderr(&output);
+    assert!(stderr.contains("skipping broken.sql: unclosed quote on line 2"), "{}", stderr);
+    assert_eq!(project.read("broken.sql"), unbalanced);
+    assert!(!stderr.contains("comments.sql"), "{}", stderr);
+    assert_ne!(project.read("comments.sql"), "-- don't worry (really\ninsert into t (a,b) values (1,'x'),(22,'y');\n");
+}
+
+#[test]
+fn check_reports_a_markdown_file_with_an_unformatted_sql_block() {
+    let project = Project::new("markdown-check");
+    let readme = format!("# Seeds\n\n```sql\n{}```\n", UNFORMATTED);
+    project.file("README.md", &readme);
+
+    let output = project.run(&["--check", "--output", "json", "README.md"]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert_eq!(would_reformat(&output), ["README.md"]);
+    assert_eq!(project.read("README.md"), readme);
+}
+
+#[test]
+fn align_grants_lines_up_consecutive_grants() {
+    let project = Project::new("align-grants");
+    let grants = "grant select on table users to app_ro;\nGRANT SELECT,INSERT ON TABLE orders TO app_rw;\n";
+    project.file("grants.sql", grants).file("plain.sql", grants);
+
+    let aligned = project.run(&["--align-grants", "grants.sql"]);
+    let plain = project.run(&["plain.sql"]);
+
+    assert_eq!(aligned.status.code(), Some(0), "{}", stderr(&aligned));
+    assert_eq!(plain.status.code(), Some(0), "{}", stderr(&plain));
+    assert_eq!(project.read("grants.sql"), "grant select         on table users  to app_ro;\nGRANT SELECT, INSERT ON TABLE orders TO app_rw;\n");
+    assert_eq!(project.read("plain.sql"), "grant select on table users to app_ro;\nGRANT SELECT, INSERT ON TABLE orders TO app_rw;\n");
+}
+
+/// One unformatted statement of each of three kinds, on lines 1, 3 and 5.
+const MIXED: &str = "insert into t (a,b) values (1,'x'),(22,'y');\n\nselect a,b from t where a>1;\n\nupdate t set a=1,b=2;\n";
+
+#[test]
+fn lines_leaves_statements_outside_the_range_byte_identical() {
+    let project = Project::new("lines");
+    project.file("mixed.sql", MIXED);
+
+    let output = project.run(&["--lines", "3:3", "mixed.sql"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        project.read("mixed.sql"),
+        "insert into t (a,b) values (1,'x'),(22,'y');\n\nselect a, b\nfrom t\nwhere a>1;\n\nupdate t set a=1,b=2;\n"
+    );
+}
+
+#[test]
+fn only_and_skip_leave_other_kinds_byte_identical() {
+    let project = Project::new("only-skip");
+    project.file("only.sql", MIXED).file("skip.sql", MIXED);
+
+    let only = project.run(&["--only", "updates", "only.sql"]);
+    let skip = project.run(&["--skip", "inserts,updates", "skip.sql"]);
+
+    assert_eq!(only.status.code(), Some(0), "{}", stderr(&only));
+    assert_eq!(skip.status.code(), Some(0), "{}", stderr(&skip));
+    assert_eq!(
+        project.read("only.sql"),
+        "insert into t (a,b) values (1,'x'),(22,'y');\n\nselect a,b from t where a>1;\n\nupdate t\nset\n    a = 1,\n    b = 2;\n"
+    );
+    assert_eq!(
+        project.read("skip.sql"),
+        "insert into t (a,b) values (1,'x'),(22,'y');\n\nselect a, b\nfrom t\nwhere a>1;\n\nupdate t set a=1,b=2;\n"
+    );
+}
+
+#[test]
+fn rewrite_leaves_no_temporary_file_behind() {
+    let project = Project::new("atomic");
+    project.file("schema.sql", UNFORMATTED);
+
+    let output = project.run(&["schema.sql"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_ne!(project.read("schema.sql"), UNFORMATTED);
+    let entries: Vec<String> = fs::read_dir(&project.dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    assert_eq!(entries, ["schema.sql"]);
+}
+
+#[test]
+fn backup_keeps_the_original_next_to_the_file() {
+    let project = Project::new("backup");
+    project.file("schema.sql", UNFORMATTED).file("other.sql", UNFORMATTED);
+
+    let output = project.run(&["--backup", "schema.sql"]);
+    let suffixed = project.run(&["--backup", "--backup-suffix", ".orig", "other.sql"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(suffixed.status.code(), Some(0), "{}", stderr(&suffixed));
+    assert_eq!(project.read("schema.sql.bak"), UNFORMATTED);
+    assert_eq!(project.read("other.sql.orig"), UNFORMATTED);
+    assert_ne!(project.read("schema.sql"), UNFORMATTED);
+}
+
+#[test]
+fn json_report_describes_each_file() {
+    let project = Project::new("json-report");
+    project.file("schema.sql", UNFORMATTED).file("clean.sql", "SELECT 1;\n");
+
+    let output = project.run(&["--check", "--output", "json", "schema.sql", "clean.sql"]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.starts_with("{\n  \"version\": 1,\n  \"check\": true,\n"), "{}", stdout);
+    assert!(!stdout.contains("Would reformat"), "{}", stdout);
+    assert_eq!(would_reformat(&output), ["schema.sql"]);
+    assert!(stdout.contains("\"path\": \"clean.sql\",\n      \"status\": \"unchanged\","), "{}", stdout);
+    assert!(stdout.contains("\"statements\": { \"insert\": 1, \"create\": 0,"), "{}", stdout);
+}
+
+#[test]
+fn stdin_filepath_applies_the_ignore_rules_for_that_path() {
+    let project = Project::new("stdin-filepath");
+    project.file(".sqlfmtignore", "dist/\n");
+
+    let ignored = project.run_with_input(&["--stdin", "--stdin-filepath", "dist/build.sql"], UNFORMATTED);
+    let formatted = project.run_with_input(&["--stdin", "--stdin-filepath", "src/query.sql"], UNFORMATTED);
+
+    assert_eq!(ignored.status.code(), Some(0), "{}", stderr(&ignored));
+    assert_eq!(stdout(&ignored), UNFORMATTED);
+    assert_eq!(formatted.status.code(), Some(0), "{}", stderr(&formatted));
+    assert_eq!(stdout(&formatted), "insert into t (a,b)\nvalues\n( 1 , 'x'),\n(22 , 'y');\n");
+
+    let output = project.run(&["--stdin-filepath", "query.sql"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--stdin"), "{}", stderr(&output));
+}
+
+#[test]
+fn file_over_max_file_size_is_skipped_with_a_message() {
+    let project = Project::new("max-file-size");
+    project.file("big.sql", UNFORMATTED).file("small.sql", "SELECT 1;\n");
+
+    let output = project.run(&["--max-file-size", "20", "."]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let printed = format!("{}{}", stdout(&output), stderr(&output));
+    assert!(printed.contains(&format!("big.sql (size {} > limit 20)", UNFORMATTED.len())), "{}", printed);
+    assert_eq!(project.read("big.sql"), UNFORMATTED);
+}
+
+#[test]
+fn statement_over_max_statement_size_is_left_as_written() {
+    let project = Project::new("max-statement-size");
+    project.file("mixed.sql", &format!("select  a,b from t;\n{}", UNFORMATTED));
+
+    let output = project.run(&["--max-statement-size", "30", "mixed.sql"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(stderr(&output).contains("mixed.sql: line 2: statement of 44 bytes is over --max-statement-size"), "{}", stderr(&output));
+    assert!(stdout(&output).contains("1 statement left as written for being over --max-statement-size"), "{}", stdout(&output));
+    assert_eq!(project.read("mixed.sql"), format!("select a, b\nfrom t;\n{}", UNFORMATTED));
+}
+
+#[test]
+fn analyze_reports_counts_and_findings_without_writing() {
+    let project = Project::new("analyze");
+    let sql = "insert into t (a,b) values (1,'x'),(22);\nselect 1;\nupdate t set a = 1\n";
+    project.file("health.sql", sql);
+
+    let output = project.run(&["--analyze", "health.sql"]);
+
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "File        Statements  Semicolons  Balanced  Findings  Kinds\n\
+         health.sql           3           2  yes              1  1 INSERT, 1 SELECT, 1 UPDATE\n\
+         \n\
+         health.sql: line 1: row 2 of the INSERT has 1 values, but its column list has 2\n"
+    );
+    assert_eq!(project.read("health.sql"), sql);
+}
+
+/// Runs git with `args` in the project directory, failing the test if it fails.
+fn git(project: &Project, args: &[&str]) {
+    let output = Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com", "-c", "commit.gpgsign=false"])
+        .args(args)
+        .current_dir(&project.dir)
+        .output()
+        .expect("git runs");
+    assert!(output.status.success(), "git {:?}: {}", args, stderr(&output));
+}
+
+#[test]
+fn changed_and_staged_only_pick_up_files_git_reports() {
+    let project = Project::new("changed");
+    project.file("committed.sql", UNFORMATTED).file("edited.sql", "SELECT 1;\n");
+    git(&project, &["init", "-q"]);
+    git(&project, &["add", "."]);
+    git(&project, &["commit", "-q", "-m", "initial"]);
+    project.file("edited.sql", UNFORMATTED).file("staged.sql", UNFORMATTED).file("db/new.sql", UNFORMATTED).file("notes.txt", UNFORMATTED);
+    git(&project, &["add", "staged.sql"]);
+
+    let staged = project.run(&["--staged", "--check", "--output", "json"]);
+    let changed = project.run(&["--changed", "--check", "--output", "json"]);
+
+    assert_eq!(staged.status.code(), Some(1), "{}", stderr(&staged));
+    assert_eq!(would_reformat(&staged), ["staged.sql"]);
+    assert_eq!(changed.status.code(), Some(1), "{}", stderr(&changed));
+    assert_eq!(would_reformat(&changed), ["db/new.sql", "edited.sql", "staged.sql"]);
+}
+
+#[test]
+fn staged_check_looks_at_the_staged_content() {
+    let project = Project::new("staged-content");
+    project.file("db/staged.sql", UNFORMATTED).file("db/fixed.sql", "SELECT 1;\n");
+    git(&project, &["init", "-q"]);
+    git(&project, &["add", "."]);
+    // Fixed in the working tree only, and broken in the working tree only
+    project.file("db/staged.sql", "SELECT 1;\n").file("db/fixed.sql", UNFORMATTED);
+
+    let output = project.run_in("db", &["--staged", "--check", "--output", "json"]);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    assert_eq!(would_reformat(&output), ["staged.sql"]);
+}
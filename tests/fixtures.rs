@@ -0,0 +1,89 @@
+//! Formats each `tests/fixtures/NAME.sql` with the default options (or those
+//! [`options`] gives it) and compares the result with `NAME.expected.sql`
+//! next to it, then formats that again to check it comes back unchanged:
+//! formatting a formatted file must be a no-op.
+//!
+//! A new case is a pair of files. Write the input, run the formatter on a
+//! copy of it, check the result by eye and save it as the expected output.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sql_fmt::{format_file_content, Alignment, BlankLines, FormatOptions, SourceKind};
+
+/// The options fixture `name` is formatted with; most use the defaults.
+fn options(name: &str) -> FormatOptions {
+    match name {
+        "align_aliases" => FormatOptions { align_aliases: true, ..FormatOptions::default() },
+        "align_constraints" => FormatOptions { align_constraints: true, ..FormatOptions::default() },
+        "align_grants" => FormatOptions { align_grants: true, ..FormatOptions::default() },
+        "align_grants_blank_lines" => FormatOptions { align_grants: true, blank_lines: BlankLines::Max(1), ..FormatOptions::default() },
+        "alter_add_columns" => FormatOptions { align_across_statements: true, ..FormatOptions::default() },
+        "create_table_constraints" => FormatOptions { align_defaults: true, ..FormatOptions::default() },
+        "indent_transactions" => FormatOptions { indent_transactions: true, ..FormatOptions::default() },
+        "insert_value_alignment" => {
+            FormatOptions { boolean_alignment: Alignment::Right, right_align_dates: true, ..FormatOptions::default() }
+        },
+        "merge_inserts_blank_lines" => {
+            FormatOptions { merge_inserts: true, merge_blank_lines: 1, blank_lines: BlankLines::Max(1), ..FormatOptions::default() }
+        },
+        "operator_spacing" => FormatOptions { normalize_operator_spacing: true, ..FormatOptions::default() },
+        "right_align_functions" => {
+            let right_align_functions = ["point", "st_geomfromtext", "interval"].map(String::from).to_vec();
+            FormatOptions { right_align_functions, ..FormatOptions::default() }
+        },
+        "trailing_comments" => FormatOptions { align_trailing_comments: true, ..FormatOptions::default() },
+        "trim_off_region" => FormatOptions { trim_trailing_whitespace: true, ..FormatOptions::default() },
+        _ => FormatOptions::default(),
+    }
+}
+
+fn format(sql: &str, options: &FormatOptions) -> String {
+    match format_file_content(sql, options, SourceKind::Sql, &mut Vec::new(), &mut Vec::new()) {
+        Ok((formatted, _)) => formatted,
+        Err(e) => format!("<error: {}>", e),
+    }
+}
+
+/// The inputs in `tests/fixtures`, in name order.
+fn inputs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+    let mut inputs: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("tests/fixtures exists")
+        .map(|entry| entry.expect("tests/fixtures is readable").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "sql"))
+        .filter(|path| !path.to_string_lossy().ends_with(".expected.sql"))
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+#[test]
+fn fixtures_format_as_expected_and_stay_formatted() {
+    let inputs = inputs();
+    assert!(!inputs.is_empty(), "no fixtures found");
+
+    let mut failures = Vec::new();
+    for input in &inputs {
+        let name = input.file_stem().unwrap().to_string_lossy();
+        let expected_path = input.with_file_name(format!("{}.expected.sql", name));
+        let sql = fs::read_to_string(input).unwrap();
+        let Ok(expected) = fs::read_to_string(&expected_path) else {
+            failures.push(format!("{}: no {}", name, expected_path.display()));
+            continue;
+        };
+
+        let options = options(&name);
+        let formatted = format(&sql, &options);
+        if formatted != expected {
+            failures.push(format!("{}: expected\n{}\n--- but got\n{}", name, expected, formatted));
+            continue;
+        }
+        let again = format(&formatted, &options);
+        if again != formatted {
+            failures.push(format!("{}: not idempotent, a second pass gives\n{}", name, again));
+        }
+    }
+
+    assert!(failures.is_empty(), "{} of {} fixtures failed:\n\n{}", failures.len(), inputs.len(), failures.join("\n\n"));
+}
@@ -0,0 +1,8 @@
+SELECT
+    customer_id,
+    SUM(amount)                                AS total_amount,
+    COUNT(*)                                   AS order_count,
+    COALESCE(MAX(shipped_at), MIN(created_at)) AS last_activity,
+    region
+FROM orders
+GROUP BY customer_id, region;
@@ -0,0 +1 @@
+CREATE TABLE order_items (id BIGINT PRIMARY KEY, order_id BIGINT NOT NULL, product_id INT NOT NULL, warehouse_id INT, CONSTRAINT fk_order FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE, CONSTRAINT fk_product_reference FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE RESTRICT, FOREIGN KEY (warehouse_id) REFERENCES warehouses(id) ON DELETE SET NULL, CHECK (order_id > 0));
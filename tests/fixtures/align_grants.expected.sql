@@ -0,0 +1,10 @@
+-- Grants at most one blank line apart line up; two blank lines start a
+-- new group
+grant select                 on table users  to app_ro;
+GRANT SELECT, INSERT, UPDATE ON TABLE users  TO app_rw;
+
+revoke delete                on table orders from app_rw;
+
+
+GRANT USAGE          ON SCHEMA reporting               TO analyst;
+grant all privileges on all tables in schema reporting to admin;
@@ -0,0 +1,7 @@
+-- With --align-grants --blank-lines 1, grants are grouped by the blank
+-- lines left after capping, so a second run groups them the same way
+GRANT SELECT         ON t         TO a;
+
+GRANT SELECT, INSERT ON table_two TO bbb;
+
+revoke delete        on orders    from bbb;
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN email VARCHAR(255) NOT NULL;
+ALTER TABLE users ADD COLUMN age INT DEFAULT 0;
+-- audit columns
+ALTER TABLE users ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT now(), ADD COLUMN updated_by BIGINT;
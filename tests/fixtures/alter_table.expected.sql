@@ -0,0 +1,7 @@
+ALTER TABLE users
+    ADD COLUMN email   VARCHAR(255)  NOT NULL,
+    ADD COLUMN balance DECIMAL(10,2) DEFAULT 0,
+    DROP COLUMN legacy_id,
+    ADD CONSTRAINT users_age_check CHECK (age IN (1,2)),
+    RENAME COLUMN nick TO nickname;
+ALTER TABLE orders ALTER COLUMN status SET NOT NULL;
@@ -0,0 +1,15 @@
+-- MySQL backtick identifiers are names, even with commas or parentheses in them
+INSERT INTO `order items` (`id`, `qty, boxed`, `price (eur)`)
+VALUES
+( 1 ,  2 ,  9.99),
+(22 , 10 , 120.5);
+
+CREATE TABLE `order items` (
+    `id`          INT           NOT NULL,
+    `qty, boxed`  INT           DEFAULT 1,
+    `price (eur)` DECIMAL(10,2)
+) ENGINE=InnoDB;
+
+SELECT `qty, boxed`, `price (eur)`
+FROM `order items`
+WHERE `id` = 1;
@@ -0,0 +1,27 @@
+-- Seed data in sections: the blank lines between groups are kept as they are
+
+-- users
+insert into users (id,name)
+values
+( 1 , 'alice'),
+(22 , 'bob');
+insert into users (id,name)
+values
+( 3 , 'carol'),
+(44 , 'dave');
+
+
+-- roles
+insert into roles (id,name)
+values
+( 1 , 'admin'),
+(22 , 'viewer');
+
+
+
+
+-- grants
+grant select on users to viewer;
+update users
+set name = 'root'
+where id=1;
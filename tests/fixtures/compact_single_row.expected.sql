@@ -0,0 +1,14 @@
+-- A single row that fits goes on one line, also when it was a grid before
+INSERT INTO settings (key, value) VALUES ('theme', 'dark');
+insert into settings (key,value) values ('lang', 'en');
+
+-- Too wide for one line, so it keeps the grid
+insert into audit_log (id, actor, action, target, details, created_at)
+values
+(1 , 'admin@example.com' , 'update' , 'settings' , 'changed the default theme to dark' , '2024-01-01 10:00:00');
+
+-- More than one row always makes a grid
+insert into settings (key, value)
+values
+('a'  , '1'),
+('bb' , '22');
@@ -0,0 +1,19 @@
+-- The rows of COPY ... FROM stdin are data, not SQL: tabs, \N, backslash
+-- escapes, semicolons and trailing spaces all stay byte for byte
+create table users (
+    id   integer,
+    name text,
+    note text
+);
+
+COPY public.users (id, name, note) FROM stdin;
+1	Ada	\N
+2	O'Brien, Jr.	semi; colon  
+3	  padded  	tab\tinside\\back
+4	insert into x values (1,2);	-- not a comment
+\.
+
+insert into users (id,name,note)
+values
+( 5 , 'Eve'     , null),
+(66 , 'Mallory' , 'x');
@@ -0,0 +1,3 @@
+create unique index concurrently if not exists idx_orders_customer_region_status_lower on orders using btree (customer_id, region_code, LOWER(status), COALESCE(shipped_at, created_at) DESC, fulfillment_center_id) include (total_amount, currency) where deleted_at IS NULL;
+CREATE INDEX idx_orders_created ON orders (created_at) WHERE deleted_at IS NULL;
+CREATE INDEX idx_users_email_lower ON users USING gin (LOWER(email)) WITH (fillfactor = 70);
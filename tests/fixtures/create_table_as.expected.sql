@@ -0,0 +1,10 @@
+CREATE TABLE report_2024 AS
+    SELECT customer_id, SUM(total) AS revenue
+    FROM orders
+    WHERE created_at >= '2024-01-01'
+    GROUP BY customer_id;
+CREATE TEMPORARY TABLE IF NOT EXISTS recent_orders AS
+    SELECT *
+    FROM orders
+    WHERE created_at > now() - interval '7 days'
+WITH NO DATA;
@@ -0,0 +1,14 @@
+-- Commas inside types, DEFAULT calls, ENUMs and CHECK expressions never split
+-- a definition
+create table orders (
+    id          bigint                                           not null,
+    amount      decimal(10,2)                                    not null default 0.00,
+    tax         numeric(12, 4),
+    status      enum('new','paid','shipped, partly','cancelled') default 'new',
+    placed_at   timestamp                                        default coalesce(null, now()),
+    customer_id bigint,
+    store_id    int,
+    unique (customer_id, store_id, placed_at),
+    check ((amount > 0 and (tax is null or tax >= 0)) or status in ('cancelled', 'new')),
+    constraint positive_total check (amount + coalesce(tax, 0) > 0)
+);
@@ -0,0 +1 @@
+CREATE TABLE accounts (id BIGINT PRIMARY KEY, owner_id INT NOT NULL REFERENCES users(id), balance DECIMAL(12,2) NOT NULL DEFAULT 0, currency CHAR(3) DEFAULT 'EUR', opened_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(), note TEXT DEFAULT '', CONSTRAINT accounts_balance_check CHECK (balance >= 0));
@@ -0,0 +1,11 @@
+-- Multi-word types and arrays stay whole in the type column
+CREATE TABLE measurements (
+  id BIGINT UNSIGNED NOT NULL,
+  ratio DOUBLE PRECISION DEFAULT 0,
+  label CHARACTER VARYING(100) NOT NULL,
+  taken_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+  local_at timestamp without time zone,
+  count INT UNSIGNED,
+  tags TEXT[],
+  readings NUMERIC(12,4)[] NOT NULL
+);
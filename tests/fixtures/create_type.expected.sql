@@ -0,0 +1,13 @@
+CREATE TYPE order_status AS ENUM (
+    'pending',
+    'paid',
+    'shipped, partially',
+    'cancelled',
+    'refunded'
+);
+CREATE TYPE flag AS ENUM ('on', 'off');
+CREATE TYPE address AS (
+    street      VARCHAR(200),
+    city        TEXT,
+    postal_code CHAR(10)
+);
@@ -0,0 +1,17 @@
+-- The query of a view is laid out like any other SELECT, under the CREATE line
+create or replace view active_customers as
+    select c.id, c.name, count(o.id) as orders
+    from customers c
+        left join orders o on o.customer_id = c.id
+    where c.active = true and c.deleted_at is null
+    group by c.id, c.name;
+
+create view recent_orders (id, placed_at) as
+    with
+        recent as (
+            select id, placed_at
+            from orders
+            where placed_at > now() - interval '7 days'
+        )
+    select id, placed_at
+    from recent;
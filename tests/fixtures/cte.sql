@@ -0,0 +1,7 @@
+-- The CTEs go one level in under WITH, each closing parenthesis lines up
+-- with its name, and each CTE's query is indented under that
+with recent as (select id, customer_id from orders where created_at > now() - interval '7 days'), totals as (select customer_id, count(*) as n from recent group by customer_id) select c.name, t.n from customers c join totals t on t.customer_id = c.id order by t.n desc;
+
+WITH RECURSIVE tree AS (SELECT id, parent_id, 1 AS depth FROM nodes WHERE parent_id IS NULL UNION ALL SELECT n.id, n.parent_id, t.depth + 1 FROM nodes n JOIN tree t ON n.parent_id = t.id) SELECT id, depth FROM tree;
+
+with a (x) as materialized (select 1 as x from t), b as not materialized (select x from a), c as (select x, count(*) as n from b group by x) select * from c;
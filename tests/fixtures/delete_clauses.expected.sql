@@ -0,0 +1,13 @@
+DELETE FROM sessions
+USING users
+WHERE sessions.user_id = users.id AND users.banned;
+DELETE t1 FROM t1
+    JOIN t2 ON t2.id = t1.parent_id
+WHERE t2.obsolete = 1;
+DELETE FROM logs
+WHERE created < '2020-01-01'
+ORDER BY created
+LIMIT 1000;
+DELETE FROM jobs
+WHERE state = 'done'
+RETURNING id;
@@ -0,0 +1,21 @@
+-- Statements between off and on, or right after skip, stay as written
+insert into t (a,b) values (1,'x'),(22,'y');
+
+-- sql-fmt: off
+insert into t (a,b) values (1,'x'),(22,'y');
+select a,b from t where a=1;
+-- sql-fmt: on
+
+insert into t (a,b) values (1,'x'),(22,'y');
+
+--SQL-FMT:SKIP
+insert into t (a,b) values (1,'x'),(22,'y');
+insert into t (a,b) values (1,'x'),(22,'y');
+
+--   Sql-Fmt:   Off
+update t set a=1,b='z' where a=22;
+--sql-fmt:ON
+update t set a=1,b='z' where a=22;
+
+-- sql-fmt: off
+create table kept (id int,name text);
@@ -0,0 +1,11 @@
+SELECT DISTINCT ON (user_id) user_id, created_at
+FROM events
+ORDER BY user_id, created_at DESC
+FOR UPDATE SKIP LOCKED;
+SELECT DISTINCT region
+FROM orders;
+SELECT id
+FROM jobs
+WHERE state = 'queued'
+LIMIT 5
+FOR SHARE OF jobs NOWAIT;
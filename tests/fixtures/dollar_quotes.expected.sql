@@ -0,0 +1,15 @@
+-- A dollar-quoted string is one value, whatever it contains: commas, quotes
+-- and parentheses such as '), inside it split nothing
+insert into snippets (id, body, note)
+values
+( 1 , $$can contain , and ' and ) freely$$ , 'plain'),
+(22 , $$ends a row? no: '), (3, 'x$$       , $tag$nested $$ and '),$tag$);
+
+select $$a, b$$ as pair, $q$'),$q$ as tricky, id
+from snippets;
+
+create table templates (
+    id   integer,
+    body text    default $$hello, ('world')$$,
+    note text
+);
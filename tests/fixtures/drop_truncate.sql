@@ -0,0 +1,7 @@
+-- Up to three names stay on one line; more go one per line, with the
+-- modifiers on a line of their own after them
+drop table if exists users,orders   cascade;
+TRUNCATE TABLE a, b, c, d, e RESTART IDENTITY;
+truncate only audit_log, events * continue identity restrict;
+DROP VIEW IF EXISTS v1, v2, v3, v4 CASCADE;
+drop index concurrently if exists idx_users_email,idx_users_name,idx_orders_user,idx_orders_created;
@@ -0,0 +1,9 @@
+-- Foreign key clauses stay with their column or constraint
+create table orders (
+    id          bigint primary key,
+    customer_id bigint not null references customers (id) on delete cascade,
+    store_id    int    references stores (id) on update cascade on delete set null,
+    note        text,
+    constraint orders_coupon_fk foreign key (coupon_id, store_id) references coupons (id, store_id) on delete restrict on update cascade,
+    coupon_id   int
+);
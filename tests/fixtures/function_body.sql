@@ -0,0 +1,14 @@
+-- Function bodies are left as written, even the INSERTs inside them; the
+-- statements around the function are formatted
+insert into log (id, msg) values (1,'before'),(22,'x');
+
+create or replace function seed_users() returns void as $$
+begin
+  insert into users (id,name) values (1,'a, b'),(2,'c');
+  insert into audit (what) values ('seeded; twice');
+end;
+$$ language plpgsql;
+
+create function tagged() returns int language sql as $body$ insert into t (a,b) values (1,2),(3,4) returning a; $body$;
+
+insert into log (id, msg) values (3,'after'),(44,'y');
@@ -0,0 +1,16 @@
+DELETE FROM users
+WHERE id IN (
+    1,  2,  3,  4,  5,  6,  7,  8,  9,  10,
+    11, 12, 13, 14, 15, 16, 17, 18, 19, 20,
+    21, 22, 23, 24, 25
+);
+SELECT id
+FROM tags
+WHERE name IN (
+    'a,b', func(1,2), 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j',
+    'k',   'l',       'm', 'n', 'o', 'p', 'q', 'r', 's', 't',
+    'u'
+);
+SELECT id
+FROM tags
+WHERE id IN (1, 2, 3);
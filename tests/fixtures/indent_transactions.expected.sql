@@ -0,0 +1,22 @@
+-- With --indent-transactions the statements of a transaction move one
+-- level in; a BEGIN inside one adds no level
+BEGIN;
+    insert into t (a,b)
+    values
+    ( 1 , 'x'),
+    (22 , 'y');
+    BEGIN;
+    update t
+    set b = 'z'
+    where a=1;
+COMMIT;
+
+start transaction;
+    delete from t
+    where a = 22;
+    SAVEPOINT before_copy;
+    insert into t (a,b) values (3, 'w');
+    ROLLBACK TO before_copy;
+rollback;
+
+select 1;
@@ -0,0 +1,6 @@
+-- Padding left over from an earlier layout is measured afresh
+INSERT INTO users (id, name, email, active)
+VALUES
+(  1 , 'alice' , 'alice@example.com' , true),
+( 22 , 'bob'   , 'bob@example.com'   , false),
+(333 , 'carol' , NULL                , true);
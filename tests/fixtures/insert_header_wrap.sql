@@ -0,0 +1,3 @@
+-- A column list too wide for one line wraps onto indented lines; the comma
+-- inside the quoted identifier is part of its name
+insert into customer_accounts (account_id, customer_name, billing_address_line_one, billing_address_line_two, "city, state", postal_code, created_at) values (1, 'Acme', '1 Main St', '', 'Springfield, IL', '62701', '2024-01-01'), (22, 'Globex', '2 Oak Ave', 'Suite 5', 'Shelbyville, IL', '62565', '2024-02-15');
@@ -0,0 +1,2 @@
+INSERT INTO archive_orders (id, total) SELECT id, total FROM orders WHERE created < now() - interval '1 year';
+INSERT INTO archive_orders SELECT * FROM orders WHERE status = 'cancelled';
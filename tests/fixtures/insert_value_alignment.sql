@@ -0,0 +1,4 @@
+INSERT INTO accounts (id, balance, active, opened_on, note) VALUES
+(1, 100, TRUE, '2024-01-01', 'first'),
+(22, NULL, FALSE, NULL, NULL),
+(333, 4500, TRUE, '2023-06-15', 'third');
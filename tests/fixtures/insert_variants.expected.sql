@@ -0,0 +1,17 @@
+INSERT INTO audit_log
+VALUES
+( 1 , 'x'      , now()),
+(22 , 'longer' , now());
+INSERT INTO counters DEFAULT VALUES;
+REPLACE INTO settings (key, value)
+VALUES
+('theme'    , 'dark'),
+('language' , 'en');
+INSERT IGNORE INTO tags (id, name)
+VALUES
+(  1 , 'a'),
+(100 , 'bb');
+INSERT OR REPLACE INTO cache (k, v)
+VALUES
+('a'   ,  1),
+('bbb' , 22);
@@ -0,0 +1,14 @@
+-- A tag inside a statement counts as a comment there, so the statement is
+-- left exactly as written; the statements around it are still formatted
+select id,name,{% if var('with_total') %}total,{% endif %}status from {{ ref('orders') }} where status = 'paid';
+insert into seeds (id,region) values (1,'eu'),{% if target.name == 'dev' %}(22,'us'),{% endif %}(333,'apac');
+
+{% if target.name == 'dev' %}
+insert into seeds (id,region)
+values
+(4444 , 'test'),
+(   5 , 'qa');
+{% endif %}
+select id, name
+from {{ ref('customers') }}
+where region = 'eu';
@@ -0,0 +1,11 @@
+{{ config(materialized='table', tags=['daily']) }}
+
+-- A dbt model: expressions are single names and values, tags and raw
+-- blocks are left exactly as written
+select o.id,o.total,c.name from {{ ref('orders') }} o join {{ source('crm', 'customers') }} c on c.id = o.customer_id where o.created_at >= '{{ var("start_date") }}';
+
+{% raw %}
+SELECT   {{ not_a_template }},x  FROM   y ;
+{% endraw %}
+
+insert into seeds (id,region,amount) values (1,{{ var('region') }},10),(22,'eu',{{ var('amount', 300) }});
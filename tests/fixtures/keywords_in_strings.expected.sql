@@ -0,0 +1,16 @@
+-- Keywords and commas inside strings are data: 'a, FROM b' neither splits a
+-- value nor starts a clause
+select 'a, FROM b' as odd, 'WHERE, x' as odder, id
+from t
+where note = 'ORDER BY 1, 2' and id > 0;
+
+insert into notes (id, body)
+values
+( 1 , 'a, FROM b'),
+(22 , 'values (1, 2); select');
+
+update notes
+set
+    body = 'SET x = 1, WHERE y',
+    id   = 3
+where body = 'a, FROM b';
@@ -0,0 +1,6 @@
+SELECT id FROM users ORDER BY id LIMIT $1 OFFSET $2;
+SELECT id FROM users ORDER BY id LIMIT ?;
+SELECT id FROM users ORDER BY id LIMIT :page_size OFFSET :page_start;
+SELECT id FROM users ORDER BY id LIMIT 20,10;
+SELECT id FROM users ORDER BY id LIMIT ALL;
+SELECT id FROM users ORDER BY id OFFSET 10 ROWS FETCH FIRST 10 ROWS ONLY;
@@ -0,0 +1,2 @@
+-- MERGE puts each WHEN branch on its own line, with its assignments aligned
+merge into inventory i using incoming n on i.sku = n.sku when matched and n.qty = 0 then delete when matched then update set qty = n.qty, updated_at = now() when not matched then insert (sku, qty) values (n.sku, n.qty);
@@ -0,0 +1,7 @@
+-- With --merge-inserts --merge-blank-lines 1 --blank-lines 1, INSERTs are
+-- merged across the blank lines left after capping
+insert into t (a,b)
+values
+(  1 , 'x'),
+( 22 , 'y'),
+(333 , 'z');
@@ -0,0 +1,8 @@
+-- With --merge-inserts --merge-blank-lines 1 --blank-lines 1, INSERTs are
+-- merged across the blank lines left after capping
+insert into t (a,b) values (1,'x');
+
+
+insert into t (a,b) values (22,'y');
+
+insert into t (a,b) values (333,'z');
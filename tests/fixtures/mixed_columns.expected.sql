@@ -0,0 +1,8 @@
+-- Numbers right-align and text left-aligns, column by column, whatever the
+-- mix of widths, NULLs, negatives, decimals and function calls
+insert into measurements (id, label, reading, delta, taken_at, verified, source)
+values
+(   1 , 'a'                   ,       3.5 ,    -2 , '2024-01-01'          , true  , null),
+(1000 , 'a much longer label' , 12345.678 ,     0 , '2024-12-31 23:59:59' , false , 'sensor-7'),
+(  42 , ''                    ,     -0.25 ,    17 , now()                 , null  , upper('manual')),
+(   7 , null                  ,         0 , -1000 , null                  , true  , '');
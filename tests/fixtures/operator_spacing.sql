@@ -0,0 +1,7 @@
+-- With --normalize-operator-spacing each operator gets one space either
+-- side, but strings, casts and JSON operators are left alone
+select a,b from t join u on u.id=t.u_id and u.kind<>'x' where a>=1 and b  <  2 or c!='a=b' and d||e like 'f%' and g not  in (1,2) and h between  1 and  5 and meta->>'k'='v' and n::int<=3;
+
+update t set a=1,b  =  b||'x' where id=1;
+
+select kind, count(*) from t group by kind having count(*)>1;
@@ -0,0 +1,8 @@
+-- Bind placeholders are values like any other: $1, ? and :name
+insert into users (id, name, email) values ($1, $2, $3), ($10, $11, $12);
+
+insert into users (id, name, email) values (?, ?, ?), (?, 'fixed', ?);
+
+insert into users (id, name, email) values (:id, :name, :email), (:other_id, :other_name, null);
+
+update users set name = $1, email = :email where id = ?;
@@ -0,0 +1,25 @@
+-- A psql script: meta-commands pass through as written and never join the
+-- statements around them, and COPY data stays byte for byte
+\set ON_ERROR_STOP on
+\set schema 'billing'
+\i setup/extensions.sql
+
+create table invoices (
+    id       integer,
+    customer text,
+    amount   numeric(10,2)
+);
+\echo loading invoices
+COPY invoices (id, customer, amount) FROM stdin;
+1	Acme, Inc.	12.50
+2	\N	-3
+\.
+\copy invoices (id,customer,amount) from 'extra.csv' with (format csv)
+insert into invoices (id,customer,amount)
+values
+( 3 , 'Globex'  ,  100),
+(44 , 'Initech' , 7.25);
+\ir local.sql
+select count(*), sum(amount)
+from invoices
+where amount>0;
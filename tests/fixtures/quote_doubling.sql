@@ -0,0 +1,3 @@
+-- A doubled quote is part of the string, not its end
+insert into people (id, name, note) values (1, 'O''Brien', 'it''s, fine'), (22, 'Smith', 'ends in a quote'''), (3, '', 'x');
+update people set name = 'O''Neil', note = 'it''s, fine' where name = 'O''Brien, Jr.';
@@ -0,0 +1,21 @@
+-- Quoted, schema-qualified names keep their quotes and case
+insert into "public"."Users" ("Id", "Full Name", "e-mail")
+values
+( 1 , 'Ada'   , 'ada@example.com'),
+(22 , 'Grace' , 'grace@example.com');
+
+create table "public"."Users" (
+    "Id"        integer      primary key,
+    "Full Name" text         not null,
+    "e-mail"    varchar(255)
+);
+
+update "public"."Users"
+set
+    "Full Name" = 'Ada L.',
+    "e-mail"    = null
+where "Id" = 1;
+
+select u."Id", u."Full Name"
+from "public"."Users" u
+    join "audit"."Log" l on l."UserId" = u."Id";
@@ -0,0 +1,4 @@
+INSERT INTO places (id, name, location, retention)
+VALUES
+(1 , 'Depot'           ,                     POINT(1, 2) ,   INTERVAL '1 day'),
+(2 , 'Warehouse north' , ST_GeomFromText('POINT(10 20)') , INTERVAL '30 days');
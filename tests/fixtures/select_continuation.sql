@@ -0,0 +1,8 @@
+select u.id, u.name, count(o.id) as orders
+        from users u
+            left join orders o on o.user_id = u.id
+    where u.active = true
+          and u.created_at > '2024-01-01'
+          and o.status in ('open', 'shipped')
+  group by u.id, u.name
+        order by orders desc;
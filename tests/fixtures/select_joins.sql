@@ -0,0 +1,5 @@
+-- Each join goes on its own line under FROM with its condition, AND
+-- included, and aliases are kept as written
+select a.id, b.name, c.total from accounts a join branches b on b.id = a.b_id and b.active join charges AS c on c.account_id = a.id left outer join notes n using (account_id) where a.closed_at is null;
+
+SELECT o.id, c.name, s.label FROM orders o INNER JOIN customers c ON c.id = o.customer_id AND c.region = o.region AND c.deleted_at IS NULL AND c.status <> 'blocked' RIGHT JOIN shipments s ON s.order_id = o.id CROSS JOIN settings;
@@ -0,0 +1,3 @@
+-- Only the top-level commas separate select list items: not the ones in
+-- function arguments, string literals or a CAST's type
+select coalesce(nickname, first_name, 'friend') as greeting, count(*), round(sum(coalesce(price, 0) * quantity), 2) as total, 'Smith, Jr.' as suffix, cast(amount as decimal(10,2)) as amount from orders group by coalesce(nickname, first_name, 'friend'), suffix order by round(sum(coalesce(price, 0) * quantity), 2) desc, greeting;
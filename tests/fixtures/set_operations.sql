@@ -0,0 +1,2 @@
+SELECT a FROM t1 UNION ALL SELECT a FROM t2 ORDER BY a;
+(SELECT id, name FROM customers WHERE active = TRUE) INTERSECT (SELECT id, name FROM vip_customers) EXCEPT SELECT id, name FROM banned LIMIT 10;
@@ -0,0 +1,8 @@
+-- Rows that don't match the column list (warned about, or an error with
+-- --strict) line up as far as their values go: a short row ends early and a
+-- long row's extra value trails after the grid
+insert into t (a, b, c)
+values
+(  1 , 'xx'  ,    3),
+( 22 , 'y'),
+(333 , 'zzz' , 4444 , 5);
@@ -0,0 +1,7 @@
+-- JSON literals, ARRAY[...] and casts are single values; their commas and
+-- brackets never split a cell
+insert into docs (id, meta, tags, scores)
+values
+( 1 , '{"a": 1, "b": [1, 2]}'::jsonb , array['x', 'y, z'] , '{1,2}'::int[]),
+(22 , '{}'::jsonb                    , array[]::text[]    , '{10,20,30}'::int[]),
+( 3 , null                           , ARRAY['only']      , cast('{4}' as int[]));
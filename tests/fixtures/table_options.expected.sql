@@ -0,0 +1,19 @@
+-- Table options after the closing parenthesis stay on its line, as written
+create table sessions (
+    id      bigint not null auto_increment,
+    user_id bigint,
+    data    json,
+    primary key (id)
+) engine=InnoDB default charset=utf8mb4 collate=utf8mb4_unicode_ci;
+
+create table events (
+    id         bigint,
+    kind       text,
+    created_at timestamptz not null
+) with (fillfactor=70);
+
+create table measurements (
+    city_id  int  not null,
+    logdate  date not null,
+    peaktemp int
+) partition by range (logdate);
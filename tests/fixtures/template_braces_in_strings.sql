@@ -0,0 +1,6 @@
+-- Template braces inside strings and comments are SQL text, not template
+-- syntax, so the statements around them are still formatted
+SELECT '{%' AS a, b FROM t; SELECT c,d FROM u WHERE x = '%}';
+
+-- a {{ in a comment doesn't open an expression
+insert into notes (id,body) values (1,'{{ not a template'),(22,'#}');
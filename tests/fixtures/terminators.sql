@@ -0,0 +1,11 @@
+-- Each statement keeps the terminator and the blank lines it had
+insert into t (a, b) values (1, 'x'), (22, 'y');
+
+
+update t set b = 'z' where a = 1;
+
+
+select a, b from t where a = 22
+;
+
+insert into t (a, b) values (3, 'w'), (44, 'v')
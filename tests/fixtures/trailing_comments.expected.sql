@@ -0,0 +1,13 @@
+-- With --align-trailing-comments the comments at the ends of INSERT rows
+-- and column definitions line up
+insert into users (id,name,role)
+values
+(  1 , 'alice' , 'admin'),   -- the first user
+( 22 , 'bob'   , 'member'),  -- invited by alice
+(333 , 'carol' , 'member');
+
+create table users (
+    id   bigint       primary key,      -- surrogate key
+    name varchar(100) not null,         -- display name
+    role text         default 'member'  -- one of admin, member
+);
@@ -0,0 +1,14 @@
+-- Trailing spaces go, except on the lines sql-fmt leaves alone
+select a,   
+  b from t;   
+
+-- sql-fmt: off
+select a,   
+  b from t;   
+-- a note   
+insert into t (a) values (1),	
+  (2);  
+-- sql-fmt: on
+
+update t set a = 1   
+where b = 2;  
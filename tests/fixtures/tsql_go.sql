@@ -0,0 +1,10 @@
+-- GO ends a batch, so a script without semicolons still splits into its
+-- statements; the GO lines stay as written
+create table dbo.users (id int not null,name nvarchar(100),created datetime2 default sysdatetime())
+GO
+insert into dbo.users (id,name) values (1,N'alice'),(22,N'bob')
+go
+update dbo.users set name=N'carol' where id=1
+GO 5
+select id,name from dbo.users where id>0
+GO
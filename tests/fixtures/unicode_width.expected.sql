@@ -0,0 +1,20 @@
+-- Columns line up by display width: accented Latin is one column a
+-- character, CJK and emoji are two
+insert into cities (id, name, country, note)
+values
+(1 , 'München' , 'Deutschland' , 'ü'),
+(2 , '東京'    , '日本'        , '🎉 party'),
+(3 , 'Zoë'     , 'France'      , '-');
+
+create table 名前 (
+    id          integer     not null,
+    表示名      varchar(40),
+    emoji_label text        default '🚀'
+);
+
+update cities
+set
+    name = 'Köln',
+    国   = '日本',
+    note = '🎉'
+where id = 1;
@@ -0,0 +1,6 @@
+update accounts
+set
+    balance    = balance - 100,
+    updated_at = now(),
+    note       = 'monthly fee'
+where id = 42;
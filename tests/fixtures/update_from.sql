@@ -0,0 +1,2 @@
+UPDATE orders SET status='x', updated_at = now() FROM customers WHERE customers.id = orders.customer_id AND customers.banned RETURNING *;
+UPDATE orders o JOIN customers c ON c.id = o.customer_id LEFT JOIN regions r ON r.id = c.region_id SET o.region = r.name, o.discount = COALESCE(c.discount, 0);
@@ -0,0 +1,3 @@
+-- Commas inside function calls, strings and CASE values never split an
+-- assignment
+update places set location = point(12.5, -3.25), name = coalesce(display_name, legal_name, 'unknown'), meta = '{"tags": ["a", "b"], "size": 3}', tier = case when visits > 100 then 'gold' when visits > 10 then 'silver' else 'bronze' end where id in (1, 2, 3);
@@ -0,0 +1,9 @@
+-- Assignments are split at their first top-level `=` and aligned on it,
+-- whatever spacing they were written with
+update settings
+set
+    a     = 1,
+    b     = 2,
+    label = '{"a=b"}',
+    flags = flags | 2
+where id >= 10;
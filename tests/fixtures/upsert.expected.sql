@@ -0,0 +1,19 @@
+-- Upserts keep their grid, with the conflict clause and its SET assignments
+-- aligned underneath
+insert into inventory (sku, qty, updated_at)
+values
+('A-1'  ,  5 , now()),
+('B-22' , 12 , now())
+on conflict (sku) do update set
+    qty        = inventory.qty + excluded.qty,
+    updated_at = excluded.updated_at;
+
+insert into inventory (sku, qty) values ('C-3', 1) on conflict do nothing;
+
+insert into inventory (sku, qty)
+values
+('A-1'  ,  5),
+('B-22' , 12)
+on duplicate key update
+    qty     = qty + values(qty),
+    touched = 1;
@@ -0,0 +1,14 @@
+-- The row list ends at its last parenthesis, not at the first semicolon
+insert into notes (id, body)
+values
+( 1 , 'end;'),
+(22 , 'a; b; c');
+insert into notes (id, body)
+values
+( 3 , 'x'),
+(44 , 'yy')
+returning *;
+insert into notes (id, body)
+values
+( 5 , 'no semicolon;'),
+(66 , 'at the end')
\ No newline at end of file
@@ -0,0 +1,24 @@
+SELECT id, name
+FROM orders
+WHERE status = 'shipped and paid'
+  AND created_at BETWEEN '2024-01-01' AND '2024-12-31'
+  AND (region = 'eu' OR region = 'us')
+   OR priority > 5;
+SELECT region, COUNT(*)
+FROM orders
+GROUP BY region
+HAVING COUNT(*) > 10
+   AND SUM(total) > 1000
+   AND MAX(total) < 50000;
+UPDATE orders
+SET status = 'archived'
+WHERE created_at < '2020-01-01'
+  AND status = 'shipped'
+  AND archived_at IS NULL;
+DELETE FROM sessions
+WHERE expires_at < NOW()
+   OR revoked = TRUE
+   OR user_id IS NULL;
+SELECT id
+FROM t
+WHERE a = 1 AND b = 2;
@@ -0,0 +1,14 @@
+SELECT
+    customer_id,
+    ROW_NUMBER() OVER (
+        PARTITION BY customer_id, region_code
+        ORDER BY created_at DESC, order_id
+        ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+    ) AS rn,
+    SUM(total) OVER w AS running_total
+FROM orders
+GROUP BY customer_id, region_code, created_at, order_id, total
+WINDOW w AS (PARTITION BY customer_id ORDER BY created_at)
+ORDER BY customer_id;
+SELECT id, RANK() OVER (ORDER BY score DESC) AS position
+FROM players;
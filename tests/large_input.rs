@@ -0,0 +1,46 @@
+//! Formats a generated script of 10,000 statements, the size
+//! `benches/format.rs` times, and checks the result is still right at that
+//! size: the same tokens, no statement given up on, and a second pass that
+//! changes nothing.
+
+use std::fmt::Write;
+
+use sql_fmt::{format_file_content, verify_idempotent, FormatOptions, SourceKind};
+
+/// `count` statements of the kinds a hand-written script mixes, several of
+/// them multi-line once formatted.
+fn script(count: usize) -> String {
+    let mut sql = String::new();
+    for i in 0..count {
+        let _ = match i % 8 {
+            0 => writeln!(sql, "insert into users (id,name,email) values ({},'user{}','user{}@example.com'),({},'o''brien, {}',null);", i, i, i, i * 7, i),
+            1 => writeln!(sql, "select id,name,coalesce(nickname,name) as shown from users where id={} and name in (select name from banned where reason<>'spam, {}') order by name;", i, i),
+            2 => writeln!(sql, "update users set name='renamed{}',updated_at=now(),meta='{{\"a\": [1, 2]}}' where id={};", i, i),
+            3 => writeln!(sql, "delete from sessions where user_id={} and expires_at<now();", i),
+            4 => writeln!(sql, "create table t{} (id integer primary key,price decimal(10,2) not null,label varchar(40) default 'a, b');", i),
+            5 => writeln!(sql, "-- step {}\nalter table t{} add column note text, add column seen boolean default false;", i, i),
+            6 => writeln!(sql, "insert into counters (id, n) values ({}, {}) on conflict (id) do update set n = counters.n + excluded.n;", i, i % 97),
+            _ => writeln!(sql, "\ngrant select, insert on t{} to reporting;", i),
+        };
+    }
+    sql
+}
+
+fn without_whitespace(sql: &str) -> String {
+    sql.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[test]
+fn ten_thousand_statements_keep_their_tokens_and_stay_formatted() {
+    let sql = script(10_000);
+    let options = FormatOptions::default();
+    let mut incidents = Vec::new();
+
+    let (formatted, counts) = format_file_content(&sql, &options, SourceKind::Sql, &mut Vec::new(), &mut incidents).expect("the script formats");
+
+    assert!(incidents.is_empty(), "{} statements left as written, the first: {}", incidents.len(), incidents[0].error);
+    assert!(counts.insert > 0 && counts.select > 0 && counts.create > 0, "too little was formatted to tell");
+    assert_ne!(formatted, sql);
+    assert!(without_whitespace(&formatted) == without_whitespace(&sql), "the formatted script has other tokens");
+    verify_idempotent(&formatted, &options, SourceKind::Sql).expect("a second pass changes nothing");
+}